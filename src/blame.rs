@@ -0,0 +1,115 @@
+//! Line authorship from `git blame`, used by `--author-lines` to keep cleanup
+//! changes scoped to one author's lines.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Runs `git blame` on `path` and marks every line last changed by `author`.
+///
+/// The blame covers the working tree file, so the flags line up with its
+/// current `str::lines`. Uncommitted lines belong to no author.
+pub fn authored_lines(path: &Path, author: &str) -> io::Result<Vec<bool>> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["blame", "--line-porcelain", "--"])
+        .arg(name)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git blame exited with {}",
+            output.status
+        )));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Ok(parse_line_porcelain(&text, author))
+}
+
+/// Maps `git blame --line-porcelain` output to one flag per line. `author`
+/// matches either the author name or the email, with or without `<>`.
+fn parse_line_porcelain(output: &str, author: &str) -> Vec<bool> {
+    let mut lines = Vec::new();
+    let mut name = "";
+    let mut mail = "";
+    for entry in output.lines() {
+        if entry.starts_with('\t') {
+            let mail_matches = mail == author
+                || mail.strip_prefix('<').and_then(|m| m.strip_suffix('>')) == Some(author);
+            lines.push(name == author || mail_matches);
+        } else if let Some(value) = entry.strip_prefix("author-mail ") {
+            mail = value;
+        } else if let Some(value) = entry.strip_prefix("author ") {
+            name = value;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLAME: &str = "\
+1111111111111111111111111111111111111111 1 1 2
+author Ada Lovelace
+author-mail <ada@example.com>
+author-time 1700000000
+summary first
+filename run.sh
+\techo one
+1111111111111111111111111111111111111111 2 2
+author Ada Lovelace
+author-mail <ada@example.com>
+summary first
+filename run.sh
+\techo two
+2222222222222222222222222222222222222222 3 3 1
+author Grace Hopper
+author-mail <grace@example.com>
+summary second
+filename run.sh
+\techo three
+0000000000000000000000000000000000000000 4 4 1
+author Not Committed Yet
+author-mail <not.committed.yet>
+summary Version of run.sh from run.sh
+filename run.sh
+\techo four
+";
+
+    #[test]
+    fn test_blame_matches_author_name() {
+        assert_eq!(
+            parse_line_porcelain(BLAME, "Ada Lovelace"),
+            vec![true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_blame_matches_author_email() {
+        assert_eq!(
+            parse_line_porcelain(BLAME, "grace@example.com"),
+            vec![false, false, true, false]
+        );
+        assert_eq!(
+            parse_line_porcelain(BLAME, "<grace@example.com>"),
+            vec![false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_blame_unknown_author_matches_nothing() {
+        assert_eq!(parse_line_porcelain(BLAME, "Ada"), vec![false; 4]);
+    }
+}
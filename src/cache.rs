@@ -0,0 +1,109 @@
+//! Persistent set of content hashes known to need no trimming.
+//!
+//! Keys are FNV-1a hashes rather than `DefaultHasher` output, whose algorithm
+//! may change between Rust releases and would silently invalidate the file.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+const HEADER: &str = "# rtrim hash cache v1";
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hashes `content` together with `tag`, which must capture every setting
+/// that changes how the content is trimmed.
+pub fn content_key(tag: &str, content: &[u8]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    // The length prefix keeps "ab" + "c" and "a" + "bc" apart.
+    let tag_len = (tag.len() as u64).to_le_bytes();
+    for &byte in tag_len.iter().chain(tag.as_bytes()).chain(content) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Loads the cache; a missing file is an empty cache. Unrecognized files are
+/// rejected so a wrong path is never overwritten.
+pub fn load(path: &Path) -> io::Result<HashSet<u64>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+        Err(e) => return Err(e),
+    };
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} is not an rtrim hash cache", path),
+        ));
+    }
+    lines
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            u64::from_str_radix(line, 16).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid hash cache entry {:?}", line),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Writes the cache through a temporary file and a rename.
+pub fn save(path: &Path, hashes: &HashSet<u64>) -> io::Result<()> {
+    let mut sorted: Vec<u64> = hashes.iter().copied().collect();
+    sorted.sort_unstable();
+
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = Path::new(&temp_name);
+    {
+        let mut file = io::BufWriter::new(fs::File::create(temp_path)?);
+        writeln!(file, "{}", HEADER)?;
+        for hash in sorted {
+            writeln!(file, "{:016x}", hash)?;
+        }
+        file.into_inner()?.sync_all()?;
+    }
+    if let Err(e) = fs::rename(temp_path, path) {
+        let _ = fs::remove_file(temp_path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_key_depends_on_tag_and_content() {
+        let key = content_key("txt", b"abc\n");
+        assert_eq!(key, content_key("txt", b"abc\n"));
+        assert_ne!(key, content_key("yaml", b"abc\n"));
+        assert_ne!(key, content_key("txt", b"abc \n"));
+        assert_ne!(content_key("a", b"bc"), content_key("ab", b"c"));
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rtrim_cache_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cache");
+
+        assert!(load(&path).unwrap().is_empty());
+        let hashes: HashSet<u64> = [1, u64::MAX, 0xdead_beef].into_iter().collect();
+        save(&path, &hashes).unwrap();
+        assert_eq!(load(&path).unwrap(), hashes);
+
+        fs::write(&path, "not a cache\n").unwrap();
+        assert_eq!(load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
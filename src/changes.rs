@@ -0,0 +1,114 @@
+//! Files changed since a git revision, used by `--since-commit` to scope a
+//! run to the files a branch touched, and files staged in the index, used
+//! by `--staged` to scope it to the next commit.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Lists the files that differ between `rev` and the working tree of the
+/// repository containing `dir`, as absolute paths under its top level.
+///
+/// Fails when `dir` is not inside a git repository or `rev` is unknown.
+pub fn changed_since(dir: &Path, rev: &str) -> io::Result<Vec<PathBuf>> {
+    let top_level = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let top_level = PathBuf::from(top_level.trim_end_matches('\n'));
+    let output = git(dir, &["diff", "--name-only", "-z", rev, "--"])?;
+    Ok(parse_name_only(&top_level, &output))
+}
+
+/// Lists the files staged in the index of the repository containing `dir`,
+/// as absolute paths under its top level. Staged deletions are left out.
+///
+/// Fails when `dir` is not inside a git repository.
+pub fn staged(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let top_level = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let top_level = PathBuf::from(top_level.trim_end_matches('\n'));
+    let output = git(
+        dir,
+        &[
+            "diff",
+            "--cached",
+            "--name-only",
+            "-z",
+            "--diff-filter=d",
+            "--",
+        ],
+    )?;
+    Ok(parse_name_only(&top_level, &output))
+}
+
+/// Lists the files whose working-tree content differs from the index of the
+/// repository containing `dir`, as absolute paths under its top level.
+///
+/// Fails when `dir` is not inside a git repository.
+pub fn unstaged(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let top_level = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let top_level = PathBuf::from(top_level.trim_end_matches('\n'));
+    let output = git(dir, &["diff", "--name-only", "-z", "--"])?;
+    Ok(parse_name_only(&top_level, &output))
+}
+
+/// Runs git in `dir` and returns its stdout; a failure carries git's message.
+fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.lines().next().unwrap_or_default();
+        return Err(io::Error::other(format!(
+            "git {} failed in {}: {}",
+            args[0],
+            dir.display(),
+            message
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Maps NUL-separated `git diff --name-only -z` output, whose paths are
+/// relative to the repository top level, to absolute paths.
+fn parse_name_only(top_level: &Path, output: &str) -> Vec<PathBuf> {
+    output
+        .split('\0')
+        .filter(|name| !name.is_empty())
+        .map(|name| top_level.join(name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_name_only() {
+        let changed = parse_name_only(Path::new("/repo"), "src/main.rs\0with space.md\0");
+        assert_eq!(
+            changed,
+            vec![
+                PathBuf::from("/repo/src/main.rs"),
+                PathBuf::from("/repo/with space.md")
+            ]
+        );
+        assert!(parse_name_only(Path::new("/repo"), "").is_empty());
+    }
+
+    #[test]
+    fn test_changed_since_outside_repository_fails() {
+        let dir = std::env::temp_dir();
+        if Command::new("git")
+            .args(["-C", &dir.to_string_lossy(), "rev-parse"])
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| !status.success())
+        {
+            assert!(changed_since(&dir, "HEAD").is_err());
+            assert!(staged(&dir).is_err());
+            assert!(unstaged(&dir).is_err());
+        }
+    }
+}
@@ -0,0 +1,199 @@
+//! Unified diff generation for previewing and exporting changes.
+//!
+//! rtrim only rewrites lines in place and adds or drops lines at the end of a
+//! file, so lines are aligned by position instead of computing a general LCS:
+//! line `i` of the input corresponds to line `i` of the output, and any excess
+//! lines at the end are reported as removed or added.
+
+use std::fmt::Write as _;
+
+/// Lines of context shown around each change.
+const CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Change,
+    Delete,
+    Insert,
+}
+
+/// Splits into lines, remembering whether the last line had a newline.
+fn split_lines(text: &str) -> (Vec<&str>, bool) {
+    let lines = text
+        .split_inclusive('\n')
+        .map(|l| l.strip_suffix('\n').unwrap_or(l));
+    (lines.collect(), text.is_empty() || text.ends_with('\n'))
+}
+
+/// Renders a unified diff between `old` and `new`, or an empty string when
+/// they are identical.
+pub fn unified_diff(old_label: &str, new_label: &str, old: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    let (old_lines, old_eol) = split_lines(old);
+    let (new_lines, new_eol) = split_lines(new);
+    let len = old_lines.len().max(new_lines.len());
+
+    let ops: Vec<Op> = (0..len)
+        .map(|i| match (old_lines.get(i), new_lines.get(i)) {
+            (Some(a), Some(b)) => {
+                // The final line also differs if only its newline changed.
+                let last_old = i + 1 == old_lines.len();
+                let last_new = i + 1 == new_lines.len();
+                let eol_a = !last_old || old_eol;
+                let eol_b = !last_new || new_eol;
+                if a == b && eol_a == eol_b {
+                    Op::Equal
+                } else {
+                    Op::Change
+                }
+            }
+            (Some(_), None) => Op::Delete,
+            (None, Some(_)) => Op::Insert,
+            (None, None) => unreachable!(),
+        })
+        .collect();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "--- {}", old_label);
+    let _ = writeln!(out, "+++ {}", new_label);
+
+    for (start, end) in hunks(&ops) {
+        let old_count = end.min(old_lines.len()).saturating_sub(start);
+        let new_count = end.min(new_lines.len()).saturating_sub(start);
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(start, old_count),
+            range(start, new_count)
+        );
+
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        let flush = |out: &mut String, removed: &mut Vec<usize>, added: &mut Vec<usize>| {
+            for &i in removed.iter() {
+                push_line(out, '-', old_lines[i], i + 1 == old_lines.len() && !old_eol);
+            }
+            for &i in added.iter() {
+                push_line(out, '+', new_lines[i], i + 1 == new_lines.len() && !new_eol);
+            }
+            removed.clear();
+            added.clear();
+        };
+
+        for (i, op) in ops.iter().enumerate().take(end).skip(start) {
+            match op {
+                Op::Equal => {
+                    flush(&mut out, &mut removed, &mut added);
+                    push_line(
+                        &mut out,
+                        ' ',
+                        old_lines[i],
+                        i + 1 == old_lines.len() && !old_eol,
+                    );
+                }
+                Op::Change => {
+                    removed.push(i);
+                    added.push(i);
+                }
+                Op::Delete => removed.push(i),
+                Op::Insert => added.push(i),
+            }
+        }
+        flush(&mut out, &mut removed, &mut added);
+    }
+    out
+}
+
+/// Groups changed positions into `[start, end)` hunks with surrounding context.
+fn hunks(ops: &[Op]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        if *op == Op::Equal {
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT);
+        let end = (i + 1 + CONTEXT).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+fn push_line(out: &mut String, prefix: char, line: &str, no_newline: bool) {
+    out.push(prefix);
+    out.push_str(line);
+    out.push('\n');
+    if no_newline {
+        out.push_str("\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_inputs_produce_no_diff() {
+        assert_eq!(unified_diff("a", "b", "x\n", "x\n"), "");
+    }
+
+    #[test]
+    fn test_single_change_with_context() {
+        let old = "1\n2\n3\n4  \n5\n6\n7\n8\n";
+        let new = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        assert_eq!(
+            unified_diff("a/f", "b/f", old, new),
+            "--- a/f\n+++ b/f\n@@ -1,7 +1,7 @@\n 1\n 2\n 3\n-4  \n+4\n 5\n 6\n 7\n"
+        );
+    }
+
+    #[test]
+    fn test_distant_changes_make_separate_hunks() {
+        let old: String = (1..=20)
+            .map(|i| format!("{}{}\n", i, if i == 2 || i == 18 { " " } else { "" }))
+            .collect();
+        let new: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let diff = unified_diff("a", "b", &old, &new);
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -1,5 +1,5 @@"));
+        assert!(diff.contains("@@ -15,6 +15,6 @@"));
+    }
+
+    #[test]
+    fn test_missing_final_newline_is_marked() {
+        let diff = unified_diff("a", "b", "x  ", "x");
+        assert_eq!(
+            diff,
+            "--- a\n+++ b\n@@ -1 +1 @@\n-x  \n\\ No newline at end of file\n+x\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_removed_trailing_lines() {
+        let diff = unified_diff("a", "b", "x\n\n\n", "x\n");
+        assert_eq!(diff, "--- a\n+++ b\n@@ -1,3 +1 @@\n x\n-\n-\n");
+    }
+
+    #[test]
+    fn test_newline_only_change() {
+        let diff = unified_diff("a", "b", "x", "x\n");
+        assert_eq!(
+            diff,
+            "--- a\n+++ b\n@@ -1 +1 @@\n-x\n\\ No newline at end of file\n+x\n"
+        );
+    }
+}
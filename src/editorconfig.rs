@@ -0,0 +1,350 @@
+//! `.editorconfig` lookup for `--editorconfig`.
+//!
+//! Only `trim_trailing_whitespace` and `insert_final_newline` are read; every
+//! other property is ignored. Files are looked up from the file's directory
+//! upwards, stopping at one that declares `root = true`. Closer files and
+//! later sections win, as the EditorConfig specification says.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::gitignore::slash_path;
+
+/// The properties rtrim honors, as declared for one file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Properties {
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+/// One `[glob]` section. A property set to `unset` is `Some(None)`.
+#[derive(Debug)]
+struct Section {
+    glob: Glob,
+    trim_trailing_whitespace: Option<Option<bool>>,
+    insert_final_newline: Option<Option<bool>>,
+}
+
+/// A parsed `.editorconfig` file.
+#[derive(Debug, Default)]
+struct ConfigFile {
+    root: bool,
+    sections: Vec<Section>,
+}
+
+impl ConfigFile {
+    fn parse(content: &str) -> ConfigFile {
+        let mut file = ConfigFile::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                file.sections.push(Section {
+                    glob: Glob::parse(header),
+                    trim_trailing_whitespace: None,
+                    insert_final_newline: None,
+                });
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_ascii_lowercase();
+            let value = match value.trim().to_ascii_lowercase().as_str() {
+                "true" => Some(true),
+                "false" => Some(false),
+                "unset" => None,
+                _ => continue,
+            };
+            match (file.sections.last_mut(), key.as_str()) {
+                (None, "root") => file.root = value == Some(true),
+                (Some(section), "trim_trailing_whitespace") => {
+                    section.trim_trailing_whitespace = Some(value)
+                }
+                (Some(section), "insert_final_newline") => {
+                    section.insert_final_newline = Some(value)
+                }
+                _ => {}
+            }
+        }
+        file
+    }
+}
+
+/// Parsed `.editorconfig` files, cached by directory for the whole run.
+#[derive(Debug, Default)]
+pub struct EditorConfigs {
+    dirs: Mutex<HashMap<PathBuf, Arc<ConfigFile>>>,
+}
+
+impl EditorConfigs {
+    /// The properties the `.editorconfig` files above `path` declare for it.
+    pub fn properties_for(&self, path: &Path) -> Properties {
+        let mut properties = Properties::default();
+        let Ok(path) = path.canonicalize() else {
+            return properties;
+        };
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let file = self.file(dir);
+            let root = file.root;
+            files.push((dir, file));
+            if root {
+                break;
+            }
+        }
+
+        for (dir, file) in files.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative: Vec<char> = slash_path(relative).chars().collect();
+            for section in file.sections.iter().filter(|s| s.glob.matches(&relative)) {
+                if let Some(value) = section.trim_trailing_whitespace {
+                    properties.trim_trailing_whitespace = value;
+                }
+                if let Some(value) = section.insert_final_newline {
+                    properties.insert_final_newline = value;
+                }
+            }
+        }
+        properties
+    }
+
+    fn file(&self, dir: &Path) -> Arc<ConfigFile> {
+        if let Some(file) = self.dirs.lock().unwrap().get(dir) {
+            return Arc::clone(file);
+        }
+        let file = fs::read_to_string(dir.join(".editorconfig"))
+            .map(|content| ConfigFile::parse(&content))
+            .unwrap_or_default();
+        let file = Arc::new(file);
+        self.dirs
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), Arc::clone(&file));
+        file
+    }
+}
+
+/// A piece of a section glob.
+#[derive(Debug, Clone)]
+enum Token {
+    Literal(char),
+    /// `?`: any character but `/`.
+    One,
+    /// `*`: any run of characters without `/`.
+    Star,
+    /// `**`: any run of characters.
+    DoubleStar,
+    /// `[...]` or `[!...]`, as inclusive ranges.
+    Class(bool, Vec<(char, char)>),
+    /// `{a,b,...}`.
+    Alternatives(Vec<Vec<Token>>),
+    /// `{n1..n2}`: an integer in the range.
+    Number(i64, i64),
+}
+
+/// A section name, matched against paths relative to the `.editorconfig`.
+#[derive(Debug)]
+struct Glob {
+    tokens: Vec<Token>,
+    /// Contains a `/`, so it is matched against the whole relative path
+    /// rather than at any depth.
+    anchored: bool,
+}
+
+impl Glob {
+    fn parse(pattern: &str) -> Glob {
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let chars: Vec<char> = pattern.chars().collect();
+        Glob {
+            tokens: parse_tokens(&chars, &mut 0, false),
+            anchored,
+        }
+    }
+
+    fn matches(&self, relative: &[char]) -> bool {
+        if self.anchored {
+            return match_tokens(&self.tokens, relative);
+        }
+        match_tokens(&self.tokens, relative)
+            || (0..relative.len())
+                .filter(|&i| relative[i] == '/')
+                .any(|i| match_tokens(&self.tokens, &relative[i + 1..]))
+    }
+}
+
+/// Parses tokens from `chars[*at..]`, up to the end, or inside braces up to
+/// the `,` or `}` ending the current alternative.
+fn parse_tokens(chars: &[char], at: &mut usize, in_braces: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    while let Some(&c) = chars.get(*at) {
+        if in_braces && (c == ',' || c == '}') {
+            break;
+        }
+        *at += 1;
+        let token = match c {
+            '\\' => match chars.get(*at) {
+                Some(&escaped) => {
+                    *at += 1;
+                    Token::Literal(escaped)
+                }
+                None => Token::Literal('\\'),
+            },
+            '?' => Token::One,
+            '*' if chars.get(*at) == Some(&'*') => {
+                *at += 1;
+                Token::DoubleStar
+            }
+            '*' => Token::Star,
+            '[' => parse_class(chars, at).unwrap_or(Token::Literal('[')),
+            '{' => parse_braces(chars, at).unwrap_or(Token::Literal('{')),
+            c => Token::Literal(c),
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+/// Parses a class after its `[`; `None` leaves an unclosed `[` literal.
+fn parse_class(chars: &[char], at: &mut usize) -> Option<Token> {
+    let start = *at;
+    let negated = chars.get(start) == Some(&'!');
+    let body = start + usize::from(negated);
+    let end = (body..chars.len()).find(|&i| chars[i] == ']')?;
+    let items = &chars[body..end];
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < items.len() {
+        if i + 2 < items.len() && items[i + 1] == '-' {
+            ranges.push((items[i], items[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((items[i], items[i]));
+            i += 1;
+        }
+    }
+    *at = end + 1;
+    Some(Token::Class(negated, ranges))
+}
+
+/// Parses `{...}` after its `{`: a numeric range, or alternatives. `None`
+/// leaves an unclosed `{` literal.
+fn parse_braces(chars: &[char], at: &mut usize) -> Option<Token> {
+    let start = *at;
+    let close = start + chars[start..].iter().position(|&c| c == '}')?;
+    let body: String = chars[start..close].iter().collect();
+    if let Some((low, high)) = body.split_once("..") {
+        if let (Ok(low), Ok(high)) = (low.parse::<i64>(), high.parse::<i64>()) {
+            *at = close + 1;
+            return Some(Token::Number(low.min(high), low.max(high)));
+        }
+    }
+
+    let mut alternatives = Vec::new();
+    let mut cursor = start;
+    loop {
+        alternatives.push(parse_tokens(chars, &mut cursor, true));
+        match chars.get(cursor) {
+            Some(',') => cursor += 1,
+            Some('}') => break,
+            _ => return None,
+        }
+    }
+    *at = cursor + 1;
+    Some(Token::Alternatives(alternatives))
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    match token {
+        Token::Literal(c) => text.first() == Some(c) && match_tokens(rest, &text[1..]),
+        Token::One => {
+            matches!(text.first(), Some(&c) if c != '/') && match_tokens(rest, &text[1..])
+        }
+        Token::Star => {
+            let run = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=run).any(|i| match_tokens(rest, &text[i..]))
+        }
+        Token::DoubleStar => (0..=text.len()).any(|i| match_tokens(rest, &text[i..])),
+        Token::Class(negated, ranges) => match text.first() {
+            Some(&c) if c != '/' => {
+                let found = ranges.iter().any(|&(low, high)| low <= c && c <= high);
+                found != *negated && match_tokens(rest, &text[1..])
+            }
+            _ => false,
+        },
+        Token::Alternatives(alternatives) => alternatives.iter().any(|alternative| {
+            let joined: Vec<Token> = alternative.iter().chain(rest).cloned().collect();
+            match_tokens(&joined, text)
+        }),
+        Token::Number(low, high) => {
+            let sign = usize::from(text.first() == Some(&'-'));
+            let digits = text[sign..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            (1..=digits).any(|len| {
+                let number: String = text[..sign + len].iter().collect();
+                number.parse::<i64>().is_ok_and(|n| *low <= n && n <= *high)
+                    && match_tokens(rest, &text[sign + len..])
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        Glob::parse(glob).matches(&path.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_glob_matching() {
+        assert!(matches("*", "a.txt"));
+        assert!(matches("*.rs", "src/deep/main.rs"));
+        assert!(!matches("*.rs", "main.rsx"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(!matches("src/*.rs", "src/bin/main.rs"));
+        assert!(!matches("src/*.rs", "lib/src/main.rs"));
+        assert!(matches("/src/**.rs", "src/bin/main.rs"));
+        assert!(matches("lib/**/x.c", "lib/a/b/x.c"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(matches("[a-c].md", "b.md"));
+        assert!(!matches("[!a-c].md", "b.md"));
+        assert!(matches("*.{js,ts}", "app.ts"));
+        assert!(matches("{package.json,.travis.yml}", ".travis.yml"));
+        assert!(!matches("*.{js,ts}", "app.rs"));
+        assert!(matches("test{1..10}.txt", "test7.txt"));
+        assert!(!matches("test{1..10}.txt", "test11.txt"));
+        assert!(matches("\\*.txt", "*.txt"));
+        assert!(!matches("\\*.txt", "a.txt"));
+    }
+
+    #[test]
+    fn test_file_parsing() {
+        let file = ConfigFile::parse(
+            "root = true\n\n# comment\n[*]\ntrim_trailing_whitespace = true\n\
+             insert_final_newline = false\n\n[*.md]\nTrim_Trailing_Whitespace = FALSE\n\
+             ; comment\n[Makefile]\ninsert_final_newline = unset\nindent_style = tab\n",
+        );
+        assert!(file.root);
+        assert_eq!(file.sections.len(), 3);
+        assert_eq!(file.sections[0].trim_trailing_whitespace, Some(Some(true)));
+        assert_eq!(file.sections[0].insert_final_newline, Some(Some(false)));
+        assert_eq!(file.sections[1].trim_trailing_whitespace, Some(Some(false)));
+        assert_eq!(file.sections[2].insert_final_newline, Some(None));
+        assert_eq!(file.sections[2].trim_trailing_whitespace, None);
+
+        assert!(!ConfigFile::parse("[*]\nroot = true\n").root);
+    }
+}
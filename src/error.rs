@@ -0,0 +1,156 @@
+//! Structured error type for per-file failures.
+
+#[cfg(feature = "backtrace")]
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An I/O failure annotated with the file and the step that failed.
+#[derive(Debug)]
+pub struct RtrimError {
+    path: PathBuf,
+    action: &'static str,
+    source: io::Error,
+    #[cfg(feature = "backtrace")]
+    backtrace: Backtrace,
+}
+
+impl RtrimError {
+    pub fn new(action: &'static str, path: &Path, source: io::Error) -> Self {
+        RtrimError {
+            path: path.to_path_buf(),
+            action,
+            source,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The step that failed, as passed to `new`.
+    pub fn action(&self) -> &'static str {
+        self.action
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        self.source.kind()
+    }
+}
+
+impl fmt::Display for RtrimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.action, self.source)
+    }
+}
+
+impl Error for RtrimError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<RtrimError> for io::Error {
+    fn from(err: RtrimError) -> Self {
+        io::Error::new(err.kind(), err)
+    }
+}
+
+/// Attaches the failing step and path to an `io::Result`.
+pub trait Context<T> {
+    fn context(self, action: &'static str, path: &Path) -> Result<T, RtrimError>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context(self, action: &'static str, path: &Path) -> Result<T, RtrimError> {
+        self.map_err(|e| RtrimError::new(action, path, e))
+    }
+}
+
+/// Formats an error for display. The terse form is a single line; the verbose
+/// form adds every `source()` in the chain with its I/O error kind and, when
+/// built with the `backtrace` feature and `RUST_BACKTRACE` is set, a backtrace.
+pub fn describe(err: &(dyn Error + 'static), verbose: bool) -> String {
+    let mut text = err.to_string();
+    if !verbose {
+        return text;
+    }
+
+    if let Some(kind) = io_kind(err) {
+        let _ = write!(text, " (kind: {:?})", kind);
+    }
+    let mut current = err.source();
+    while let Some(e) = current {
+        let _ = write!(text, "\n  caused by: {}", e);
+        if let Some(kind) = io_kind(e) {
+            let _ = write!(text, " (kind: {:?})", kind);
+        }
+        current = e.source();
+    }
+
+    #[cfg(feature = "backtrace")]
+    if let Some(rtrim) = find_rtrim_error(err) {
+        if rtrim.backtrace.status() == BacktraceStatus::Captured {
+            let _ = write!(text, "\n  backtrace:\n{}", rtrim.backtrace);
+        }
+    }
+    text
+}
+
+fn io_kind(err: &(dyn Error + 'static)) -> Option<io::ErrorKind> {
+    err.downcast_ref::<io::Error>()
+        .map(io::Error::kind)
+        .or_else(|| err.downcast_ref::<RtrimError>().map(RtrimError::kind))
+}
+
+#[cfg(feature = "backtrace")]
+fn find_rtrim_error<'a>(err: &'a (dyn Error + 'static)) -> Option<&'a RtrimError> {
+    if let Some(rtrim) = err.downcast_ref::<RtrimError>() {
+        return Some(rtrim);
+    }
+    // An `RtrimError` converted into an `io::Error` is kept as its inner error.
+    err.downcast_ref::<io::Error>()
+        .and_then(|io| io.get_ref())
+        .and_then(|inner| inner.downcast_ref::<RtrimError>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn not_found() -> RtrimError {
+        RtrimError::new(
+            "failed to open file",
+            Path::new("missing.txt"),
+            io::Error::new(io::ErrorKind::NotFound, "no such file"),
+        )
+    }
+
+    #[test]
+    fn test_terse_description_is_one_line() {
+        let err = not_found();
+        assert_eq!(describe(&err, false), "failed to open file: no such file");
+        assert_eq!(err.path(), Path::new("missing.txt"));
+    }
+
+    #[test]
+    fn test_verbose_description_includes_chain_and_kind() {
+        let err = not_found();
+        let text = describe(&err, true);
+        assert!(text.starts_with("failed to open file: no such file (kind: NotFound)"));
+        assert!(text.contains("\n  caused by: no such file (kind: NotFound)"));
+    }
+
+    #[test]
+    fn test_conversion_to_io_error_keeps_kind_and_chain() {
+        let err: io::Error = not_found().into();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        let text = describe(&err, true);
+        assert!(text.contains("failed to open file"));
+        assert!(text.contains("caused by: no such file (kind: NotFound)"));
+    }
+}
@@ -0,0 +1,129 @@
+//! The `eol` attribute from `.gitattributes`, for `--respect-gitattributes-eol`.
+//!
+//! Only `eol=lf`, `eol=crlf` and the settings that clear it (`-eol`, `!eol`,
+//! `-text` and the `binary` macro) are read; every other attribute is
+//! ignored. Files are looked up from the repository root down to the file,
+//! and later lines and deeper files win, as in git.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::gitignore::{slash_path, PathPattern};
+
+/// Line ending declared by the `eol` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eol {
+    Lf,
+    Crlf,
+}
+
+impl Eol {
+    /// Rewrites every line ending of `content` to this one.
+    pub fn apply(self, content: &str) -> String {
+        let lf = content.replace("\r\n", "\n");
+        match self {
+            Eol::Lf => lf,
+            Eol::Crlf => lf.replace('\n', "\r\n"),
+        }
+    }
+}
+
+/// A line of a `.gitattributes` file that sets or clears `eol`.
+#[derive(Debug)]
+struct Rule {
+    pattern: PathPattern,
+    eol: Option<Eol>,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let mut fields = line.split_whitespace();
+        let pattern = fields.next().filter(|p| !p.starts_with('#'))?;
+        let eol = fields
+            .filter_map(|attribute| match attribute {
+                "eol=lf" => Some(Some(Eol::Lf)),
+                "eol=crlf" => Some(Some(Eol::Crlf)),
+                "-eol" | "!eol" | "-text" | "binary" => Some(None),
+                _ => None,
+            })
+            .next_back()?;
+        Some(Rule {
+            pattern: PathPattern::parse(pattern)?,
+            eol,
+        })
+    }
+}
+
+/// Parsed `.gitattributes` files, cached by directory for the whole run.
+#[derive(Debug, Default)]
+pub struct EolRules {
+    dirs: Mutex<HashMap<PathBuf, Arc<Vec<Rule>>>>,
+}
+
+impl EolRules {
+    /// The line ending `.gitattributes` declares for `path`, if any. Files
+    /// outside a git repository have none.
+    pub fn eol_for(&self, path: &Path) -> Option<Eol> {
+        let path = path.canonicalize().ok()?;
+        let mut dirs: Vec<&Path> = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            dirs.push(dir);
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        if !dirs.last()?.join(".git").exists() {
+            return None;
+        }
+
+        let mut eol = None;
+        for dir in dirs.into_iter().rev() {
+            let relative = slash_path(path.strip_prefix(dir).ok()?);
+            for rule in self.rules(dir).iter() {
+                if rule.pattern.matches(&relative) {
+                    eol = rule.eol;
+                }
+            }
+        }
+        eol
+    }
+
+    fn rules(&self, dir: &Path) -> Arc<Vec<Rule>> {
+        if let Some(rules) = self.dirs.lock().unwrap().get(dir) {
+            return Arc::clone(rules);
+        }
+        let rules: Vec<Rule> = fs::read_to_string(dir.join(".gitattributes"))
+            .map(|content| content.lines().filter_map(Rule::parse).collect())
+            .unwrap_or_default();
+        let rules = Arc::new(rules);
+        self.dirs
+            .lock()
+            .unwrap()
+            .insert(dir.to_path_buf(), Arc::clone(&rules));
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_parsing() {
+        let rule = Rule::parse("*.bat text eol=crlf").unwrap();
+        assert_eq!(rule.eol, Some(Eol::Crlf));
+        assert!(rule.pattern.matches("scripts/run.bat"));
+        assert_eq!(Rule::parse("*.png binary").unwrap().eol, None);
+        assert_eq!(Rule::parse("*.sh eol=crlf -eol").unwrap().eol, None);
+        assert!(Rule::parse("*.rs diff=rust").is_none());
+        assert!(Rule::parse("# *.txt eol=lf").is_none());
+    }
+
+    #[test]
+    fn test_apply_eol() {
+        assert_eq!(Eol::Crlf.apply("a\nb\r\nc"), "a\r\nb\r\nc");
+        assert_eq!(Eol::Lf.apply("a\r\nb\n"), "a\nb\n");
+    }
+}
@@ -0,0 +1,320 @@
+//! `.gitignore` rules for `--respect-gitignore` folder walks.
+//!
+//! Supports the usual syntax: comments, `!` negation, a trailing `/` for
+//! directories only, patterns anchored by a `/`, and the `*`, `?`, `[...]`
+//! and `**` wildcards. Global excludes and `.git/info/exclude` are not read.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// A path pattern as written in `.gitignore` and `.gitattributes` files.
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    glob: Vec<char>,
+    /// Matched against the whole relative path rather than the file name.
+    anchored: bool,
+}
+
+impl PathPattern {
+    /// Parses `pattern`; a `/` anywhere anchors it to the directory of the
+    /// file it comes from. An empty pattern gives `None`.
+    pub fn parse(pattern: &str) -> Option<PathPattern> {
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        (!pattern.is_empty()).then(|| PathPattern {
+            glob: pattern.chars().collect(),
+            anchored,
+        })
+    }
+
+    /// Whether the `/`-separated `relative` path matches.
+    pub fn matches(&self, relative: &str) -> bool {
+        let text = if self.anchored {
+            relative
+        } else {
+            relative.rsplit('/').next().unwrap_or(relative)
+        };
+        glob_match(&self.glob, &text.chars().collect::<Vec<_>>())
+    }
+}
+
+/// One parsed line of a `.gitignore` file.
+#[derive(Debug, Clone)]
+struct Rule {
+    pattern: PathPattern,
+    negated: bool,
+    dir_only: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end_matches(['\r', ' ']);
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (
+                false,
+                line.strip_prefix('\\')
+                    .filter(|rest| rest.starts_with(['!', '#']))
+                    .unwrap_or(line),
+            ),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        Some(Rule {
+            pattern: PathPattern::parse(line)?,
+            negated,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        (is_dir || !self.dir_only) && self.pattern.matches(relative)
+    }
+}
+
+/// The rules of one `.gitignore` file, with the walk path they apply under.
+#[derive(Debug, Clone)]
+pub struct Gitignore {
+    /// Walk path that `prefix` refers to.
+    base: PathBuf,
+    /// Path of `base` relative to the directory holding the file; empty
+    /// unless the file lives above the walk root.
+    prefix: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    /// Reads `dir/.gitignore`. A missing, unreadable or empty file gives `None`.
+    pub fn load(dir: &Path) -> Option<Gitignore> {
+        Gitignore::read(dir, dir.to_path_buf(), PathBuf::new())
+    }
+
+    fn read(holder: &Path, base: PathBuf, prefix: PathBuf) -> Option<Gitignore> {
+        let content = fs::read_to_string(holder.join(".gitignore")).ok()?;
+        let rules: Vec<Rule> = content.lines().filter_map(Rule::parse).collect();
+        (!rules.is_empty()).then_some(Gitignore {
+            base,
+            prefix,
+            rules,
+        })
+    }
+
+    /// The `.gitignore` files between the enclosing repository root and
+    /// `root` (exclusive), outermost first. Nothing is found outside a
+    /// repository.
+    pub fn above(root: &Path) -> Vec<Gitignore> {
+        let Ok(canonical) = root.canonicalize() else {
+            return Vec::new();
+        };
+        let mut found = Vec::new();
+        let mut prefix = PathBuf::new();
+        let mut current = canonical.as_path();
+        while let Some(parent) = current.parent() {
+            if current.join(".git").exists() {
+                found.reverse();
+                return found;
+            }
+            if let Some(name) = current.file_name() {
+                prefix = Path::new(name).join(&prefix);
+            }
+            found.extend(Gitignore::read(parent, root.to_path_buf(), prefix.clone()));
+            current = parent;
+        }
+        Vec::new()
+    }
+
+    /// The verdict of the last rule matching `path`, if any: `true` for
+    /// ignored, `false` for re-included by a `!` rule.
+    fn verdict(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let rest = path.strip_prefix(&self.base).ok()?;
+        let relative = slash_path(&self.prefix.join(rest));
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.matches(&relative, is_dir))
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// `path` with its components joined by `/`, as patterns expect.
+pub fn slash_path(path: &Path) -> String {
+    let components: Vec<_> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    components.join("/")
+}
+
+/// Whether `path` is ignored by `ignores`, ordered from the outermost file to
+/// the innermost; deeper files override shallower ones.
+pub fn is_ignored<G: Deref<Target = Gitignore>>(ignores: &[G], path: &Path, is_dir: bool) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .find_map(|ignore| ignore.verdict(path, is_dir))
+        .unwrap_or(false)
+}
+
+/// Matches `text` against a glob where `*` and `?` stop at `/` and `**`
+/// spans directories.
+fn glob_match(glob: &[char], text: &[char]) -> bool {
+    match glob.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) if rest.first() == Some(&'*') => {
+            let rest = &rest[1..];
+            match rest.split_first() {
+                None => true,
+                Some(('/', after)) => {
+                    glob_match(after, text)
+                        || (0..text.len())
+                            .filter(|&i| text[i] == '/')
+                            .any(|i| glob_match(after, &text[i + 1..]))
+                }
+                Some(_) => (0..=text.len()).any(|i| glob_match(rest, &text[i..])),
+            }
+        }
+        Some(('*', rest)) => {
+            let run = text.iter().position(|&c| c == '/').unwrap_or(text.len());
+            (0..=run).any(|i| glob_match(rest, &text[i..]))
+        }
+        Some(('?', rest)) => {
+            matches!(text.first(), Some(&c) if c != '/') && glob_match(rest, &text[1..])
+        }
+        Some(('[', rest)) => match (text.first(), class_end(rest)) {
+            (Some(&c), Some(end)) => {
+                c != '/'
+                    && class_matches(&rest[..end], c)
+                    && glob_match(&rest[end + 1..], &text[1..])
+            }
+            (Some(&c), None) => c == '[' && glob_match(rest, &text[1..]),
+            (None, _) => false,
+        },
+        Some(('\\', rest)) if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && glob_match(&rest[1..], &text[1..])
+        }
+        Some((&literal, rest)) => text.first() == Some(&literal) && glob_match(rest, &text[1..]),
+    }
+}
+
+/// Index of the `]` closing a class whose body starts at `class[0]`.
+fn class_end(class: &[char]) -> Option<usize> {
+    let start = match class.first() {
+        Some('!' | '^') => 1,
+        _ => 0,
+    };
+    // A `]` right after the opening bracket is a literal member.
+    (start + 1..class.len()).find(|&i| class[i] == ']')
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, items) = match class.split_first() {
+        Some(('!' | '^', rest)) => (true, rest),
+        _ => (false, class),
+    };
+    let mut i = 0;
+    let mut found = false;
+    while i < items.len() {
+        if i + 2 < items.len() && items[i + 1] == '-' {
+            found |= items[i] <= c && c <= items[i + 2];
+            i += 3;
+        } else {
+            found |= items[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    fn ignore(rules: &str) -> Gitignore {
+        Gitignore {
+            base: PathBuf::from("root"),
+            prefix: PathBuf::new(),
+            rules: rules.lines().filter_map(Rule::parse).collect(),
+        }
+    }
+
+    fn ignored(rules: &str, path: &str, is_dir: bool) -> bool {
+        is_ignored(
+            &[Rc::new(ignore(rules))],
+            &Path::new("root").join(path),
+            is_dir,
+        )
+    }
+
+    #[test]
+    fn test_unanchored_patterns_match_at_any_depth() {
+        assert!(ignored("*.log", "a.log", false));
+        assert!(ignored("*.log", "deep/er/a.log", false));
+        assert!(!ignored("*.log", "a.logx", false));
+        assert!(ignored("dist", "pkg/dist", true));
+    }
+
+    #[test]
+    fn test_anchored_and_directory_patterns() {
+        assert!(ignored("/build", "build", true));
+        assert!(!ignored("/build", "src/build", true));
+        assert!(ignored("gen/proto", "gen/proto", true));
+        assert!(!ignored("gen/proto", "x/gen/proto", true));
+        assert!(ignored("cache/", "cache", true));
+        assert!(!ignored("cache/", "cache", false));
+    }
+
+    #[test]
+    fn test_negation_and_comments() {
+        let rules = "# logs\n*.log\n!keep.log\n";
+        assert!(ignored(rules, "a.log", false));
+        assert!(!ignored(rules, "keep.log", false));
+        assert!(ignored("\\!bang", "!bang", false));
+        assert!(ignored("\\#hash", "#hash", false));
+        assert!(ignored("\\*.txt", "*.txt", false));
+        assert!(!ignored("\\*.txt", "a.txt", false));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert!(ignored("**/gen", "a/b/gen", true));
+        assert!(ignored("a/**/b", "a/b", true));
+        assert!(ignored("a/**/b", "a/x/y/b", true));
+        assert!(ignored("out/**", "out/x/y.txt", false));
+        assert!(ignored("file?.txt", "file1.txt", false));
+        assert!(ignored("[a-c].txt", "b.txt", false));
+        assert!(!ignored("[!a-c].txt", "b.txt", false));
+        assert!(!ignored("a*b", "a/b", false));
+    }
+
+    #[test]
+    fn test_deeper_files_override_shallower_ones() {
+        let outer = ignore("*.txt\n");
+        let inner = Gitignore {
+            base: PathBuf::from("root/sub"),
+            prefix: PathBuf::new(),
+            rules: vec![Rule::parse("!keep.txt").unwrap()],
+        };
+        let ignores = [Rc::new(outer), Rc::new(inner)];
+        assert!(is_ignored(&ignores, Path::new("root/a.txt"), false));
+        assert!(!is_ignored(&ignores, Path::new("root/sub/keep.txt"), false));
+        assert!(is_ignored(&ignores, Path::new("root/sub/other.txt"), false));
+    }
+
+    #[test]
+    fn test_prefix_locates_walk_root_below_gitignore() {
+        let ignores = [Rc::new(Gitignore {
+            base: PathBuf::from("src"),
+            prefix: PathBuf::from("src"),
+            rules: vec![Rule::parse("/src/gen").unwrap()],
+        })];
+        assert!(is_ignored(&ignores, Path::new("src/gen"), true));
+        assert!(!is_ignored(&ignores, Path::new("src/lib"), true));
+    }
+}
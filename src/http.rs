@@ -0,0 +1,201 @@
+//! Minimal HTTP/1.1 client for reading remote files in report-only mode.
+//!
+//! Only plain `http://` GET requests are supported: there is no TLS without
+//! external crates, and redirects are reported as errors rather than followed.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest body accepted. The body is held in memory whole, so a server
+/// announcing (or sending) more is refused rather than trusted.
+const MAX_BODY: usize = 256 * 1024 * 1024;
+
+/// Fetches the body of `url`, failing on any non-2xx status.
+pub fn get(url: &str) -> io::Result<Vec<u8>> {
+    let (host, port, path) = parse_url(url)?;
+
+    let mut stream = TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: rtrim/{}\r\nAccept-Encoding: identity\r\nConnection: close\r\n\r\n",
+        path,
+        host,
+        env!("CARGO_PKG_VERSION")
+    )?;
+    stream.flush()?;
+
+    let mut reader = BufReader::new(stream);
+    let status_line = read_line(&mut reader)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid(format!("malformed status line {:?}", status_line)))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(invalid(format!("malformed header {:?}", line)));
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = Some(
+                value
+                    .parse::<usize>()
+                    .map_err(|_| invalid("invalid Content-Length".to_string()))?,
+            );
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        }
+    }
+
+    if !(200..300).contains(&status) {
+        return Err(io::Error::other(format!(
+            "{} returned HTTP status {}",
+            url, status
+        )));
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        read_chunked(&mut reader, &mut body, MAX_BODY)?;
+    } else if let Some(length) = content_length {
+        check_size(length, MAX_BODY)?;
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.take(MAX_BODY as u64 + 1).read_to_end(&mut body)?;
+        check_size(body.len(), MAX_BODY)?;
+    }
+    Ok(body)
+}
+
+/// Fails when a body of `size` bytes would exceed `limit`.
+fn check_size(size: usize, limit: usize) -> io::Result<()> {
+    if size > limit {
+        return Err(invalid(format!(
+            "response body is larger than {} bytes",
+            limit
+        )));
+    }
+    Ok(())
+}
+
+/// Splits an `http://host[:port][/path]` URL.
+fn parse_url(url: &str) -> io::Result<(&str, u16, &str)> {
+    if url.starts_with("https://") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "https:// URLs are not supported (this build has no TLS); use http://",
+        ));
+    }
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| invalid(format!("not an http:// URL: {:?}", url)))?;
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| invalid(format!("invalid port in {:?}", url)))?,
+        ),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(invalid(format!("missing host in {:?}", url)));
+    }
+    Ok((host, port, path))
+}
+
+fn read_chunked(reader: &mut impl BufRead, body: &mut Vec<u8>, limit: usize) -> io::Result<()> {
+    loop {
+        let size_line = read_line(reader)?;
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| invalid(format!("invalid chunk size {:?}", size_line)))?;
+        if size == 0 {
+            // Skip optional trailers up to the final empty line.
+            while !read_line(reader)?.is_empty() {}
+            return Ok(());
+        }
+        let start = body.len();
+        check_size(start.saturating_add(size), limit)?;
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        read_line(reader)?;
+    }
+}
+
+/// Reads one CRLF- (or LF-) terminated line without its terminator.
+fn read_line(reader: &mut impl BufRead) -> io::Result<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed before the response was complete",
+        ));
+    }
+    let trimmed = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(trimmed);
+    Ok(line)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("http://example.com/a/b.txt").unwrap(),
+            ("example.com", 80, "/a/b.txt")
+        );
+        assert_eq!(
+            parse_url("http://127.0.0.1:8080").unwrap(),
+            ("127.0.0.1", 8080, "/")
+        );
+        assert_eq!(
+            parse_url("https://example.com/x").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+        assert!(parse_url("http://:80/x").is_err());
+    }
+
+    #[test]
+    fn test_read_chunked() {
+        let mut input = Cursor::new(b"4\r\nab  \r\n2;ext=1\r\n\nc\r\n0\r\n\r\n".to_vec());
+        let mut body = Vec::new();
+        read_chunked(&mut input, &mut body, 6).unwrap();
+        assert_eq!(body, b"ab  \nc");
+    }
+
+    #[test]
+    fn test_read_chunked_refuses_body_over_limit() {
+        let mut input = Cursor::new(b"4\r\nab  \r\nffffffffffff\r\n".to_vec());
+        let mut body = Vec::new();
+        let err = read_chunked(&mut input, &mut body, 1024).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(body, b"ab  ");
+
+        assert!(check_size(1024, 1024).is_ok());
+        assert!(check_size(1025, 1024).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+//! Leading-indentation normalization for `--normalize-indent`.
+//!
+//! Only the run of spaces and tabs at the start of each line is rewritten;
+//! the rest of the line, including any whitespace after the first non-blank
+//! character, is left alone. With `keep_alignment` only the leading tab run is
+//! indentation, so "tabs for indentation, spaces for alignment" code keeps its
+//! alignment spaces.
+
+/// The unit used for leading indentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tab,
+    Space,
+}
+
+/// The leading run of spaces and tabs of `line`.
+fn leading_whitespace(line: &str) -> &str {
+    let end = line
+        .find(|c: char| c != ' ' && c != '\t')
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Picks the style used by most indented lines, or `None` if no line is
+/// indented. Ties favor spaces.
+pub fn detect(content: &str) -> Option<IndentStyle> {
+    let (mut tabs, mut spaces) = (0usize, 0usize);
+    for line in content.lines() {
+        match leading_whitespace(line).chars().next() {
+            Some('\t') => tabs += 1,
+            Some(' ') => spaces += 1,
+            _ => {}
+        }
+    }
+    match (tabs, spaces) {
+        (0, 0) => None,
+        (tabs, spaces) if tabs > spaces => Some(IndentStyle::Tab),
+        _ => Some(IndentStyle::Space),
+    }
+}
+
+/// Rewrites the leading whitespace of every line in `style`, treating a tab as
+/// advancing to the next multiple of `size` columns. Blank lines are kept as
+/// they are. With `keep_alignment`, the spaces after a line's leading tabs
+/// are alignment and are kept as they are; lines without leading tabs are
+/// rewritten in full. Returns the new content and the number of lines changed.
+pub fn normalize(
+    content: &str,
+    style: IndentStyle,
+    size: usize,
+    keep_alignment: bool,
+) -> (String, usize) {
+    let size = size.max(1);
+    let mut output = String::with_capacity(content.len());
+    let mut changed = 0;
+
+    for line in content.split_inclusive('\n') {
+        let mut indent = leading_whitespace(line);
+        if line.trim().is_empty() {
+            output.push_str(line);
+            continue;
+        }
+        if keep_alignment && indent.starts_with('\t') {
+            indent = &indent[..indent.len() - indent.trim_start_matches('\t').len()];
+        }
+        let rest = &line[indent.len()..];
+        if indent.is_empty() {
+            output.push_str(line);
+            continue;
+        }
+
+        let width = indent.chars().fold(0, |column, c| match c {
+            '\t' => (column / size + 1) * size,
+            _ => column + 1,
+        });
+        let new_indent = match style {
+            IndentStyle::Space => " ".repeat(width),
+            IndentStyle::Tab => "\t".repeat(width / size) + &" ".repeat(width % size),
+        };
+        if new_indent != indent {
+            changed += 1;
+        }
+        output.push_str(&new_indent);
+        output.push_str(rest);
+    }
+    (output, changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_predominant_style() {
+        assert_eq!(detect("a\n\tb\n\tc\n    d\n"), Some(IndentStyle::Tab));
+        assert_eq!(detect("a\n  b\n\tc\n"), Some(IndentStyle::Space));
+        assert_eq!(detect("a\nb\n"), None);
+    }
+
+    #[test]
+    fn test_spaces_to_tabs() {
+        let input = "fn a() {\n    one();\n        two();\n      three();\n}\n";
+        let (output, changed) = normalize(input, IndentStyle::Tab, 4, false);
+        assert_eq!(output, "fn a() {\n\tone();\n\t\ttwo();\n\t  three();\n}\n");
+        assert_eq!(changed, 3);
+    }
+
+    #[test]
+    fn test_tabs_to_spaces() {
+        let input = "if x:\n\ty = 1\n\t\tz = 2\n  \tw = 3";
+        let (output, changed) = normalize(input, IndentStyle::Space, 4, false);
+        assert_eq!(output, "if x:\n    y = 1\n        z = 2\n    w = 3");
+        assert_eq!(changed, 3);
+    }
+
+    #[test]
+    fn test_only_leading_region_is_touched() {
+        let input = "    a\tb    c\n\t\n";
+        let (output, changed) = normalize(input, IndentStyle::Tab, 4, false);
+        assert_eq!(output, "\ta\tb    c\n\t\n");
+        assert_eq!(changed, 1);
+    }
+
+    #[test]
+    fn test_keep_alignment_after_tab_indent() {
+        let input = "\tcall(one,\n\t     two);\n\t\t  x = 1;\n  y = 2;\n";
+        let (output, changed) = normalize(input, IndentStyle::Tab, 4, true);
+        assert_eq!(
+            output,
+            "\tcall(one,\n\t     two);\n\t\t  x = 1;\n  y = 2;\n"
+        );
+        assert_eq!(changed, 0);
+        let (output, _) = normalize(input, IndentStyle::Tab, 4, false);
+        assert_eq!(output, "\tcall(one,\n\t\t two);\n\t\t  x = 1;\n  y = 2;\n");
+
+        let (output, changed) = normalize(input, IndentStyle::Space, 4, true);
+        assert_eq!(
+            output,
+            "    call(one,\n         two);\n          x = 1;\n  y = 2;\n"
+        );
+        assert_eq!(changed, 3);
+    }
+}
@@ -0,0 +1,178 @@
+//! Write-ahead journal for `--journal` and `--rollback`.
+//!
+//! Every rewrite appends a `plan` line, with the hash of the original
+//! content, before the file is touched and a `done` line once it has been
+//! replaced. Each line is synced, so after a crash the journal lists every
+//! file that may have changed; the originals are in the `--backup` copies.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::cache;
+
+const HEADER: &str = "# rtrim journal v1";
+
+/// Hash of a file's content as recorded in the journal.
+pub fn content_hash(content: &[u8]) -> u64 {
+    cache::content_key("journal", content)
+}
+
+/// An open journal, shared by all worker threads.
+#[derive(Debug)]
+pub struct Journal {
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Starts a new journal at `path`, replacing any previous one.
+    pub fn create(path: &Path) -> io::Result<Journal> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", HEADER)?;
+        file.sync_all()?;
+        Ok(Journal {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Records that `path`, currently holding content with hash `original`,
+    /// is about to be replaced.
+    pub fn plan(&self, path: &Path, original: u64) -> io::Result<()> {
+        self.append(&format!("plan\t{:016x}\t{}", original, line_path(path)?))
+    }
+
+    /// Records that the new content of `path` is in place.
+    pub fn done(&self, path: &Path) -> io::Result<()> {
+        self.append(&format!("done\t{}", line_path(path)?))
+    }
+
+    fn append(&self, line: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        file.sync_data()
+    }
+}
+
+/// `path` as one journal field; paths that cannot be written on one line
+/// are refused rather than recorded ambiguously.
+fn line_path(path: &Path) -> io::Result<&str> {
+    path.to_str().filter(|p| !p.contains('\n')).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the journal only records UTF-8 paths without newlines",
+        )
+    })
+}
+
+/// A file the journal says was, or was about to be, rewritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// Hash of the content before the rewrite.
+    pub original: u64,
+    /// Whether the rewrite was recorded as complete.
+    pub done: bool,
+}
+
+/// Reads the entries of a journal, in the order they were planned. A final
+/// line cut short by a crash is ignored.
+pub fn load(path: &Path) -> io::Result<Vec<Entry>> {
+    let text = fs::read_to_string(path)?;
+    let invalid = |line: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid journal line {:?}", line),
+        )
+    };
+    let complete = match text.rfind('\n') {
+        Some(end) => &text[..end],
+        None => "",
+    };
+    let mut lines = complete.lines();
+    if lines.next() != Some(HEADER) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} is not an rtrim journal", path),
+        ));
+    }
+
+    let mut entries: Vec<Entry> = Vec::new();
+    for line in lines {
+        match line.split_once('\t') {
+            Some(("plan", rest)) => {
+                let (hash, file) = rest.split_once('\t').ok_or_else(|| invalid(line))?;
+                let original = u64::from_str_radix(hash, 16).map_err(|_| invalid(line))?;
+                entries.push(Entry {
+                    path: PathBuf::from(file),
+                    original,
+                    done: false,
+                });
+            }
+            Some(("done", file)) => {
+                let entry = entries
+                    .iter_mut()
+                    .rev()
+                    .find(|entry| entry.path == Path::new(file))
+                    .ok_or_else(|| invalid(line))?;
+                entry.done = true;
+            }
+            _ => return Err(invalid(line)),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    #[test]
+    fn test_journal_round_trip_ignores_cut_off_line() {
+        let dir = std::env::temp_dir().join(format!("rtrim_journal_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("journal");
+
+        let journal = Journal::create(&path).unwrap();
+        journal.plan(Path::new("a.txt"), 1).unwrap();
+        journal.done(Path::new("a.txt")).unwrap();
+        journal.plan(Path::new("b c.txt"), u64::MAX).unwrap();
+        drop(journal);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"done\tb c.t").unwrap();
+
+        assert_eq!(
+            load(&path).unwrap(),
+            vec![
+                Entry {
+                    path: PathBuf::from("a.txt"),
+                    original: 1,
+                    done: true,
+                },
+                Entry {
+                    path: PathBuf::from("b c.txt"),
+                    original: u64::MAX,
+                    done: false,
+                },
+            ]
+        );
+
+        fs::write(&path, "not a journal\n").unwrap();
+        assert_eq!(load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_refuses_paths_with_newlines() {
+        let dir = std::env::temp_dir().join(format!("rtrim_journal_nl_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let journal = Journal::create(&dir.join("journal")).unwrap();
+        assert_eq!(
+            journal.plan(Path::new("a\nb"), 0).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,27 @@
+//! JSON output helpers, shared by notebook rewriting and JSON reports.
+
+use std::fmt::Write as _;
+
+/// Encodes `text` as a JSON string literal. Only quotes, backslashes and
+/// control characters are escaped, matching how Jupyter writes notebooks.
+pub fn encode_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,461 @@
+//! Minimal, language-aware scanning used by the source-code trimming modes.
+//!
+//! This is not a full lexer: it only tracks enough state (comments and string
+//! literals) to tell where each line ends and where line comments begin, which
+//! is all the source-aware modes need.
+
+use std::path::Path;
+
+/// Languages with a known comment/string syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    C,
+    Python,
+}
+
+/// File extensions mapped to the language used to scan them.
+const EXTENSIONS: &[(&str, Language)] = &[
+    ("rs", Language::Rust),
+    ("c", Language::C),
+    ("h", Language::C),
+    ("cc", Language::C),
+    ("cpp", Language::C),
+    ("hpp", Language::C),
+    ("py", Language::Python),
+    ("pyi", Language::Python),
+];
+
+impl Language {
+    /// Detects the language from the file extension (case-insensitive).
+    pub fn from_path(path: &Path) -> Option<Language> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        EXTENSIONS
+            .iter()
+            .find(|(known, _)| *known == ext)
+            .map(|(_, lang)| *lang)
+    }
+}
+
+/// Where the end of a line falls relative to the language's syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnd {
+    Code,
+    Comment,
+    String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Code,
+    LineComment,
+    /// Block comment with its nesting depth (only Rust nests).
+    BlockComment(usize),
+    /// Quoted string; `multiline` strings survive a newline.
+    Str {
+        quote: char,
+        multiline: bool,
+    },
+    /// Rust raw string `r#"..."#` with the number of hashes.
+    RawStr(usize),
+    /// Python triple-quoted string.
+    TripleStr(char),
+}
+
+impl State {
+    fn line_end(self) -> LineEnd {
+        match self {
+            State::Code => LineEnd::Code,
+            State::LineComment | State::BlockComment(_) => LineEnd::Comment,
+            State::Str { .. } | State::RawStr(_) | State::TripleStr(_) => LineEnd::String,
+        }
+    }
+}
+
+/// Classifies the end of every line of `content` (one entry per `str::lines` item).
+pub fn line_ends(content: &str, lang: Language) -> Vec<LineEnd> {
+    scan(content, lang)
+        .into_iter()
+        .map(|line| line.end)
+        .collect()
+}
+
+/// Byte offset, within each line, of the line comment marker (`//` or `#`)
+/// that starts on it, if any (one entry per `str::lines` item).
+pub fn line_comment_starts(content: &str, lang: Language) -> Vec<Option<usize>> {
+    scan(content, lang)
+        .into_iter()
+        .map(|line| line.comment_start)
+        .collect()
+}
+
+/// Collapses the run of spaces and tabs between code and an inline line
+/// comment to exactly `spaces` spaces. Comments on their own line, comment
+/// markers inside strings and comments directly attached to code are left
+/// alone. Returns the new content and the number of lines changed; content
+/// the scanner cannot map line by line is returned unchanged.
+pub fn normalize_comment_spacing(content: &str, lang: Language, spaces: usize) -> (String, usize) {
+    let starts = line_comment_starts(content, lang);
+    if starts.len() != content.lines().count() {
+        return (content.to_string(), 0);
+    }
+    let gap = " ".repeat(spaces);
+    let mut output = String::with_capacity(content.len());
+    let mut changed = 0;
+
+    for (line, start) in content.split_inclusive('\n').zip(starts) {
+        let Some((before, comment)) =
+            start.and_then(|start| line.get(..start).zip(line.get(start..)))
+        else {
+            output.push_str(line);
+            continue;
+        };
+        let code = before.trim_end_matches([' ', '\t']);
+        let run = &before[code.len()..];
+        if code.trim().is_empty() || run.is_empty() || run == gap {
+            output.push_str(line);
+            continue;
+        }
+        output.push_str(code);
+        output.push_str(&gap);
+        output.push_str(comment);
+        changed += 1;
+    }
+    (output, changed)
+}
+
+/// What the scanner learned about one line.
+struct ScannedLine {
+    end: LineEnd,
+    comment_start: Option<usize>,
+}
+
+fn scan(content: &str, lang: Language) -> Vec<ScannedLine> {
+    let (offsets, chars): (Vec<usize>, Vec<char>) = content.char_indices().unzip();
+    let mut lines = Vec::new();
+    let mut state = State::Code;
+    let mut line_start = 0;
+    let mut comment_start = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if c == '\n' {
+            lines.push(ScannedLine {
+                end: state.line_end(),
+                comment_start: comment_start.take(),
+            });
+            line_start = offsets[i] + 1;
+            state = match state {
+                State::LineComment => State::Code,
+                State::Str {
+                    multiline: false, ..
+                } => State::Code,
+                other => other,
+            };
+            i += 1;
+            continue;
+        }
+
+        match state {
+            State::Code => {
+                if starts_line_comment(&chars, i, lang) {
+                    state = State::LineComment;
+                    comment_start = Some(offsets[i] - line_start);
+                } else if lang != Language::Python && c == '/' && next == Some('*') {
+                    state = State::BlockComment(1);
+                    i += 1;
+                } else if let Some((skip, new_state)) = string_start(&chars, i, lang) {
+                    state = new_state;
+                    i += skip - 1;
+                }
+            }
+            State::LineComment => {}
+            State::BlockComment(depth) => {
+                if c == '*' && next == Some('/') {
+                    state = if depth > 1 {
+                        State::BlockComment(depth - 1)
+                    } else {
+                        State::Code
+                    };
+                    i += 1;
+                } else if lang == Language::Rust && c == '/' && next == Some('*') {
+                    state = State::BlockComment(depth + 1);
+                    i += 1;
+                }
+            }
+            State::Str { quote, .. } => {
+                if c == '\\' {
+                    // Skip the escaped character, unless it is the newline itself.
+                    if next.is_some() && next != Some('\n') {
+                        i += 1;
+                    }
+                } else if c == quote {
+                    state = State::Code;
+                }
+            }
+            State::RawStr(hashes) => {
+                if c == '"' && (1..=hashes).all(|k| chars.get(i + k) == Some(&'#')) {
+                    state = State::Code;
+                    i += hashes;
+                }
+            }
+            State::TripleStr(quote) => {
+                if c == '\\' {
+                    // Skip the escaped character, unless it is the newline itself.
+                    if next.is_some() && next != Some('\n') {
+                        i += 1;
+                    }
+                } else if c == quote && next == Some(quote) && chars.get(i + 2) == Some(&quote) {
+                    state = State::Code;
+                    i += 2;
+                }
+            }
+        }
+        i += 1;
+    }
+
+    if chars.last().is_some_and(|&c| c != '\n') {
+        lines.push(ScannedLine {
+            end: state.line_end(),
+            comment_start,
+        });
+    }
+    lines
+}
+
+fn starts_line_comment(chars: &[char], i: usize, lang: Language) -> bool {
+    match lang {
+        Language::Python => chars[i] == '#',
+        Language::Rust | Language::C => chars[i] == '/' && chars.get(i + 1) == Some(&'/'),
+    }
+}
+
+/// Detects a string literal opening at `i`, returning how many characters the
+/// opening delimiter spans and the state it enters.
+fn string_start(chars: &[char], i: usize, lang: Language) -> Option<(usize, State)> {
+    let c = chars[i];
+    let at = |k: usize| chars.get(i + k).copied();
+
+    match lang {
+        Language::Rust => {
+            // Raw strings: r"..." or r#"..."#.
+            if c == 'r' && !is_ident_char(i.checked_sub(1).map(|p| chars[p])) {
+                let hashes = chars[i + 1..].iter().take_while(|&&h| h == '#').count();
+                if at(1 + hashes) == Some('"') {
+                    return Some((2 + hashes, State::RawStr(hashes)));
+                }
+            }
+            if c == '"' {
+                return Some((
+                    1,
+                    State::Str {
+                        quote: '"',
+                        multiline: true,
+                    },
+                ));
+            }
+            if c == '\'' {
+                // Char literals ('a', '\n'); anything else is a lifetime.
+                if at(1) == Some('\\') {
+                    return Some((
+                        1,
+                        State::Str {
+                            quote: '\'',
+                            multiline: false,
+                        },
+                    ));
+                }
+                if at(2) == Some('\'') {
+                    return Some((3, State::Code));
+                }
+            }
+            None
+        }
+        Language::C => match c {
+            '"' | '\'' => Some((
+                1,
+                State::Str {
+                    quote: c,
+                    multiline: false,
+                },
+            )),
+            _ => None,
+        },
+        Language::Python => match c {
+            '"' | '\'' if at(1) == Some(c) && at(2) == Some(c) => Some((3, State::TripleStr(c))),
+            '"' | '\'' => Some((
+                1,
+                State::Str {
+                    quote: c,
+                    multiline: false,
+                },
+            )),
+            _ => None,
+        },
+    }
+}
+
+fn is_ident_char(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            Language::from_path(Path::new("a/main.rs")),
+            Some(Language::Rust)
+        );
+        assert_eq!(
+            Language::from_path(Path::new("x.PY")),
+            Some(Language::Python)
+        );
+        assert_eq!(Language::from_path(Path::new("lib.h")), Some(Language::C));
+        assert_eq!(Language::from_path(Path::new("README.md")), None);
+        assert_eq!(Language::from_path(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn test_rust_line_comments() {
+        let src = "let a = 1; // note\nlet b = \"// not a comment\";\n// whole line\n";
+        assert_eq!(
+            line_ends(src, Language::Rust),
+            vec![LineEnd::Comment, LineEnd::Code, LineEnd::Comment]
+        );
+    }
+
+    #[test]
+    fn test_rust_block_comments() {
+        let src = "/* start\n   middle\n   end */ code\ncode /* inline */\n";
+        assert_eq!(
+            line_ends(src, Language::Rust),
+            vec![
+                LineEnd::Comment,
+                LineEnd::Comment,
+                LineEnd::Code,
+                LineEnd::Code
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rust_nested_block_comments() {
+        let src = "/* outer /* inner */\n still outer */\nx\n";
+        assert_eq!(
+            line_ends(src, Language::Rust),
+            vec![LineEnd::Comment, LineEnd::Code, LineEnd::Code]
+        );
+    }
+
+    #[test]
+    fn test_rust_strings_and_chars() {
+        let src =
+            "let s = \"open\nstill string\";\nlet c = '\"'; let l: &'a str = r#\"raw \"/*\"#;\n";
+        assert_eq!(
+            line_ends(src, Language::Rust),
+            vec![LineEnd::String, LineEnd::Code, LineEnd::Code]
+        );
+    }
+
+    #[test]
+    fn test_python_comments_and_triple_quotes() {
+        let src = "x = 1  # note\ns = \"\"\"doc\n# not a comment\n\"\"\"\ny = '#'\n";
+        assert_eq!(
+            line_ends(src, Language::Python),
+            vec![
+                LineEnd::Comment,
+                LineEnd::String,
+                LineEnd::String,
+                LineEnd::Code,
+                LineEnd::Code
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaped_newline_in_triple_quotes_keeps_line_count() {
+        let src = "s = \"\"\"abc\\\ndef   \n\"\"\"\n# c   \n";
+        assert_eq!(
+            line_ends(src, Language::Python),
+            vec![
+                LineEnd::String,
+                LineEnd::String,
+                LineEnd::Code,
+                LineEnd::Comment
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_comments() {
+        let src = "int a; /* c */\nchar *s = \"/*\"; // tail\n";
+        assert_eq!(
+            line_ends(src, Language::C),
+            vec![LineEnd::Code, LineEnd::Comment]
+        );
+    }
+
+    #[test]
+    fn test_line_comment_starts() {
+        let src = "let s = \"é // no\"; // yes\n/* // */ x\n// whole\n";
+        assert_eq!(
+            line_comment_starts(src, Language::Rust),
+            vec![Some(20), None, Some(0)]
+        );
+        assert_eq!(
+            line_comment_starts("x = '#'  # c\ny", Language::Python),
+            vec![Some(9), None]
+        );
+    }
+
+    #[test]
+    fn test_normalize_rust_comment_spacing() {
+        let src = "let a = 1;    // one\nlet s = \"x    // y\";\n    // own line\nf();\t// tab\ng();// tight\n";
+        assert_eq!(
+            normalize_comment_spacing(src, Language::Rust, 1),
+            (
+                "let a = 1; // one\nlet s = \"x    // y\";\n    // own line\nf(); // tab\ng();// tight\n"
+                    .to_string(),
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn test_normalize_python_comment_spacing() {
+        let src = "x = 1 # a\ny = '  #'      # b\n    # own line\nz = 3  # c";
+        assert_eq!(
+            normalize_comment_spacing(src, Language::Python, 2),
+            (
+                "x = 1  # a\ny = '  #'  # b\n    # own line\nz = 3  # c".to_string(),
+                2
+            )
+        );
+    }
+
+    #[test]
+    fn test_normalize_keeps_every_line_after_escaped_newline_in_triple_quotes() {
+        let src = "s = \"\"\"abc\\\ndef\n\"\"\"; fo   = 1\nx = 1    # c\nlast = 2\n";
+        let (output, _) = normalize_comment_spacing(src, Language::Python, 1);
+        let (before, after): (Vec<&str>, Vec<&str>) =
+            (src.lines().collect(), output.lines().collect());
+        assert_eq!(after.len(), before.len());
+        assert_eq!(after[..3], before[..3]);
+        assert_eq!(after[4], "last = 2");
+    }
+
+    #[test]
+    fn test_final_line_without_newline() {
+        assert_eq!(
+            line_ends("a\n// b", Language::Rust),
+            vec![LineEnd::Code, LineEnd::Comment]
+        );
+        assert!(line_ends("", Language::Rust).is_empty());
+    }
+}
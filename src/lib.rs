@@ -0,0 +1,9555 @@
+//! Core of rtrim: trailing whitespace trimming, single-file and folder
+//! processing, and the option parsing behind the `rtrim` binary.
+//!
+//! Nothing here prints or exits on its own: output goes to the sinks given to
+//! [`Reporter::with_sinks`] (or [`run`]), and results are returned.
+
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt;
+#[cfg(unix)]
+use std::fs::Permissions;
+use std::fs::{self, File, FileType, Metadata, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+#[cfg(feature = "progress")]
+use std::io::IsTerminal;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::net::Shutdown;
+use std::ops::{AddAssign, Range};
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(unix)]
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Component, Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::SystemTime;
+
+mod blame;
+mod cache;
+mod changes;
+mod diff;
+mod editorconfig;
+mod error;
+mod gitattributes;
+mod gitignore;
+#[cfg(feature = "http")]
+mod http;
+mod indent;
+mod journal;
+mod json;
+mod lang;
+#[cfg(feature = "ipynb")]
+mod notebook;
+#[cfg(feature = "regex")]
+mod pattern;
+#[cfg(feature = "progress")]
+mod progress;
+mod significant;
+
+use editorconfig::EditorConfigs;
+use error::Context;
+pub use error::RtrimError;
+use gitattributes::{Eol, EolRules};
+use gitignore::Gitignore;
+use indent::IndentStyle;
+use journal::Journal;
+use lang::{Language, LineEnd};
+#[cfg(feature = "regex")]
+use pattern::Pattern;
+#[cfg(feature = "progress")]
+use progress::Progress;
+
+/// Defines the operation mode and target path.
+///
+/// Built from command-line arguments by [`parse_config`], or with
+/// [`Config::new`] for the default options.
+#[derive(Debug, Default)]
+pub struct Config {
+    pub mode: Mode,
+    pub path: PathBuf,
+    /// Targets of a `Mode::Paths` run, processed in order.
+    pub paths: Vec<PathBuf>,
+    verbose: bool,
+    /// Do not print the per-file `Processed:` lines or the end-of-run summary.
+    quiet: bool,
+    /// Do not print per-file errors; they still count for the exit status.
+    quiet_errors: bool,
+    /// Print the full error chain (and backtrace, if enabled) for failures.
+    verbose_errors: bool,
+    /// Maximum number of warnings to print before suppressing the rest.
+    max_warnings: Option<usize>,
+    /// Warn whenever trimming changes whether a file ends with a newline.
+    warn_eof_newline: bool,
+    /// End every non-empty file with exactly one newline.
+    final_newline: bool,
+    /// Collapse the blank lines at the end of a file into its last newline.
+    squeeze_blank_eof: bool,
+    /// Files with more lines than this are skipped (likely generated).
+    max_lines: Option<usize>,
+    /// Trim files that contain unresolved merge conflict markers.
+    allow_conflicts: bool,
+    /// In folder mode, skip files larger than a percentile of the tree's sizes.
+    skip_outliers: bool,
+    /// Percentile for `--skip-outliers`.
+    outlier_percentile: Option<f64>,
+    /// Only rewrite files with at least this many lines to trim.
+    min_dirty_lines: Option<usize>,
+    /// Skip files where trimming would change more than this fraction of lines.
+    max_changed_fraction: Option<f64>,
+    /// Decode invalid UTF-8 as U+FFFD instead of skipping the file.
+    lossy: bool,
+    /// How files that are not valid UTF-8 are read.
+    encoding: Encoding,
+    /// In stdin mode, echo binary or non-UTF-8 input unchanged instead of
+    /// trimming it or failing.
+    stdin_passthrough: bool,
+    /// File recording content hashes of files known to be clean.
+    hash_cache: Option<PathBuf>,
+    /// Only trim lines that end inside a comment (source files only).
+    comments_only: bool,
+    /// Keep the trailing whitespace of lines ending inside a multi-line
+    /// string literal (source files only).
+    preserve_strings: bool,
+    /// Only process files changed between this git revision and the working
+    /// tree.
+    since_commit: Option<String>,
+    /// Only process files staged in git's index.
+    staged: bool,
+    /// Only trim lines last changed by this author, according to `git blame`.
+    author_lines: Option<String>,
+    /// Preserve YAML block scalar content in `.yaml`/`.yml` files.
+    yaml_aware: bool,
+    /// Preserve here-doc bodies in `.sh`/`.bash` scripts.
+    shell_aware: bool,
+    /// Preserve whitespace inside quoted fields of `.csv` files.
+    csv_aware: bool,
+    /// Preserve `<pre>` and `<textarea>` content in `.html`/`.htm` files.
+    html_aware: bool,
+    /// Strip leading as well as trailing whitespace from trimmed lines.
+    strip_both: bool,
+    /// Rewrite leading indentation in a single style.
+    normalize_indent: bool,
+    /// Indentation style for `--normalize-indent`; detected per file if unset.
+    indent_style: Option<IndentStyle>,
+    /// Columns per indentation level (tab width) for `--normalize-indent`.
+    indent_size: Option<usize>,
+    /// Treat only leading tabs as indentation and keep the alignment spaces
+    /// after them.
+    keep_alignment: bool,
+    /// Collapse the whitespace between code and an inline comment.
+    normalize_comment_spacing: bool,
+    /// Spaces left before inline comments by `--normalize-comment-spacing`.
+    comment_spaces: Option<usize>,
+    /// Trim only the code cell sources of `.ipynb` notebooks.
+    #[cfg(feature = "ipynb")]
+    ipynb: bool,
+    /// Canonicalize paths so no file is processed twice in one run.
+    dedupe: bool,
+    /// Report the kinds of trailing whitespace found instead of trimming.
+    report_trailing_types: bool,
+    /// List the files that would be modified, without writing anything.
+    check: bool,
+    /// File that receives the `--check` result when the run completes.
+    dry_run_report: Option<PathBuf>,
+    /// Format of `dry_run_report`; chosen by its extension when unset.
+    dry_run_report_format: Option<ReportFormat>,
+    /// List which files end with a newline and which don't, without trimming.
+    eof_newline_report: bool,
+    /// List the files that start with a byte order mark, without trimming.
+    bom_report: bool,
+    /// List this many files with the longest lines, without trimming.
+    longest_lines_report: Option<usize>,
+    /// Tally trailing whitespace by file age, without trimming.
+    age_report: bool,
+    /// Granularity of whitespace counts; per-file counts are shown only when set.
+    trim_report: Option<TrimReport>,
+    /// Exit status for each kind of run result.
+    exit_codes: ExitCodes,
+    /// Leave files without any write permission bit untouched.
+    skip_readonly: bool,
+    /// Rewrite files in place, keeping their inode, instead of temp + rename.
+    keep_inode: bool,
+    /// `--atomic off`: rewrite files in place without syncing them.
+    non_atomic: bool,
+    /// Rename temp files over their targets in groups of this many, followed
+    /// by one directory sync per group.
+    group_commit: Option<usize>,
+    /// What to do with a file whose temp file cannot be created.
+    on_temp_fail: TempFailPolicy,
+    /// Leave rewritten files with the temp file's default mode (0666 minus the
+    /// umask) instead of copying the original permissions.
+    reset_permissions: bool,
+    /// Give rewritten files back their original modification time.
+    preserve_mtime: bool,
+    /// Copy each file to `<name>.bak` before it is rewritten.
+    backup: bool,
+    /// Write-ahead journal of every rewrite, for `--rollback`; implies backups.
+    journal: Option<PathBuf>,
+    /// Do not descend into directories on a different filesystem.
+    one_filesystem: bool,
+    /// Levels of a folder walk to process; 1 is the given directory only.
+    max_depth: Option<usize>,
+    /// Do not descend into Git submodule working trees.
+    skip_submodules: bool,
+    /// Skip folder entries matched by `.gitignore` files.
+    respect_gitignore: bool,
+    /// Process symlink targets instead of skipping symlinks.
+    follow_symlinks: bool,
+    /// Let followed symlinks lead outside the folder being walked.
+    allow_symlinks_outside_root: bool,
+    /// Write the line endings that the `eol` attribute of `.gitattributes`
+    /// declares.
+    respect_gitattributes_eol: bool,
+    /// Only trim files that `.editorconfig` declares
+    /// `trim_trailing_whitespace = true` for, and honor its
+    /// `insert_final_newline = true`.
+    editorconfig: bool,
+    /// Extensions (lowercase, without the dot) a folder walk is limited to.
+    include: Vec<String>,
+    /// Extensions a folder walk leaves out.
+    exclude: Vec<String>,
+    /// Refuse `--file` paths that lie inside an ignored directory.
+    enforce_ignores_on_file: bool,
+    /// Pipe trimmed `.rs` files through rustfmt before writing them.
+    with_rustfmt: bool,
+    /// Command (program and arguments) run for each modified file.
+    on_modify_exec: Vec<String>,
+    /// Only trim files with at least one line matching this pattern.
+    #[cfg(feature = "regex")]
+    content_match: Option<Pattern>,
+    /// Lines matching this pattern are never trimmed.
+    #[cfg(feature = "regex")]
+    protect_regex: Option<Pattern>,
+    /// Lines ending in a backslash (line continuations) are never trimmed.
+    preserve_continuations: bool,
+    /// Print diffs for the first N files that would change, then stop.
+    preview: Option<usize>,
+    /// Write all changes as one unified diff to this file instead of applying them.
+    output_patch: Option<PathBuf>,
+    /// Draw a progress bar on stderr during folder runs (terminals only).
+    #[cfg(feature = "progress")]
+    progress_bar: bool,
+    /// Socket of a running `--daemon` that processes the `--file` instead.
+    connect: Option<PathBuf>,
+    /// Worker threads for folder processing; one per CPU when unset.
+    threads: Option<Threads>,
+    /// Print one stable `STATUS\tPATH` line per file instead of human output.
+    porcelain: bool,
+    /// Print one JSON object per file and a final summary object instead of
+    /// human output (`--format json`).
+    json_output: bool,
+    /// Print every path left out of the run with the reason, instead of
+    /// modifying files.
+    list_ignored: bool,
+}
+
+impl Config {
+    /// Default options for processing `path` in `mode`.
+    pub fn new(mode: Mode, path: impl Into<PathBuf>) -> Self {
+        Config {
+            mode,
+            path: path.into(),
+            ..Config::default()
+        }
+    }
+
+    /// How modified files are written back, from the write options.
+    fn write_path(&self) -> WritePath {
+        if self.non_atomic {
+            WritePath::InPlace { sync: false }
+        } else if self.keep_inode {
+            WritePath::InPlace { sync: true }
+        } else if let Some(size) = self.group_commit {
+            WritePath::Grouped(size)
+        } else {
+            WritePath::Atomic
+        }
+    }
+
+    /// Whether the `--include`/`--exclude` filters let a folder walk process
+    /// `path`. Files without an extension only pass when nothing is included.
+    fn extension_allowed(&self, path: &Path) -> bool {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension {
+            Some(ext) => {
+                (self.include.is_empty() || self.include.contains(&ext))
+                    && !self.exclude.contains(&ext)
+            }
+            None => self.include.is_empty(),
+        }
+    }
+
+    /// Whether this run rewrites files, as opposed to only reporting on them.
+    fn writes_files(&self) -> bool {
+        !self.report_trailing_types
+            && !self.check
+            && !self.eof_newline_report
+            && !self.bom_report
+            && self.longest_lines_report.is_none()
+            && !self.age_report
+            && self.preview.is_none()
+            && self.output_patch.is_none()
+            && !self.list_ignored
+    }
+
+    /// Whether rewritten files get a `.bak` copy: `--backup`, or the copies
+    /// `--journal` keeps for `--rollback`.
+    fn writes_backups(&self) -> bool {
+        self.backup || self.journal.is_some()
+    }
+}
+
+/// File format of `--dry-run-write-report`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ReportFormat {
+    /// One path per line, with `--trim-report` counts when given.
+    Text,
+    /// An object with per-file and total whitespace counts.
+    Json,
+}
+
+impl ReportFormat {
+    /// The format set by `--dry-run-report-format`, or JSON for `.json` files
+    /// and text for anything else.
+    fn for_report(path: &Path, format: Option<ReportFormat>) -> ReportFormat {
+        format.unwrap_or(if has_extension(path, &["json"]) {
+            ReportFormat::Json
+        } else {
+            ReportFormat::Text
+        })
+    }
+}
+
+/// How a modified file's new content replaces the original.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WritePath {
+    /// Synced temp file renamed over the original.
+    Atomic,
+    /// Like `Atomic`, with the renames batched by `--group-commit`.
+    Grouped(usize),
+    /// Rewritten through the original inode (`--keep-inode`, `--atomic off`).
+    InPlace { sync: bool },
+}
+
+/// Size of the worker pool used for folder processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Threads {
+    /// One worker per available CPU.
+    Auto,
+    Count(usize),
+}
+
+impl Threads {
+    /// Number of workers to start; `Auto` falls back to 1 if the CPU count
+    /// cannot be determined.
+    fn resolve(self) -> usize {
+        match self {
+            Threads::Auto => thread::available_parallelism().map_or(1, |n| n.get()),
+            Threads::Count(count) => count.max(1),
+        }
+    }
+}
+
+/// Encoding assumed for files that are not valid UTF-8.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// Only UTF-8 is text; anything else is skipped as binary.
+    #[default]
+    Utf8,
+    /// Any single-byte, ASCII-compatible encoding such as Latin-1 or
+    /// Windows-1252, trimmed byte by byte.
+    Latin1,
+}
+
+/// What the whitespace counts in reports are expressed in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum TrimReport {
+    /// Number of lines that had trailing whitespace.
+    Lines,
+    /// Number of whitespace characters, by kind.
+    Chars,
+    #[default]
+    Both,
+}
+
+/// How `--on-temp-fail` handles a file whose temp file cannot be created.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum TempFailPolicy {
+    /// Fail the file, as for any other write error.
+    #[default]
+    Error,
+    /// Leave the file untouched with a warning.
+    Skip,
+    /// Rewrite the file through its own inode, as `--keep-inode` does.
+    InPlace,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub enum Mode {
+    File,
+    Folder,
+    /// Read NUL-separated paths from stdin (`find -print0 | rtrim --stdin-paths0`).
+    StdinPaths0,
+    /// Serve trimming requests on a Unix socket (`rtrim --daemon <socket>`).
+    Daemon,
+    /// Trim stdin to stdout as a filter (`rtrim -`).
+    Stdin,
+    /// Trim stdin and answer with a JSON object for editor plugins
+    /// (`rtrim --stdin-json`).
+    StdinJson,
+    /// Process several files and folders in turn (`rtrim a.rs src/`).
+    Paths,
+    /// Restore the files listed in a `--journal` from their backups
+    /// (`rtrim --rollback <journal>`).
+    Rollback,
+    /// Nothing to run: the binary prints its help text.
+    #[default]
+    Help,
+}
+
+const USAGE: &str = "Usage: rtrim --file <path> | rtrim --folder <path> | rtrim <path>... | rtrim --stdin-paths0 | rtrim - | rtrim --help";
+
+/// Directories to be ignored during recursive traversal.
+const IGNORED_DIRS: &[&str] = &[
+    ".git",
+    ".svn",
+    ".hg",
+    "node_modules",
+    "target",
+    "__pycache__",
+    ".venv",
+    "venv",
+    ".idea",
+    ".vscode",
+];
+
+/// Performs manual CLI argument parsing.
+pub fn parse_config(args: &[String]) -> Result<Config, &'static str> {
+    if args.len() < 2 {
+        return Err(USAGE);
+    }
+
+    let mut config = Config::default();
+    // An explicit would-modify status wins over the `--check` default.
+    let mut would_modify_set = false;
+
+    // Options are only recognized before the optional "--" end-of-flags marker;
+    // everything after it is passed through unchanged.
+    let mut filtered_args: Vec<&String> = vec![&args[0]];
+    let mut iter = args[1..].iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--" => {
+                filtered_args.extend(iter);
+                break;
+            }
+            "--verbose" | "-v" => config.verbose = true,
+            "--quiet" | "-q" => config.quiet = true,
+            "--quiet-errors" => config.quiet_errors = true,
+            "--verbose-errors" => config.verbose_errors = true,
+            "--warn-eof-newline" => config.warn_eof_newline = true,
+            "--final-newline" => config.final_newline = true,
+            "--squeeze-blank-eof" => config.squeeze_blank_eof = true,
+            "--trim-doc-comments-only" => config.comments_only = true,
+            "--preserve-continuations" => config.preserve_continuations = true,
+            "--preserve-trailing-whitespace-in-strings" => config.preserve_strings = true,
+            "--yaml" | "--trim-yaml-aware" => config.yaml_aware = true,
+            "--shell" => config.shell_aware = true,
+            "--csv" | "--trim-csv-aware" => config.csv_aware = true,
+            "--html" | "--trim-html-aware" => config.html_aware = true,
+            "--dedupe" => config.dedupe = true,
+            "--skip-readonly" => config.skip_readonly = true,
+            "--keep-inode" => config.keep_inode = true,
+            "--one-filesystem" => config.one_filesystem = true,
+            "--max-depth" => {
+                config.max_depth = Some(
+                    iter.next()
+                        .and_then(|v| v.parse::<usize>().ok())
+                        .filter(|depth| *depth > 0)
+                        .ok_or("Invalid value for --max-depth: expected a positive integer")?,
+                );
+            }
+            "--skip-submodules" => config.skip_submodules = true,
+            "--respect-gitignore" => config.respect_gitignore = true,
+            "--follow-symlinks" => config.follow_symlinks = true,
+            "--allow-symlinks-outside-root" => config.allow_symlinks_outside_root = true,
+            "--respect-gitattributes-eol" => config.respect_gitattributes_eol = true,
+            "--editorconfig" => config.editorconfig = true,
+            "--backup" => config.backup = true,
+            "--journal" => {
+                config.journal = Some(PathBuf::from(
+                    iter.next()
+                        .ok_or("Missing value for --journal: expected a file path")?,
+                ));
+            }
+            "--include" => config.include.push(parse_extension(
+                iter.next(),
+                "Invalid value for --include: expected a file extension",
+            )?),
+            "--exclude" => config.exclude.push(parse_extension(
+                iter.next(),
+                "Invalid value for --exclude: expected a file extension",
+            )?),
+            "--with-rustfmt" | "--trim-then-format" => config.with_rustfmt = true,
+            "--enforce-ignores-on-file" => config.enforce_ignores_on_file = true,
+            "--lossy" => config.lossy = true,
+            "--encoding" => {
+                config.encoding = match iter.next().map(String::as_str) {
+                    Some("utf8" | "utf-8") => Encoding::Utf8,
+                    Some("latin1" | "latin-1") => Encoding::Latin1,
+                    _ => return Err("Invalid value for --encoding: expected utf8 or latin1"),
+                };
+            }
+            "--null-output-on-stdin" => config.stdin_passthrough = true,
+            #[cfg(feature = "progress")]
+            "--progress-bar" => config.progress_bar = true,
+            #[cfg(not(feature = "progress"))]
+            "--progress-bar" => {
+                return Err("--progress-bar requires rtrim to be built with the `progress` feature")
+            }
+            #[cfg(feature = "ipynb")]
+            "--ipynb" => config.ipynb = true,
+            #[cfg(not(feature = "ipynb"))]
+            "--ipynb" => return Err("--ipynb requires rtrim to be built with the `ipynb` feature"),
+            "--porcelain" => config.porcelain = true,
+            "--format" => {
+                config.json_output = match iter.next().map(String::as_str) {
+                    Some("json") => true,
+                    Some("text") => false,
+                    _ => return Err("Invalid value for --format: expected text or json"),
+                };
+            }
+            "--list-ignored" => config.list_ignored = true,
+            #[cfg(feature = "regex")]
+            "--content-match" => {
+                let source = iter
+                    .next()
+                    .ok_or("Missing value for --content-match: expected a regex")?;
+                config.content_match = Some(
+                    Pattern::new(source)
+                        .map_err(|_| "Invalid value for --content-match: malformed regex")?,
+                );
+            }
+            #[cfg(not(feature = "regex"))]
+            "--content-match" => {
+                return Err("--content-match requires rtrim to be built with the `regex` feature")
+            }
+            #[cfg(feature = "regex")]
+            "--protect-regex" => {
+                let source = iter
+                    .next()
+                    .ok_or("Missing value for --protect-regex: expected a regex")?;
+                config.protect_regex = Some(
+                    Pattern::new(source)
+                        .map_err(|_| "Invalid value for --protect-regex: malformed regex")?,
+                );
+            }
+            #[cfg(not(feature = "regex"))]
+            "--protect-regex" => {
+                return Err("--protect-regex requires rtrim to be built with the `regex` feature")
+            }
+            "--since-commit" => {
+                config.since_commit = Some(
+                    iter.next()
+                        .filter(|rev| !rev.is_empty())
+                        .ok_or("Invalid value for --since-commit: expected a git revision")?
+                        .clone(),
+                );
+            }
+            "--staged" => config.staged = true,
+            "--author-lines" => {
+                config.author_lines = Some(
+                    iter.next()
+                        .filter(|author| !author.is_empty())
+                        .ok_or(
+                            "Invalid value for --author-lines: expected an author name or email",
+                        )?
+                        .clone(),
+                );
+            }
+            "--on-modify-exec" => {
+                config.on_modify_exec = iter
+                    .next()
+                    .map(|cmd| cmd.split_whitespace().map(String::from).collect())
+                    .unwrap_or_default();
+                if config.on_modify_exec.is_empty() {
+                    return Err("Invalid value for --on-modify-exec: expected a command");
+                }
+            }
+            "--report-trailing-types" | "--report-only" => config.report_trailing_types = true,
+            "--check" | "--dry-run" => config.check = true,
+            "--dry-run-write-report" => {
+                config.check = true;
+                config.dry_run_report = Some(PathBuf::from(
+                    iter.next()
+                        .ok_or("Missing value for --dry-run-write-report: expected a file path")?,
+                ));
+            }
+            "--dry-run-report-format" => {
+                config.dry_run_report_format = Some(match iter.next().map(String::as_str) {
+                    Some("text") => ReportFormat::Text,
+                    Some("json") => ReportFormat::Json,
+                    _ => {
+                        return Err(
+                            "Invalid value for --dry-run-report-format: expected text or json",
+                        )
+                    }
+                });
+            }
+            "--dedupe-trailing-newlines-across-files" => config.eof_newline_report = true,
+            "--report-bom" => config.bom_report = true,
+            "--report-age" => config.age_report = true,
+            "--report-longest-lines" => {
+                config.longest_lines_report = Some(parse_count(
+                    iter.next(),
+                    "Invalid value for --report-longest-lines: expected a non-negative integer",
+                )?);
+            }
+            "--allow-conflicts" => config.allow_conflicts = true,
+            "--max-lines" => {
+                config.max_lines = Some(parse_count(
+                    iter.next(),
+                    "Invalid value for --max-lines: expected a non-negative integer",
+                )?);
+            }
+            "--max-changed-fraction" => {
+                config.max_changed_fraction = Some(
+                    iter.next()
+                        .and_then(|v| v.parse::<f64>().ok())
+                        .filter(|f| (0.0..=1.0).contains(f))
+                        .ok_or(
+                            "Invalid value for --max-changed-fraction: expected a number from 0 to 1",
+                        )?,
+                );
+            }
+            "--skip-outliers" => config.skip_outliers = true,
+            "--outlier-percentile" => {
+                config.outlier_percentile = Some(
+                    iter.next()
+                        .and_then(|v| v.parse::<f64>().ok())
+                        .filter(|p| *p > 0.0 && *p <= 100.0)
+                        .ok_or(
+                            "Invalid value for --outlier-percentile: expected a number in (0, 100]",
+                        )?,
+                );
+            }
+            "--min-dirty-lines" => {
+                config.min_dirty_lines = Some(parse_count(
+                    iter.next(),
+                    "Invalid value for --min-dirty-lines: expected a non-negative integer",
+                )?);
+            }
+            "--modified-exit-code" => {
+                let code = parse_count(
+                    iter.next(),
+                    "Invalid value for --modified-exit-code: expected an integer from 0 to 125",
+                )?;
+                if code > 125 {
+                    return Err(
+                        "Invalid value for --modified-exit-code: expected an integer from 0 to 125",
+                    );
+                }
+                config.exit_codes.modified = code as i32;
+                config.exit_codes.would_modify = code as i32;
+                would_modify_set = true;
+            }
+            "--exit-code" => {
+                let value = iter.next().map(String::as_str);
+                parse_exit_code(value, &mut config.exit_codes)?;
+                would_modify_set |= value.is_some_and(|v| v.starts_with("would-modify="));
+            }
+            _ if arg.starts_with("--exit-code=") => {
+                let value = arg.split_once('=').map(|(_, value)| value);
+                parse_exit_code(value, &mut config.exit_codes)?;
+                would_modify_set |= value.is_some_and(|v| v.starts_with("would-modify="));
+            }
+            "--preview" => {
+                config.preview = Some(parse_count(
+                    iter.next(),
+                    "Invalid value for --preview: expected a non-negative integer",
+                )?);
+            }
+            "--output-patch" => {
+                config.output_patch = Some(PathBuf::from(
+                    iter.next()
+                        .ok_or("Missing value for --output-patch: expected a file path")?,
+                ));
+            }
+            "--hash-cache" => {
+                config.hash_cache = Some(PathBuf::from(
+                    iter.next()
+                        .ok_or("Missing value for --hash-cache: expected a file path")?,
+                ));
+            }
+            "--preserve-mtime" => config.preserve_mtime = true,
+            "--preserve-permissions" => {
+                config.reset_permissions =
+                    !parse_on_off(iter.next().map(String::as_str), PRESERVE_PERMISSIONS_ERR)?;
+            }
+            _ if arg.starts_with("--preserve-permissions=") => {
+                config.reset_permissions = !parse_on_off(
+                    arg.split_once('=').map(|(_, value)| value),
+                    PRESERVE_PERMISSIONS_ERR,
+                )?;
+            }
+            "--atomic" => {
+                config.non_atomic = !parse_on_off(iter.next().map(String::as_str), ATOMIC_ERR)?;
+            }
+            _ if arg.starts_with("--atomic=") => {
+                config.non_atomic =
+                    !parse_on_off(arg.split_once('=').map(|(_, value)| value), ATOMIC_ERR)?;
+            }
+            "--on-temp-fail" => {
+                config.on_temp_fail = parse_temp_fail_policy(iter.next().map(String::as_str))?;
+            }
+            _ if arg.starts_with("--on-temp-fail=") => {
+                config.on_temp_fail =
+                    parse_temp_fail_policy(arg.split_once('=').map(|(_, value)| value))?;
+            }
+            "--trim-report" => {
+                config.trim_report = Some(parse_trim_report(iter.next().map(String::as_str))?);
+            }
+            _ if arg.starts_with("--trim-report=") => {
+                config.trim_report = Some(parse_trim_report(
+                    arg.split_once('=').map(|(_, value)| value),
+                )?);
+            }
+            "--strip-both" | "--trim-trailing-and-leading" => config.strip_both = true,
+            "--normalize-indent" => config.normalize_indent = true,
+            "--normalize-tabs-in-indent-only" => {
+                config.normalize_indent = true;
+                config.keep_alignment = true;
+            }
+            "--indent-style" => {
+                config.indent_style = Some(match iter.next().map(String::as_str) {
+                    Some("tab") => IndentStyle::Tab,
+                    Some("space") => IndentStyle::Space,
+                    _ => return Err("Invalid value for --indent-style: expected tab or space"),
+                });
+            }
+            "--indent-size" => {
+                let size = parse_count(
+                    iter.next(),
+                    "Invalid value for --indent-size: expected a positive integer",
+                )?;
+                if size == 0 {
+                    return Err("Invalid value for --indent-size: expected a positive integer");
+                }
+                config.indent_size = Some(size);
+            }
+            "--normalize-comment-spacing" | "--trim-whitespace-before-comment-markers" => {
+                config.normalize_comment_spacing = true
+            }
+            "--comment-spaces" => {
+                let spaces = parse_count(
+                    iter.next(),
+                    "Invalid value for --comment-spaces: expected a positive integer",
+                )?;
+                if spaces == 0 {
+                    return Err("Invalid value for --comment-spaces: expected a positive integer");
+                }
+                config.comment_spaces = Some(spaces);
+            }
+            "--group-commit" => {
+                let size = parse_count(
+                    iter.next(),
+                    "Invalid value for --group-commit: expected a positive integer",
+                )?;
+                if size == 0 {
+                    return Err("Invalid value for --group-commit: expected a positive integer");
+                }
+                config.group_commit = Some(size);
+            }
+            "--threads" | "--jobs" | "-j" => {
+                let message = if arg == "--threads" {
+                    "Invalid value for --threads: expected a positive integer or auto"
+                } else {
+                    "Invalid value for --jobs: expected a positive integer or auto"
+                };
+                config.threads = Some(match iter.next().map(String::as_str) {
+                    Some("auto") => Threads::Auto,
+                    value => Threads::Count(
+                        value
+                            .and_then(|v| v.parse().ok())
+                            .filter(|&n| n > 0)
+                            .ok_or(message)?,
+                    ),
+                });
+            }
+            "--connect" => {
+                config.connect = Some(PathBuf::from(
+                    iter.next()
+                        .ok_or("Missing value for --connect: expected a socket path")?,
+                ));
+            }
+            "--max-warnings" => {
+                config.max_warnings = Some(parse_count(
+                    iter.next(),
+                    "Invalid value for --max-warnings: expected a non-negative integer",
+                )?);
+            }
+            _ => filtered_args.push(arg),
+        }
+    }
+
+    if filtered_args.len() < 2 {
+        return Err(USAGE);
+    }
+    if config.porcelain && config.verbose {
+        return Err("--porcelain cannot be combined with --verbose");
+    }
+    if config.quiet && config.verbose {
+        return Err("--quiet cannot be combined with --verbose");
+    }
+    if config.allow_symlinks_outside_root && !config.follow_symlinks {
+        return Err("--allow-symlinks-outside-root requires --follow-symlinks");
+    }
+    if config.lossy && config.encoding == Encoding::Latin1 {
+        return Err("--lossy cannot be combined with --encoding latin1");
+    }
+    if config.staged && config.since_commit.is_some() {
+        return Err("--staged cannot be combined with --since-commit");
+    }
+    if config.porcelain && config.list_ignored {
+        return Err("--porcelain cannot be combined with --list-ignored");
+    }
+    if config.json_output && (config.porcelain || config.verbose || config.list_ignored) {
+        return Err(
+            "--format json cannot be combined with --porcelain, --verbose or --list-ignored",
+        );
+    }
+    if config.json_output && config.connect.is_some() {
+        return Err("--format json cannot be combined with --connect");
+    }
+    if config.json_output
+        && (config.preview.is_some()
+            || config.report_trailing_types
+            || config.eof_newline_report
+            || config.longest_lines_report.is_some()
+            || config.age_report
+            || config.bom_report)
+    {
+        return Err("--format json cannot be combined with --preview or a text report");
+    }
+
+    config.mode = match filtered_args[1].as_str() {
+        "--help" | "-h" => return Ok(config),
+        "--file" => Mode::File,
+        "--folder" => Mode::Folder,
+        "--daemon" => Mode::Daemon,
+        "--stdin-paths0" => Mode::StdinPaths0,
+        "-" | "--stdin" => Mode::Stdin,
+        "--stdin-json" => Mode::StdinJson,
+        "--rollback" => Mode::Rollback,
+        path if !path.starts_with('-') => Mode::Paths,
+        _ => {
+            return Err("Invalid flag. Use --file, --folder, --stdin-paths0, -, paths, or --help.")
+        }
+    };
+
+    if config.check && !would_modify_set {
+        config.exit_codes.would_modify = 1;
+    }
+    if config.json_output && matches!(config.mode, Mode::Stdin | Mode::StdinJson) {
+        return Err("--format json cannot be combined with - or --stdin-json");
+    }
+    if matches!(
+        config.mode,
+        Mode::StdinPaths0 | Mode::Stdin | Mode::StdinJson
+    ) {
+        return Ok(config);
+    }
+
+    if filtered_args.len() < 3 && config.mode != Mode::Paths {
+        return Err(match config.mode {
+            Mode::File => "Usage: rtrim --file <path>",
+            Mode::Daemon => "Usage: rtrim --daemon <socket>",
+            Mode::Rollback => "Usage: rtrim --rollback <journal>",
+            _ => "Usage: rtrim --folder <path>",
+        });
+    }
+
+    // Several paths, given bare or after --file and --folder, are each
+    // processed according to their type.
+    let targets: Vec<&String> = match config.mode {
+        Mode::Paths => filtered_args[1..].to_vec(),
+        Mode::File | Mode::Folder => filtered_args[2..]
+            .iter()
+            .copied()
+            .filter(|arg| !matches!(arg.as_str(), "--file" | "--folder"))
+            .collect(),
+        _ => Vec::new(),
+    };
+    if targets.len() > 1 || config.mode == Mode::Paths {
+        if config.connect.is_some() {
+            return Err("--connect takes a single --file path");
+        }
+        if targets.iter().any(|target| is_url(Path::new(target))) {
+            return Err("A URL must be given alone to --file");
+        }
+        config.mode = Mode::Paths;
+        config.paths = targets.into_iter().map(PathBuf::from).collect();
+    }
+
+    if config.connect.is_some() && config.mode != Mode::File {
+        return Err("--connect requires --file");
+    }
+    if config.mode == Mode::Daemon && config.group_commit.is_some() {
+        return Err("--group-commit cannot be combined with --daemon");
+    }
+
+    if config.group_commit.is_some() && config.keep_inode {
+        return Err("--group-commit cannot be combined with --keep-inode");
+    }
+    if config.group_commit.is_some() && config.preserve_mtime {
+        return Err("--group-commit cannot be combined with --preserve-mtime");
+    }
+    if config.group_commit.is_some() && config.non_atomic {
+        return Err("--group-commit cannot be combined with --atomic off");
+    }
+    if config.group_commit.is_some() && !config.on_modify_exec.is_empty() {
+        return Err("--group-commit cannot be combined with --on-modify-exec");
+    }
+
+    if config.mode == Mode::Paths {
+        return Ok(config);
+    }
+    config.path = PathBuf::from(filtered_args[2]);
+    if config.mode == Mode::File && is_url(&config.path) {
+        if !cfg!(feature = "http") {
+            return Err("Reading a URL requires rtrim to be built with the `http` feature");
+        }
+        if config.writes_files() {
+            return Err("A URL cannot be rewritten; add --report-only to report on it");
+        }
+    }
+    Ok(config)
+}
+
+/// Whether a `--file` target is an `http://` or `https://` URL.
+fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|p| p.starts_with("http://") || p.starts_with("https://"))
+}
+
+fn parse_trim_report(value: Option<&str>) -> Result<TrimReport, &'static str> {
+    match value {
+        Some("lines") => Ok(TrimReport::Lines),
+        Some("chars") => Ok(TrimReport::Chars),
+        Some("both") => Ok(TrimReport::Both),
+        _ => Err("Invalid value for --trim-report: expected lines, chars or both"),
+    }
+}
+
+/// Parses a `CELL=N` value of `--exit-code` into `codes`.
+fn parse_exit_code(value: Option<&str>, codes: &mut ExitCodes) -> Result<(), &'static str> {
+    const ERR: &str = "Invalid value for --exit-code: expected CELL=N with CELL one of clean, \
+                       modified, would-modify, binary-only or error, and N from 0 to 125";
+    let (cell, code) = value.and_then(|v| v.split_once('=')).ok_or(ERR)?;
+    let code = code
+        .parse::<i32>()
+        .ok()
+        .filter(|code| (0..=125).contains(code));
+    let code = code.ok_or(ERR)?;
+    match cell {
+        "clean" => codes.clean = code,
+        "modified" => codes.modified = code,
+        "would-modify" => codes.would_modify = code,
+        "binary-only" => codes.binary_only = code,
+        "error" => codes.error = code,
+        _ => return Err(ERR),
+    }
+    Ok(())
+}
+
+/// Parses the value of `--on-temp-fail`.
+fn parse_temp_fail_policy(value: Option<&str>) -> Result<TempFailPolicy, &'static str> {
+    match value {
+        Some("error") => Ok(TempFailPolicy::Error),
+        Some("skip") => Ok(TempFailPolicy::Skip),
+        Some("inplace") => Ok(TempFailPolicy::InPlace),
+        _ => Err("Invalid value for --on-temp-fail: expected error, skip or inplace"),
+    }
+}
+
+const PRESERVE_PERMISSIONS_ERR: &str =
+    "Invalid value for --preserve-permissions: expected on or off";
+const ATOMIC_ERR: &str = "Invalid value for --atomic: expected on or off";
+
+/// Parses an `on|off` option value, returning `err` when missing or invalid.
+fn parse_on_off(value: Option<&str>, err: &'static str) -> Result<bool, &'static str> {
+    match value {
+        Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        _ => Err(err),
+    }
+}
+
+/// Parses an extension for `--include`/`--exclude`: `rs` and `.rs` both
+/// become `rs`.
+fn parse_extension(value: Option<&String>, err: &'static str) -> Result<String, &'static str> {
+    value
+        .map(|ext| ext.strip_prefix('.').unwrap_or(ext).to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .ok_or(err)
+}
+
+/// Parses the numeric value of an option, returning `err` when missing or invalid.
+fn parse_count(value: Option<&String>, err: &'static str) -> Result<usize, &'static str> {
+    value.and_then(|v| v.parse().ok()).ok_or(err)
+}
+
+/// Moves to the start of the line and erases it, removing the progress bar.
+#[cfg(feature = "progress")]
+const CLEAR_LINE: &str = "\r\x1b[K";
+
+/// A single line of output destined for one of the two standard streams.
+enum Line {
+    Out(String),
+    Err(String),
+    /// Bytes for stdout as they are, such as the text filtered from stdin.
+    Raw(Vec<u8>),
+    /// Replaces the progress bar kept at the bottom of stderr.
+    #[cfg(feature = "progress")]
+    Progress(String),
+}
+
+/// Centralizes all output so that noisy runs can be capped and lines from
+/// concurrent workers never interleave.
+///
+/// Lines are sent over a channel to a dedicated printer thread, which is the
+/// only place that touches stdout/stderr. Serial and parallel runs share the
+/// same path, so output is always written one whole line at a time.
+#[derive(Debug)]
+pub struct Reporter {
+    max_warnings: Option<usize>,
+    warnings: AtomicUsize,
+    sender: Option<Sender<Line>>,
+    printer: Option<JoinHandle<()>>,
+}
+
+impl Default for Reporter {
+    fn default() -> Self {
+        Reporter::new(None)
+    }
+}
+
+impl Reporter {
+    fn new(max_warnings: Option<usize>) -> Self {
+        Reporter::with_sinks(max_warnings, Box::new(io::stdout()), Box::new(io::stderr()))
+    }
+
+    /// Creates a reporter whose printer thread writes to the given sinks.
+    pub fn with_sinks(
+        max_warnings: Option<usize>,
+        mut out: Box<dyn Write + Send>,
+        mut err: Box<dyn Write + Send>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel::<Line>();
+        let printer = thread::spawn(move || {
+            #[cfg(feature = "progress")]
+            let mut bar: Option<String> = None;
+            for line in receiver {
+                // Any other line is printed above the bar: clear it first and
+                // redraw it afterwards.
+                #[cfg(feature = "progress")]
+                if bar.is_some() {
+                    let _ = write!(err, "{}", CLEAR_LINE);
+                }
+                // Output errors (e.g. a closed pipe) must not abort processing.
+                let _ = match line {
+                    Line::Out(text) => writeln!(out, "{}", text).and_then(|()| out.flush()),
+                    Line::Err(text) => writeln!(err, "{}", text),
+                    Line::Raw(bytes) => out.write_all(&bytes).and_then(|()| out.flush()),
+                    #[cfg(feature = "progress")]
+                    Line::Progress(text) => {
+                        bar = Some(text);
+                        Ok(())
+                    }
+                };
+                #[cfg(feature = "progress")]
+                if let Some(text) = &bar {
+                    let _ = write!(err, "{}", text).and_then(|()| err.flush());
+                }
+            }
+            #[cfg(feature = "progress")]
+            if bar.is_some() {
+                let _ = writeln!(err);
+            }
+            let _ = out.flush();
+            let _ = err.flush();
+        });
+
+        Reporter {
+            max_warnings,
+            warnings: AtomicUsize::new(0),
+            sender: Some(sender),
+            printer: Some(printer),
+        }
+    }
+
+    fn send(&self, line: Line) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(line);
+        }
+    }
+
+    /// Redraws the progress bar at the bottom of stderr.
+    #[cfg(feature = "progress")]
+    fn progress(&self, bar: String) {
+        self.send(Line::Progress(bar));
+    }
+
+    /// Prints an informational line to stdout.
+    fn info(&self, message: impl fmt::Display) {
+        self.send(Line::Out(message.to_string()));
+    }
+
+    /// Writes `bytes` to stdout unchanged, with no newline added.
+    fn raw(&self, bytes: Vec<u8>) {
+        self.send(Line::Raw(bytes));
+    }
+
+    /// Prints an error to stderr; errors are never capped.
+    fn error(&self, message: impl fmt::Display) {
+        self.send(Line::Err(message.to_string()));
+    }
+
+    /// Prints a warning to stderr unless the cap has been reached.
+    /// Returns whether the warning was actually printed.
+    fn warn(&self, message: impl fmt::Display) -> bool {
+        let count = self.warnings.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if self.max_warnings.is_some_and(|max| count > max) {
+            return false;
+        }
+        self.send(Line::Err(format!("Warning: {}", message)));
+        true
+    }
+
+    /// Total number of warnings raised, including suppressed ones.
+    fn warning_count(&self) -> usize {
+        self.warnings.load(Ordering::Relaxed)
+    }
+
+    /// Number of warnings that were swallowed by the cap.
+    fn suppressed(&self) -> usize {
+        self.max_warnings
+            .map(|max| self.warning_count().saturating_sub(max))
+            .unwrap_or(0)
+    }
+
+    /// Prints the trailing note about suppressed warnings and waits for all
+    /// pending output to be written.
+    fn finish(mut self) {
+        let suppressed = self.suppressed();
+        if suppressed > 0 {
+            self.send(Line::Err(format!(
+                "({} more warnings suppressed)",
+                suppressed
+            )));
+        }
+        self.close();
+    }
+
+    fn close(&mut self) {
+        // Dropping the sender ends the printer loop once the queue drains.
+        self.sender = None;
+        if let Some(printer) = self.printer.take() {
+            let _ = printer.join();
+        }
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Run-wide state shared by every file processed in one invocation.
+#[derive(Debug, Default)]
+pub struct Session {
+    reporter: Reporter,
+    /// Canonical paths already handled, consulted when `--dedupe` is set.
+    seen: Mutex<HashSet<PathBuf>>,
+    /// Trailing whitespace found across all files, by kind.
+    removed: Mutex<WhitespaceBreakdown>,
+    /// Set once any file is (or would be) modified.
+    modified: AtomicBool,
+    /// Files processed so far, by outcome.
+    stats: Mutex<Stats>,
+    /// Number of `--on-modify-exec` invocations that failed.
+    hook_failures: AtomicUsize,
+    /// Number of diffs printed for `--preview`.
+    previews: AtomicUsize,
+    /// Text files grouped by whether they end with a newline.
+    eof_newlines: Mutex<EofNewlines>,
+    /// Longest line of each text file, for `--report-longest-lines`.
+    longest_lines: Mutex<Vec<LongestLine>>,
+    /// Trailing whitespace by file age, for `--report-age`.
+    ages: Mutex<AgeBuckets>,
+    /// Text files grouped by their byte order mark, for `--report-bom`.
+    boms: Mutex<ByteOrderMarks>,
+    /// `.gitattributes` line endings, for `--respect-gitattributes-eol`.
+    eol_rules: EolRules,
+    /// `.editorconfig` properties, for `--editorconfig`.
+    editorconfigs: EditorConfigs,
+    /// Open `--journal`, set before any file is processed.
+    journal: OnceLock<Journal>,
+    /// Content hashes known to be clean, for `--hash-cache`.
+    clean_hashes: Mutex<HashSet<u64>>,
+    /// Files left dirty because of `--min-dirty-lines`.
+    below_threshold: AtomicUsize,
+    /// Folder entries that are neither a file, a directory nor a symlink.
+    special_entries: AtomicUsize,
+    /// Files that would be modified, for `--dry-run-write-report`.
+    would_modify: Mutex<Vec<(PathBuf, WhitespaceBreakdown)>>,
+    /// Lines trimmed in each modified file, until its `--format json`
+    /// record is printed.
+    lines_trimmed: Mutex<HashMap<PathBuf, usize>>,
+    /// Accumulated diff for `--output-patch`.
+    patch: Mutex<String>,
+    /// Written temp files waiting for their `--group-commit` rename.
+    group: Mutex<Vec<StagedWrite>>,
+    /// Canonical paths of the files changed since `--since-commit`.
+    changed: OnceLock<HashSet<PathBuf>>,
+    /// Canonical paths of the files staged in git's index, for `--staged`.
+    staged: OnceLock<HashSet<PathBuf>>,
+    /// Canonical paths of the files with changes not yet staged, which
+    /// `--staged` leaves alone.
+    partially_staged: OnceLock<HashSet<PathBuf>>,
+    /// Largest file size processed, set by the `--skip-outliers` scan.
+    size_limit: OnceLock<u64>,
+    /// Set once `--with-rustfmt` found no rustfmt to run.
+    rustfmt_missing: AtomicBool,
+    /// Progress bar of a `--progress-bar` folder run, set after counting.
+    #[cfg(feature = "progress")]
+    progress: OnceLock<Progress>,
+}
+
+/// Files seen by `--dedupe-trailing-newlines-across-files`, by final byte.
+#[derive(Debug, Default)]
+struct EofNewlines {
+    with_newline: Vec<PathBuf>,
+    without_newline: Vec<PathBuf>,
+}
+
+/// The longest line of a file, for `--report-longest-lines`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LongestLine {
+    /// Length in characters, line terminator excluded.
+    length: usize,
+    /// 1-based line number; the first of equally long lines.
+    line: usize,
+    path: PathBuf,
+}
+
+impl LongestLine {
+    /// Finds the longest line of `content`, or `None` if it has no lines.
+    fn find(path: &Path, content: &str) -> Option<LongestLine> {
+        let mut longest: Option<LongestLine> = None;
+        for (index, line) in content.lines().enumerate() {
+            let length = line.chars().count();
+            if longest.as_ref().is_none_or(|l| length > l.length) {
+                longest = Some(LongestLine {
+                    length,
+                    line: index + 1,
+                    path: path.to_path_buf(),
+                });
+            }
+        }
+        longest
+    }
+}
+
+/// A byte order mark at the start of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bom {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Bom {
+    /// Longest mark `detect` looks for.
+    const MAX_LEN: usize = 3;
+
+    fn detect(prefix: &[u8]) -> Option<Bom> {
+        match prefix {
+            [0xEF, 0xBB, 0xBF, ..] => Some(Bom::Utf8),
+            [0xFF, 0xFE, ..] => Some(Bom::Utf16Le),
+            [0xFE, 0xFF, ..] => Some(Bom::Utf16Be),
+            _ => None,
+        }
+    }
+}
+
+/// Files seen by `--report-bom`, by byte order mark.
+#[derive(Debug, Default)]
+struct ByteOrderMarks {
+    utf8: Vec<PathBuf>,
+    utf16_le: Vec<PathBuf>,
+    utf16_be: Vec<PathBuf>,
+    without_bom: usize,
+}
+
+impl ByteOrderMarks {
+    fn record(&mut self, bom: Option<Bom>, path: &Path) {
+        let list = match bom {
+            Some(Bom::Utf8) => &mut self.utf8,
+            Some(Bom::Utf16Le) => &mut self.utf16_le,
+            Some(Bom::Utf16Be) => &mut self.utf16_be,
+            None => {
+                self.without_bom += 1;
+                return;
+            }
+        };
+        list.push(path.to_path_buf());
+    }
+}
+
+/// How long ago a file was last modified, for `--report-age`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AgeBucket {
+    Day,
+    Week,
+    Month,
+    Older,
+}
+
+impl AgeBucket {
+    const ALL: [AgeBucket; 4] = [
+        AgeBucket::Day,
+        AgeBucket::Week,
+        AgeBucket::Month,
+        AgeBucket::Older,
+    ];
+
+    /// Buckets a modification time; times in the future count as fresh.
+    fn of(modified: SystemTime, now: SystemTime) -> AgeBucket {
+        const DAY: u64 = 24 * 60 * 60;
+        let age = now.duration_since(modified).unwrap_or_default().as_secs();
+        if age < DAY {
+            AgeBucket::Day
+        } else if age < 7 * DAY {
+            AgeBucket::Week
+        } else if age < 30 * DAY {
+            AgeBucket::Month
+        } else {
+            AgeBucket::Older
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            AgeBucket::Day => "< 1d",
+            AgeBucket::Week => "< 1w",
+            AgeBucket::Month => "< 1mo",
+            AgeBucket::Older => "older",
+        }
+    }
+}
+
+/// Per-bucket totals for `--report-age`, indexed like `AgeBucket::ALL`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct AgeBuckets {
+    files: [usize; 4],
+    dirty_files: [usize; 4],
+    dirty_lines: [usize; 4],
+}
+
+impl AgeBuckets {
+    fn record(&mut self, bucket: AgeBucket, removed: &WhitespaceBreakdown) {
+        let index = bucket as usize;
+        self.files[index] += 1;
+        if removed.lines > 0 {
+            self.dirty_files[index] += 1;
+            self.dirty_lines[index] += removed.lines;
+        }
+    }
+}
+
+impl Session {
+    pub fn new(reporter: Reporter) -> Self {
+        Session {
+            reporter,
+            seen: Mutex::default(),
+            removed: Mutex::default(),
+            modified: AtomicBool::new(false),
+            stats: Mutex::default(),
+            hook_failures: AtomicUsize::new(0),
+            previews: AtomicUsize::new(0),
+            eof_newlines: Mutex::default(),
+            boms: Mutex::default(),
+            longest_lines: Mutex::default(),
+            ages: Mutex::default(),
+            journal: OnceLock::new(),
+            eol_rules: EolRules::default(),
+            editorconfigs: EditorConfigs::default(),
+            clean_hashes: Mutex::default(),
+            below_threshold: AtomicUsize::new(0),
+            special_entries: AtomicUsize::new(0),
+            would_modify: Mutex::default(),
+            lines_trimmed: Mutex::default(),
+            patch: Mutex::default(),
+            group: Mutex::default(),
+            changed: OnceLock::new(),
+            staged: OnceLock::new(),
+            partially_staged: OnceLock::new(),
+            size_limit: OnceLock::new(),
+            rustfmt_missing: AtomicBool::new(false),
+            #[cfg(feature = "progress")]
+            progress: OnceLock::new(),
+        }
+    }
+
+    /// Counts the outcome of one file for the summary and the exit status;
+    /// `None` is an error.
+    fn record(&self, outcome: Option<Outcome>) {
+        let mut stats = self.stats.lock().unwrap();
+        stats.scanned += 1;
+        let counter = match outcome {
+            Some(Outcome::Modified) => &mut stats.modified,
+            Some(Outcome::Unchanged) => &mut stats.unchanged,
+            Some(Outcome::Skipped(SkipReason::Binary)) => &mut stats.binary,
+            Some(Outcome::Skipped(SkipReason::Symlink)) => &mut stats.symlinks,
+            Some(Outcome::Skipped(_)) => &mut stats.skipped,
+            None => &mut stats.failed,
+        };
+        *counter += 1;
+    }
+
+    /// Files processed so far, by outcome.
+    pub fn stats(&self) -> Stats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Records `path` as processed. Returns false if it was already seen.
+    fn first_visit(&self, path: &Path) -> Result<bool, RtrimError> {
+        let canonical = fs::canonicalize(path).context("failed to canonicalize path", path)?;
+        Ok(self.seen.lock().unwrap().insert(canonical))
+    }
+
+    /// Whether `path` is outside the `--since-commit` change set. Always false
+    /// when the option is unset.
+    fn unchanged_since(&self, path: &Path) -> Result<bool, RtrimError> {
+        let Some(changed) = self.changed.get() else {
+            return Ok(false);
+        };
+        let canonical = fs::canonicalize(path).context("failed to canonicalize path", path)?;
+        Ok(!changed.contains(&canonical))
+    }
+
+    /// Why `--staged` leaves `path` alone: it is not staged, or it also has
+    /// unstaged changes that re-staging the trimmed file would sweep into the
+    /// commit. Always `None` when the option is unset.
+    fn staged_skip(&self, path: &Path) -> Result<Option<SkipReason>, RtrimError> {
+        let Some(staged) = self.staged.get() else {
+            return Ok(None);
+        };
+        let canonical = fs::canonicalize(path).context("failed to canonicalize path", path)?;
+        if !staged.contains(&canonical) {
+            return Ok(Some(SkipReason::NotStaged));
+        }
+        let partially_staged = self
+            .partially_staged
+            .get()
+            .is_some_and(|partial| partial.contains(&canonical));
+        Ok(partially_staged.then_some(SkipReason::PartiallyStaged))
+    }
+
+    /// Whether traversal should end early because `--preview` is satisfied.
+    fn stopped(&self, config: &Config) -> bool {
+        config
+            .preview
+            .is_some_and(|limit| self.previews.load(Ordering::Relaxed) >= limit)
+    }
+
+    /// Queues `staged` for its rename, committing the group once it holds
+    /// `size` files.
+    fn stage(&self, staged: StagedWrite, size: usize, config: &Config) {
+        let mut group = self.group.lock().unwrap();
+        group.push(staged);
+        if group.len() >= size {
+            self.commit(&mut group, config);
+        }
+    }
+
+    /// Commits any files still staged by `--group-commit`. Returns the number
+    /// of renames that failed.
+    fn commit_group(&self, config: &Config) -> usize {
+        self.commit(&mut self.group.lock().unwrap(), config)
+    }
+
+    /// Renames every staged temp file over its target, then syncs each parent
+    /// directory once. Each renamed file then gets its journal entry, its
+    /// `Processed` line and its hash cache entry. Failures are reported per
+    /// file and fail the run; the temp file of a failed rename is removed when
+    /// it is dropped.
+    fn commit(&self, group: &mut Vec<StagedWrite>, config: &Config) -> usize {
+        let mut failed = 0;
+        let mut dirs = HashSet::new();
+        for staged in group.drain(..) {
+            let target = staged.target;
+            let renamed = staged
+                .temp
+                .persist(&target)
+                .context("failed to replace file", &target)
+                .and_then(|()| {
+                    dirs.insert(match target.parent() {
+                        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                        _ => PathBuf::from("."),
+                    });
+                    match self.journal.get() {
+                        Some(journal) => journal
+                            .done(&target)
+                            .context("failed to write journal", &target),
+                        None => Ok(()),
+                    }
+                });
+            if let Err(err) = renamed {
+                // The file was already counted as modified; the failure
+                // still has to fail the run.
+                failed += 1;
+                self.stats.lock().unwrap().failed += 1;
+                report_error(&target, &err, config, &self.reporter);
+                continue;
+            }
+            if !config.porcelain && !config.json_output && !config.quiet {
+                self.reporter
+                    .info(format_args!("  Processed: {:?}{}", target, staged.counts));
+            }
+            if let Some(key) = staged.clean_hash {
+                self.clean_hashes.lock().unwrap().insert(key);
+            }
+        }
+        for dir in dirs {
+            if let Err(e) = sync_dir(&dir) {
+                self.reporter
+                    .warn(format_args!("Could not sync directory {:?}: {}", dir, e));
+            }
+        }
+        failed
+    }
+
+    /// What the files processed so far amount to, for the exit status.
+    pub fn summary(&self, config: &Config) -> RunSummary {
+        let stats = self.stats();
+        RunSummary {
+            failed: stats.failed > 0,
+            modified: self.modified.load(Ordering::Relaxed),
+            writes_files: writes_files(config),
+            files: stats.scanned - stats.failed - stats.symlinks,
+            binary_files: stats.binary,
+        }
+    }
+
+    /// Prints the end-of-run notes and waits for all output to be written.
+    pub fn finish(self) {
+        self.reporter.finish();
+    }
+}
+
+/// Runs the configured mode, printing to `out` and `err`, and returns the
+/// exit status. A fatal error is printed last, after the per-file output.
+pub fn run(config: &Config, out: Box<dyn Write + Send>, err: Box<dyn Write + Send>) -> i32 {
+    let session = Session::new(Reporter::with_sinks(config.max_warnings, out, err));
+    let result = execute(config, &session);
+    let mut summary = session.summary(config);
+    summary.failed |= result.is_err();
+    if let Err(e) = result {
+        let message = error::describe(&e, config.verbose_errors);
+        if config.json_output {
+            session.reporter.info(format_args!(
+                "{{\"type\": \"error\", \"error\": {}}}",
+                json::encode_string(&message)
+            ));
+        } else {
+            session
+                .reporter
+                .error(format_args!("Execution Error: {}", message));
+        }
+    }
+    if config.json_output && config.mode != Mode::Help {
+        session.reporter.info(session.stats().json_record());
+    }
+    session.finish();
+    exit_code(&summary, &config.exit_codes)
+}
+
+/// Whether this run rewrites files. URLs are only ever reported on.
+fn writes_files(config: &Config) -> bool {
+    #[cfg(feature = "http")]
+    if config.mode == Mode::File && is_url(&config.path) {
+        return false;
+    }
+    config.writes_files()
+}
+
+/// What a run did, as far as its exit status is concerned.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RunSummary {
+    /// The run, or any single file, failed.
+    pub failed: bool,
+    /// A file was modified, or would be in report-only modes.
+    pub modified: bool,
+    pub writes_files: bool,
+    /// Files processed without an error, skipped ones included.
+    pub files: usize,
+    pub binary_files: usize,
+}
+
+/// Files processed in a run, by outcome.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Every file looked at, failed ones included.
+    pub scanned: usize,
+    /// Files modified, or that would be in report-only modes.
+    pub modified: usize,
+    pub unchanged: usize,
+    pub binary: usize,
+    pub symlinks: usize,
+    /// Files skipped for any other reason.
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl Stats {
+    /// The one-line end-of-run summary, e.g.
+    /// `Scanned 342, modified 12, unchanged 326, skipped 3 binary, 1 symlink`.
+    fn describe(&self) -> String {
+        let mut line = format!(
+            "Scanned {}, modified {}, unchanged {}, skipped {} binary, {} {}",
+            self.scanned,
+            self.modified,
+            self.unchanged,
+            self.binary,
+            self.symlinks,
+            if self.symlinks == 1 {
+                "symlink"
+            } else {
+                "symlinks"
+            }
+        );
+        if self.skipped > 0 {
+            line.push_str(&format!(", {} other", self.skipped));
+        }
+        if self.failed > 0 {
+            line.push_str(&format!(", failed {}", self.failed));
+        }
+        line
+    }
+
+    /// The final `--format json` record.
+    fn json_record(&self) -> String {
+        format!(
+            "{{\"type\": \"summary\", \"scanned\": {}, \"modified\": {}, \"unchanged\": {}, \
+             \"binary\": {}, \"symlinks\": {}, \"skipped\": {}, \"failed\": {}}}",
+            self.scanned,
+            self.modified,
+            self.unchanged,
+            self.binary,
+            self.symlinks,
+            self.skipped,
+            self.failed
+        )
+    }
+}
+
+/// Exit status for each row of the exit status table in the help text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ExitCodes {
+    clean: i32,
+    modified: i32,
+    would_modify: i32,
+    binary_only: i32,
+    error: i32,
+}
+
+impl Default for ExitCodes {
+    fn default() -> Self {
+        ExitCodes {
+            clean: 0,
+            modified: 0,
+            would_modify: 0,
+            binary_only: 0,
+            error: 1,
+        }
+    }
+}
+
+/// Picks the exit status for `summary`: the first matching row of error,
+/// modified (or would-modify), binary-only and clean.
+fn exit_code(summary: &RunSummary, codes: &ExitCodes) -> i32 {
+    if summary.failed {
+        codes.error
+    } else if summary.modified && summary.writes_files {
+        codes.modified
+    } else if summary.modified {
+        codes.would_modify
+    } else if summary.files > 0 && summary.binary_files == summary.files {
+        codes.binary_only
+    } else {
+        codes.clean
+    }
+}
+
+/// Directory whose repository `--since-commit` and `--staged` ask git about.
+fn git_dir(config: &Config) -> &Path {
+    match config.mode {
+        Mode::Folder => config.path.as_path(),
+        Mode::File => match config.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        },
+        _ => Path::new("."),
+    }
+}
+
+/// Runs the configured mode and prints the end-of-run reports.
+fn execute(config: &Config, session: &Session) -> io::Result<()> {
+    if let Some(path) = &config.hash_cache {
+        *session.clean_hashes.lock().unwrap() = cache::load(path)?;
+    }
+    if let Some(rev) = &config.since_commit {
+        let changed = changes::changed_since(git_dir(config), rev)?
+            .into_iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .collect();
+        let _ = session.changed.set(changed);
+    }
+    if config.staged && config.mode != Mode::Help {
+        let staged = changes::staged(git_dir(config))?
+            .into_iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .collect();
+        let _ = session.staged.set(staged);
+        let partially_staged = changes::unstaged(git_dir(config))?
+            .into_iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .collect();
+        let _ = session.partially_staged.set(partially_staged);
+    }
+    if let Some(path) = &config.journal {
+        if config.mode != Mode::Help && config.writes_files() {
+            let _ = session.journal.set(Journal::create(path)?);
+        }
+    }
+    if config.non_atomic && config.mode != Mode::Help && config.writes_files() {
+        session.reporter.warn(format_args!(
+            "--atomic off rewrites files in place without syncing; a crash can corrupt them"
+        ));
+    }
+    if config.strip_both && config.mode != Mode::Help && config.writes_files() {
+        session.reporter.warn(format_args!(
+            "--strip-both removes leading whitespace too; indentation will be lost"
+        ));
+    }
+    let result = match config.mode {
+        Mode::Help => Ok(()),
+        #[cfg(feature = "http")]
+        Mode::File if is_url(&config.path) => run_url(&config.path, config, session),
+        Mode::File => match &config.connect {
+            #[cfg(unix)]
+            Some(socket) => run_client(socket, &config.path, config, session),
+            #[cfg(not(unix))]
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "--connect requires Unix domain sockets",
+            )),
+            None => run_file(&config.path, config, session),
+        },
+        #[cfg(unix)]
+        Mode::Daemon => run_daemon(&config.path, config, session),
+        #[cfg(not(unix))]
+        Mode::Daemon => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--daemon requires Unix domain sockets",
+        )),
+        Mode::Folder => process_folder(&config.path, config, session),
+        Mode::StdinPaths0 => process_paths0(io::stdin().lock(), config, session),
+        Mode::Paths => process_paths(&config.paths, config, session),
+        Mode::Rollback => run_rollback(&config.path, config, session),
+        Mode::Stdin | Mode::StdinJson => {
+            let mut output = Vec::new();
+            let result = if config.mode == Mode::Stdin {
+                filter(io::stdin().lock(), &mut output, config, session)
+            } else {
+                filter_json(io::stdin().lock(), &mut output, config, session)
+            };
+            session.reporter.raw(output);
+            result
+        }
+    };
+    let failed_renames = session.commit_group(config);
+    let result = match result {
+        Ok(()) if failed_renames > 0 && config.mode == Mode::File => Err(io::Error::other(
+            format!("{} grouped rename(s) failed", failed_renames),
+        )),
+        _ => result,
+    };
+    let result = match &config.output_patch {
+        Some(patch_path) if result.is_ok() => {
+            fs::write(patch_path, &*session.patch.lock().unwrap())
+        }
+        _ => result,
+    };
+    let result = match &config.dry_run_report {
+        Some(report_path) if result.is_ok() => {
+            let mut files = session.would_modify.lock().unwrap();
+            files.sort_by(|a, b| a.0.cmp(&b.0));
+            let report = match ReportFormat::for_report(report_path, config.dry_run_report_format) {
+                ReportFormat::Text => dry_run_text_report(&files, config),
+                ReportFormat::Json => dry_run_json_report(&files),
+            };
+            fs::write(report_path, report)
+        }
+        _ => result,
+    };
+    if config.report_trailing_types {
+        let removed = *session.removed.lock().unwrap();
+        session.reporter.info(format_args!(
+            "Trailing whitespace: {}",
+            removed.describe(config.trim_report.unwrap_or_default())
+        ));
+    }
+    if config.eof_newline_report {
+        let mut guard = session.eof_newlines.lock().unwrap();
+        let eof = &mut *guard;
+        for (heading, paths) in [
+            ("Files ending with a newline", &mut eof.with_newline),
+            ("Files without a final newline", &mut eof.without_newline),
+        ] {
+            paths.sort();
+            session
+                .reporter
+                .info(format_args!("{} ({}):", heading, paths.len()));
+            for path in paths.iter() {
+                session.reporter.info(format_args!("  {}", path.display()));
+            }
+        }
+    }
+    if let Some(count) = config.longest_lines_report {
+        let mut longest = session.longest_lines.lock().unwrap();
+        longest.sort_by(|a, b| b.length.cmp(&a.length).then_with(|| a.path.cmp(&b.path)));
+        session.reporter.info(format_args!(
+            "Files with the longest lines ({} of {}):",
+            count.min(longest.len()),
+            longest.len()
+        ));
+        for entry in longest.iter().take(count) {
+            session.reporter.info(format_args!(
+                "  {:>8} chars  {}:{}",
+                entry.length,
+                entry.path.display(),
+                entry.line
+            ));
+        }
+    }
+    if config.age_report {
+        let ages = *session.ages.lock().unwrap();
+        session.reporter.info(format_args!(
+            "{:<8}{:>8}{:>8}{:>8}",
+            "Age", "Files", "Dirty", "Lines"
+        ));
+        for bucket in AgeBucket::ALL {
+            let index = bucket as usize;
+            session.reporter.info(format_args!(
+                "{:<8}{:>8}{:>8}{:>8}",
+                bucket.label(),
+                ages.files[index],
+                ages.dirty_files[index],
+                ages.dirty_lines[index]
+            ));
+        }
+    }
+    if config.bom_report {
+        let mut guard = session.boms.lock().unwrap();
+        let boms = &mut *guard;
+        for (heading, paths) in [
+            ("Files with a UTF-8 BOM", &mut boms.utf8),
+            ("Files with a UTF-16 LE BOM", &mut boms.utf16_le),
+            ("Files with a UTF-16 BE BOM", &mut boms.utf16_be),
+        ] {
+            paths.sort();
+            session
+                .reporter
+                .info(format_args!("{} ({}):", heading, paths.len()));
+            for path in paths.iter() {
+                session.reporter.info(format_args!("  {}", path.display()));
+            }
+        }
+        session
+            .reporter
+            .info(format_args!("Files without a BOM: {}", boms.without_bom));
+    }
+    if let Some(min) = config.min_dirty_lines {
+        let below = session.below_threshold.load(Ordering::Relaxed);
+        if below > 0 {
+            session.reporter.info(format_args!(
+                "{} file(s) with fewer than {} dirty lines left untouched (--min-dirty-lines)",
+                below, min
+            ));
+        }
+    }
+    // Multi-file runs end with a summary, unless stdout is meant for scripts.
+    let multi_file = matches!(config.mode, Mode::Folder | Mode::Paths | Mode::StdinPaths0);
+    let scripted = config.porcelain || config.json_output || config.check || config.list_ignored;
+    if multi_file && result.is_ok() && !scripted && !config.quiet {
+        session.reporter.info(session.stats().describe());
+    }
+    let special = session.special_entries.load(Ordering::Relaxed);
+    if special > 0 && !config.json_output {
+        session.reporter.info(format_args!(
+            "{} special file(s) skipped: not a regular file, directory or symlink (see --verbose)",
+            special
+        ));
+    }
+    let hook_failures = session.hook_failures.load(Ordering::Relaxed);
+    if hook_failures > 0 {
+        session.reporter.warn(format_args!(
+            "{} --on-modify-exec command(s) failed",
+            hook_failures
+        ));
+    }
+    if let Some(path) = &config.hash_cache {
+        if let Err(e) = cache::save(path, &session.clean_hashes.lock().unwrap()) {
+            session
+                .reporter
+                .warn(format_args!("Could not save hash cache {:?}: {}", path, e));
+        }
+    }
+    result
+}
+
+fn run_file(path: &Path, config: &Config, session: &Session) -> io::Result<()> {
+    let reporter = &session.reporter;
+    // Check if it's a symlink before processing
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() && !config.follow_symlinks {
+        session.record(Some(Outcome::Skipped(SkipReason::Symlink)));
+        report_ignored(path, SkipReason::Symlink.describe(), config, reporter);
+        if config.verbose {
+            reporter.info(format_args!("  Skipped (symlink): {:?}", path));
+        } else {
+            reporter.warn(format_args!("Ignoring symlink {:?}", path));
+        }
+        report_status(
+            path,
+            Some(Outcome::Skipped(SkipReason::Symlink)),
+            config,
+            session,
+        );
+        return Ok(());
+    }
+    if config.enforce_ignores_on_file {
+        if let Some(dir) = ignored_ancestor(path) {
+            reporter.warn(format_args!(
+                "Refusing {:?}: inside ignored directory {:?}",
+                path, dir
+            ));
+            session.record(Some(Outcome::Skipped(SkipReason::IgnoredDir)));
+            report_ignored(path, SkipReason::IgnoredDir.describe(), config, reporter);
+            report_status(
+                path,
+                Some(Outcome::Skipped(SkipReason::IgnoredDir)),
+                config,
+                session,
+            );
+            return Ok(());
+        }
+    }
+    match process_file(path, config, session) {
+        Ok(outcome) => {
+            session.record(Some(outcome));
+            report_status(path, Some(outcome), config, session);
+            if let Outcome::Skipped(reason) = outcome {
+                report_ignored(path, reason.describe(), config, reporter);
+            }
+            Ok(())
+        }
+        Err(e) => {
+            report_status(path, None, config, session);
+            Err(e.into())
+        }
+    }
+}
+
+/// Fetches a remote file and reports on it. `parse_config` only lets
+/// report-only modes get here, since a URL cannot be rewritten.
+#[cfg(feature = "http")]
+fn run_url(url: &Path, config: &Config, session: &Session) -> io::Result<()> {
+    let reporter = &session.reporter;
+    if config.verbose {
+        reporter.info(format_args!("  Fetching: {}", url.display()));
+    }
+    let body = http::get(&url.to_string_lossy())?;
+
+    let outcome = match std::str::from_utf8(&body) {
+        Err(_) => {
+            if config.verbose {
+                reporter.info(format_args!("  Skipped (binary): {}", url.display()));
+            }
+            Outcome::Skipped(SkipReason::Binary)
+        }
+        Ok(content) => match trim_for_config(url, content, config) {
+            Err(reason) => Outcome::Skipped(reason),
+            Ok(result) => {
+                *session.removed.lock().unwrap() += result.removed;
+                if result.modified {
+                    session.modified.store(true, Ordering::Relaxed);
+                    if config.verbose {
+                        reporter.info(format_args!("  Would modify: {}", url.display()));
+                    }
+                    Outcome::Modified
+                } else {
+                    Outcome::Unchanged
+                }
+            }
+        },
+    };
+    session.record(Some(outcome));
+    report_status(url, Some(outcome), config, session);
+    Ok(())
+}
+
+/// Processes each NUL-separated path read from `input` as if given via `--file`.
+/// A failure on one path is reported as a warning and does not stop the rest.
+fn process_paths0(input: impl Read, config: &Config, session: &Session) -> io::Result<()> {
+    for entry in BufReader::new(input).split(b'\0') {
+        if session.stopped(config) {
+            break;
+        }
+        let entry = entry?;
+        if entry.is_empty() {
+            continue;
+        }
+        let path = path_from_bytes(&entry);
+        if let Err(e) = run_file(&path, config, session) {
+            session.record(None);
+            report_error(&path, &e, config, &session.reporter);
+        }
+    }
+    Ok(())
+}
+
+/// Processes each path in turn: folders recursively, anything else as a
+/// single file. A path that fails is reported and the run moves on.
+fn process_paths(paths: &[PathBuf], config: &Config, session: &Session) -> io::Result<()> {
+    for path in paths {
+        if session.stopped(config) {
+            break;
+        }
+        let metadata = if config.follow_symlinks {
+            fs::metadata(path)
+        } else {
+            fs::symlink_metadata(path)
+        };
+        let is_dir = metadata.is_ok_and(|metadata| metadata.is_dir());
+        let result = if is_dir {
+            process_folder(path, config, session)
+        } else {
+            run_file(path, config, session)
+        };
+        if let Err(e) = result {
+            session.record(None);
+            report_error(path, &e, config, &session.reporter);
+        }
+    }
+    Ok(())
+}
+
+/// A path read as raw bytes. Unix paths are arbitrary bytes; elsewhere they
+/// must be UTF-8, and invalid sequences are replaced.
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Trims all of `input` and writes the result to `output`, without touching
+/// the filesystem. Options that depend on a file extension do not apply.
+/// Report-only modes write nothing; the exit status tells the result.
+fn filter(
+    mut input: impl Read,
+    mut output: impl Write,
+    config: &Config,
+    session: &Session,
+) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    input.read_to_end(&mut buffer)?;
+    // An editor buffer that is not text goes back exactly as it came.
+    if config.stdin_passthrough && (looks_binary(&buffer) || std::str::from_utf8(&buffer).is_err())
+    {
+        session.record(Some(Outcome::Unchanged));
+        if config.writes_files() {
+            output.write_all(&buffer)?;
+            output.flush()?;
+        }
+        return Ok(());
+    }
+    let (_, trimmed, _) = trim_input(buffer.as_slice(), config, session)?;
+    if config.writes_files() {
+        output.write_all(trimmed.as_bytes())?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Like `filter`, but answers with one JSON object holding whether the text
+/// changed, the trimmed text and the 1-based numbers of the changed lines:
+/// `{"changed": true, "content": "...", "lines_trimmed": [2, 5]}`.
+fn filter_json(
+    input: impl Read,
+    mut output: impl Write,
+    config: &Config,
+    session: &Session,
+) -> io::Result<()> {
+    let (content, trimmed, changed) = trim_input(input, config, session)?;
+    let lines_trimmed: Vec<String> = content
+        .lines()
+        .zip(trimmed.lines())
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(index, _)| (index + 1).to_string())
+        .collect();
+    writeln!(
+        output,
+        "{{\"changed\": {}, \"content\": {}, \"lines_trimmed\": [{}]}}",
+        changed,
+        json::encode_string(&trimmed),
+        lines_trimmed.join(", ")
+    )?;
+    output.flush()
+}
+
+/// Reads all of `input` and trims it, returning the original text, the
+/// trimmed text and whether trimming modified it. Input that is not UTF-8 is
+/// an error.
+fn trim_input(
+    mut input: impl Read,
+    config: &Config,
+    session: &Session,
+) -> io::Result<(String, String, bool)> {
+    let mut buffer = Vec::new();
+    input.read_to_end(&mut buffer)?;
+    let content = String::from_utf8(buffer)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "stdin is not valid UTF-8"))?;
+
+    let (trimmed, modified) = match trim_for_config(Path::new("-"), &content, config) {
+        Ok(result) => {
+            *session.removed.lock().unwrap() += result.removed;
+            if result.modified {
+                session.modified.store(true, Ordering::Relaxed);
+                session.record(Some(Outcome::Modified));
+                (result.content, true)
+            } else {
+                // Trimming may normalize line endings without removing
+                // anything; unchanged input goes back byte for byte.
+                session.record(Some(Outcome::Unchanged));
+                (content.clone(), false)
+            }
+        }
+        Err(reason) => {
+            session.record(Some(Outcome::Skipped(reason)));
+            (content.clone(), false)
+        }
+    };
+    Ok((content, trimmed, modified))
+}
+
+/// Listens on `socket` and processes the file paths that clients send, until
+/// the process is stopped.
+#[cfg(unix)]
+fn run_daemon(socket: &Path, config: &Config, session: &Session) -> io::Result<()> {
+    let listener = bind_socket(socket)?;
+    let _socket_file = SocketFile(socket);
+    session
+        .reporter
+        .info(format_args!("Listening on {}", socket.display()));
+    serve(&listener, config, session)
+}
+
+/// Binds `socket`, replacing a stale socket file left behind by a daemon that
+/// is no longer running. A live daemon on the same socket is an error, and so
+/// is any path that is not a socket: it is never removed.
+#[cfg(unix)]
+fn bind_socket(socket: &Path) -> io::Result<UnixListener> {
+    match UnixListener::bind(socket) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse && UnixStream::connect(socket).is_err() => {
+            if !fs::symlink_metadata(socket)?.file_type().is_socket() {
+                return Err(e);
+            }
+            fs::remove_file(socket)?;
+            UnixListener::bind(socket)
+        }
+        result => result,
+    }
+}
+
+/// The socket file of a running daemon, removed when the daemon shuts down.
+#[cfg(unix)]
+struct SocketFile<'a>(&'a Path);
+
+#[cfg(unix)]
+impl Drop for SocketFile<'_> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.0);
+    }
+}
+
+/// Handles every connection on its own thread. Each line a client sends is
+/// an absolute file path; the reply is the file's porcelain status line, or
+/// `E<TAB>PATH<TAB>ERROR` when it failed.
+#[cfg(unix)]
+fn serve(listener: &UnixListener, config: &Config, session: &Session) -> io::Result<()> {
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            scope.spawn(move || {
+                if let Err(e) = serve_client(stream, config, session) {
+                    session
+                        .reporter
+                        .warn(format_args!("Daemon client failed: {}", e));
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+#[cfg(unix)]
+fn serve_client(stream: UnixStream, config: &Config, session: &Session) -> io::Result<()> {
+    let mut replies = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let path = Path::new(&line);
+        let reply = match process_file(path, config, session) {
+            Ok(outcome) => format!("{}\t{}", outcome.porcelain_code(), path.display()),
+            Err(e) => format!(
+                "{}\t{}\t{}",
+                PORCELAIN_ERROR,
+                path.display(),
+                error::describe(&e, false)
+            ),
+        };
+        writeln!(replies, "{}", reply)?;
+    }
+    Ok(())
+}
+
+/// Sends `path` to the daemon on `socket` and returns its reply line.
+#[cfg(unix)]
+fn request_daemon(socket: &Path, path: &Path) -> io::Result<String> {
+    // The daemon resolves relative paths against its own working directory.
+    let path = std::path::absolute(path)?;
+    let path = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8"))?;
+    let mut stream = UnixStream::connect(socket)?;
+    writeln!(stream, "{}", path)?;
+    stream.shutdown(Shutdown::Write)?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply.trim_end().to_string())
+}
+
+/// `--connect`: has the daemon process `path` and reports its answer like a
+/// local `--file` run would.
+#[cfg(unix)]
+fn run_client(socket: &Path, path: &Path, config: &Config, session: &Session) -> io::Result<()> {
+    let reply = request_daemon(socket, path)?;
+    let mut fields = reply.splitn(3, '\t');
+    let code = fields.next().unwrap_or_default();
+    if config.porcelain {
+        session.reporter.info(format_args!("{}", reply));
+    }
+    match code {
+        PORCELAIN_ERROR => Err(io::Error::other(
+            fields
+                .nth(1)
+                .unwrap_or("daemon reported an error")
+                .to_string(),
+        )),
+        "" => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "daemon closed the connection without a reply",
+        )),
+        code => {
+            if code == Outcome::Modified.porcelain_code() {
+                session.modified.store(true, Ordering::Relaxed);
+                if !config.porcelain && !config.quiet {
+                    session
+                        .reporter
+                        .info(format_args!("  Processed: {:?}", path));
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Porcelain status for a file that failed with an error.
+const PORCELAIN_ERROR: &str = "E";
+
+/// Prints the status of a file: a `STATUS\tPATH` line with `--porcelain`, or
+/// a JSON object with `--format json`. `None` stands for a failure, whose
+/// JSON record `report_error` prints along with the message.
+fn report_status(path: &Path, outcome: Option<Outcome>, config: &Config, session: &Session) {
+    if config.porcelain {
+        let code = outcome.map_or(PORCELAIN_ERROR, Outcome::porcelain_code);
+        session
+            .reporter
+            .info(format_args!("{}\t{}", code, path.display()));
+    } else if let (true, Some(outcome)) = (config.json_output, outcome) {
+        let status = match outcome {
+            Outcome::Modified => {
+                let lines = session.lines_trimmed.lock().unwrap().remove(path);
+                format!("\"modified\", \"lines\": {}", lines.unwrap_or(0))
+            }
+            Outcome::Unchanged => "\"unchanged\", \"lines\": 0".to_string(),
+            Outcome::Skipped(reason) => format!(
+                "\"skipped\", \"reason\": {}",
+                json::encode_string(reason.describe())
+            ),
+        };
+        session.reporter.info(format_args!(
+            "{{\"type\": \"file\", \"path\": {}, \"status\": {}}}",
+            json::encode_string(&path.to_string_lossy()),
+            status
+        ));
+    }
+}
+
+/// Prints a `REASON\tPATH` line for a path left out of the run when
+/// `--list-ignored` is set.
+fn report_ignored(path: &Path, reason: &str, config: &Config, reporter: &Reporter) {
+    if config.list_ignored {
+        reporter.info(format_args!("{}\t{}", reason, path.display()));
+    }
+}
+
+/// Warns about a file that could not be processed, unless `--quiet-errors`.
+/// With `--format json` the error is a record on stdout instead.
+fn report_error(
+    path: &Path,
+    err: &(dyn std::error::Error + 'static),
+    config: &Config,
+    reporter: &Reporter,
+) {
+    if config.json_output {
+        reporter.info(format_args!(
+            "{{\"type\": \"file\", \"path\": {}, \"status\": \"error\", \"error\": {}}}",
+            json::encode_string(&path.to_string_lossy()),
+            json::encode_string(&error::describe(err, config.verbose_errors))
+        ));
+        return;
+    }
+    if config.quiet_errors {
+        return;
+    }
+    reporter.warn(format_args!(
+        "Error processing {:?}: {}",
+        path,
+        error::describe(err, config.verbose_errors)
+    ));
+}
+
+/// Checks if a directory should be ignored.
+fn should_ignore_dir(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| IGNORED_DIRS.contains(&name) || name.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// The first directory of `path`, as written, that folder processing would
+/// not descend into. Only the directories leading to the file are checked.
+fn ignored_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .skip(1)
+        .filter(|dir| matches!(dir.components().next_back(), Some(Component::Normal(_))))
+        .filter(|dir| should_ignore_dir(dir))
+        .last()
+}
+
+/// Recursive filesystem traversal (without following symlinks). With more
+/// than one worker thread (the default is one per CPU), the walk feeds file
+/// paths to a pool of workers.
+pub fn process_folder(dir: &Path, config: &Config, session: &Session) -> io::Result<()> {
+    let workers = config.threads.unwrap_or(Threads::Auto).resolve();
+    if workers <= 1 {
+        return feed_folder(dir, config, session, &mut |path| {
+            process_entry(&path, config, session)
+        });
+    }
+
+    let (sender, receiver) = mpsc::channel::<PathBuf>();
+    let receiver = Mutex::new(receiver);
+    let receiver = &receiver;
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(move || loop {
+                // The lock is released before the file is processed.
+                let next = receiver.lock().unwrap().recv();
+                match next {
+                    Ok(path) => process_entry(&path, config, session),
+                    Err(_) => break,
+                }
+            });
+        }
+        let result = feed_folder(dir, config, session, &mut |path| {
+            let _ = sender.send(path);
+        });
+        // Closing the queue lets idle workers exit once it drains.
+        drop(sender);
+        result
+    })
+}
+
+/// Default percentile for `--skip-outliers`.
+const DEFAULT_OUTLIER_PERCENTILE: f64 = 99.0;
+
+/// Hands every file of the tree to `on_file`. With `--skip-outliers` or a
+/// progress bar the whole tree is scanned first, to set the size limit and
+/// the file total before any file is processed.
+fn feed_folder(
+    dir: &Path,
+    config: &Config,
+    session: &Session,
+    on_file: &mut dyn FnMut(PathBuf),
+) -> io::Result<()> {
+    #[cfg(feature = "progress")]
+    let progress_bar = config.progress_bar && io::stderr().is_terminal();
+    #[cfg(not(feature = "progress"))]
+    let progress_bar = false;
+    if !config.skip_outliers && !progress_bar {
+        return walk_folder(dir, config, session, None, 1, &[], &[], on_file);
+    }
+
+    let mut files = Vec::new();
+    walk_folder(dir, config, session, None, 1, &[], &[], &mut |path| {
+        files.push(path)
+    })?;
+    #[cfg(feature = "progress")]
+    if progress_bar {
+        let _ = session.progress.set(Progress::new(files.len()));
+    }
+    if config.skip_outliers {
+        let sizes: Vec<u64> = files
+            .iter()
+            .map(|path| fs::symlink_metadata(path).map_or(0, |m| m.len()))
+            .collect();
+        let percentile = config
+            .outlier_percentile
+            .unwrap_or(DEFAULT_OUTLIER_PERCENTILE);
+        if let Some(limit) = percentile_size(sizes, percentile) {
+            let _ = session.size_limit.set(limit);
+        }
+    }
+
+    for path in files {
+        if session.stopped(config) {
+            break;
+        }
+        on_file(path);
+    }
+    Ok(())
+}
+
+/// The size at `percentile` (nearest-rank method), or `None` for no sizes.
+fn percentile_size(mut sizes: Vec<u64>, percentile: f64) -> Option<u64> {
+    sizes.sort_unstable();
+    let rank = (percentile / 100.0 * sizes.len() as f64).ceil() as usize;
+    sizes.get(rank.clamp(1, sizes.len().max(1)) - 1).copied()
+}
+
+/// Processes one file found during traversal, reporting instead of
+/// propagating its errors so the rest of the tree is still processed.
+fn process_entry(path: &Path, config: &Config, session: &Session) {
+    let reporter = &session.reporter;
+    match process_file(path, config, session) {
+        Ok(outcome) => {
+            session.record(Some(outcome));
+            report_status(path, Some(outcome), config, session);
+            if let Outcome::Skipped(reason) = outcome {
+                report_ignored(path, reason.describe(), config, reporter);
+            }
+        }
+        Err(e) => {
+            session.record(None);
+            report_status(path, None, config, session);
+            report_error(path, &e, config, reporter);
+        }
+    }
+    #[cfg(feature = "progress")]
+    if let Some(bar) = session.progress.get().and_then(Progress::tick) {
+        reporter.progress(bar);
+    }
+}
+
+/// Whether a directory lives on a different filesystem than the traversal root.
+fn on_other_filesystem(root_dev: u64, metadata: &Metadata) -> bool {
+    device_id(metadata) != root_dev
+}
+
+/// Id of the device holding an entry.
+#[cfg(unix)]
+fn device_id(metadata: &Metadata) -> u64 {
+    metadata.dev()
+}
+
+/// std has no stable device id outside Unix, so `--one-filesystem` treats
+/// every entry as being on the root's filesystem.
+#[cfg(not(unix))]
+fn device_id(_metadata: &Metadata) -> u64 {
+    0
+}
+
+/// Submodule paths listed in `dir/.gitmodules`, joined onto `dir`. A missing
+/// or unreadable file lists none.
+fn gitmodules_paths(dir: &Path) -> Vec<PathBuf> {
+    let Ok(content) = fs::read_to_string(dir.join(".gitmodules")) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            (key.trim() == "path").then(|| dir.join(value.trim()))
+        })
+        .collect()
+}
+
+/// Whether `dir` is the working tree of a submodule: checked-out submodules
+/// have a `.git` file pointing into the superproject instead of a directory.
+/// `listed` holds the paths from the enclosing `.gitmodules` files.
+fn is_submodule(dir: &Path, listed: &[PathBuf]) -> bool {
+    listed.iter().any(|path| path == dir)
+        || fs::symlink_metadata(dir.join(".git")).is_ok_and(|m| m.is_file())
+}
+
+/// What a folder walk keeps from its top-level directory.
+#[derive(Debug)]
+struct WalkRoot {
+    /// Device id, for `--one-filesystem`.
+    dev: u64,
+    /// Canonical path, which `--follow-symlinks` targets must stay under.
+    path: PathBuf,
+    /// Canonical directories already walked, so symlink loops end.
+    visited: RefCell<HashSet<PathBuf>>,
+}
+
+/// Resolves a symlink for `--follow-symlinks` to its canonical target and
+/// the target's metadata. Targets outside `root` are refused unless
+/// `--allow-symlinks-outside-root` is set; the error is the skip reason.
+fn resolve_symlink(
+    link: &Path,
+    root: &Path,
+    config: &Config,
+) -> Result<(PathBuf, Metadata), &'static str> {
+    let target = fs::canonicalize(link).map_err(|_| "broken symlink")?;
+    if !config.allow_symlinks_outside_root && !target.starts_with(root) {
+        return Err("symlink outside root");
+    }
+    let metadata = fs::symlink_metadata(&target).map_err(|_| "broken symlink")?;
+    Ok((target, metadata))
+}
+
+/// Recursive step of `process_folder`, handing every regular file to
+/// `on_file`. `root` is captured from the top-level directory on the first
+/// call, which is at `depth` 1; `submodules` holds the `--skip-submodules` paths listed in the
+/// `.gitmodules` files above `dir`, and `gitignores` the
+/// `--respect-gitignore` files that apply to it, outermost first.
+#[allow(clippy::too_many_arguments)]
+fn walk_folder(
+    dir: &Path,
+    config: &Config,
+    session: &Session,
+    root: Option<&WalkRoot>,
+    depth: usize,
+    submodules: &[PathBuf],
+    gitignores: &[Rc<Gitignore>],
+    on_file: &mut dyn FnMut(PathBuf),
+) -> io::Result<()> {
+    let reporter = &session.reporter;
+    let verbose = config.verbose;
+
+    // Use symlink_metadata to avoid following symlinks
+    let metadata = fs::symlink_metadata(dir)?;
+
+    // Ignore symlinks, unless the walk starts at one it may follow
+    if metadata.file_type().is_symlink() {
+        if config.follow_symlinks && root.is_none() {
+            let target = fs::canonicalize(dir)?;
+            return walk_folder(
+                &target, config, session, None, depth, submodules, gitignores, on_file,
+            );
+        }
+        if verbose {
+            reporter.info(format_args!("  Skipped (symlink): {:?}", dir));
+        }
+        return Ok(());
+    }
+
+    if !metadata.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Path is not a directory",
+        ));
+    }
+
+    let at_root = root.is_none();
+    let own_root;
+    let root = match root {
+        Some(root) => root,
+        None => {
+            own_root = WalkRoot {
+                dev: device_id(&metadata),
+                path: fs::canonicalize(dir)?,
+                visited: RefCell::default(),
+            };
+            &own_root
+        }
+    };
+    if config.follow_symlinks && !root.visited.borrow_mut().insert(fs::canonicalize(dir)?) {
+        report_ignored(dir, "already visited", config, reporter);
+        if verbose {
+            reporter.info(format_args!("  Skipped (already visited): {:?}", dir));
+        }
+        return Ok(());
+    }
+
+    if verbose {
+        reporter.info(format_args!("Scanning: {:?}", dir));
+    }
+
+    let mut submodules: Vec<PathBuf> = submodules
+        .iter()
+        .filter(|path| path.starts_with(dir))
+        .cloned()
+        .collect();
+    if config.skip_submodules {
+        submodules.extend(gitmodules_paths(dir));
+    }
+    let mut gitignores = gitignores.to_vec();
+    if config.respect_gitignore {
+        // A walk started inside a repository also honors the .gitignore
+        // files above it, up to the repository root.
+        if at_root {
+            gitignores.extend(Gitignore::above(dir).into_iter().map(Rc::new));
+        }
+        gitignores.extend(Gitignore::load(dir).map(Rc::new));
+    }
+
+    for entry in fs::read_dir(dir)? {
+        if session.stopped(config) {
+            break;
+        }
+        let entry = entry?;
+        let path = entry.path();
+
+        // Temp files of this or a concurrent run, which may be renamed away
+        // at any moment (staged `--group-commit` files stay around longer).
+        if is_temp_file_name(&entry.file_name()) {
+            report_ignored(&path, "temporary file", config, reporter);
+            continue;
+        }
+        if config.writes_backups() && is_backup_file_name(&entry.file_name()) {
+            report_ignored(&path, "backup file", config, reporter);
+            continue;
+        }
+
+        // Use symlink_metadata to detect symlinks without following them
+        let entry_metadata = match fs::symlink_metadata(&path) {
+            Ok(m) => m,
+            Err(e) => {
+                reporter.warn(format_args!(
+                    "Could not read metadata for {:?}: {}",
+                    path, e
+                ));
+                continue;
+            }
+        };
+
+        // A followed symlink stands in for its target from here on
+        let mut refused = None;
+        let (path, entry_metadata) =
+            if config.follow_symlinks && entry_metadata.file_type().is_symlink() {
+                match resolve_symlink(&path, &root.path, config) {
+                    Ok(resolved) => resolved,
+                    Err(reason) => {
+                        refused = Some(reason);
+                        (path, entry_metadata)
+                    }
+                }
+            } else {
+                (path, entry_metadata)
+            };
+
+        // Ignore other symlinks completely
+        if entry_metadata.file_type().is_symlink() {
+            let reason = refused.unwrap_or(SkipReason::Symlink.describe());
+            session.record(Some(Outcome::Skipped(SkipReason::Symlink)));
+            report_ignored(&path, reason, config, reporter);
+            if verbose {
+                reporter.info(format_args!("  Skipped ({}): {:?}", reason, path));
+            }
+            report_status(
+                &path,
+                Some(Outcome::Skipped(SkipReason::Symlink)),
+                config,
+                session,
+            );
+            continue;
+        }
+
+        if config.respect_gitignore
+            && gitignore::is_ignored(&gitignores, &path, entry_metadata.is_dir())
+        {
+            report_ignored(&path, SkipReason::Gitignored.describe(), config, reporter);
+            if verbose {
+                reporter.info(format_args!("  Skipped (gitignore): {:?}", path));
+            }
+            // Ignored directories are left out whole, like the built-in ones.
+            if !entry_metadata.is_dir() {
+                let outcome = Outcome::Skipped(SkipReason::Gitignored);
+                session.record(Some(outcome));
+                report_status(&path, Some(outcome), config, session);
+            }
+            continue;
+        }
+
+        if entry_metadata.is_dir() {
+            // Ignore special directories
+            if should_ignore_dir(&path) {
+                report_ignored(&path, SkipReason::IgnoredDir.describe(), config, reporter);
+                if verbose {
+                    reporter.info(format_args!("  Skipped (ignored dir): {:?}", path));
+                }
+                continue;
+            }
+            if config.one_filesystem && on_other_filesystem(root.dev, &entry_metadata) {
+                report_ignored(&path, "other filesystem", config, reporter);
+                if verbose {
+                    reporter.info(format_args!("  Skipped (other filesystem): {:?}", path));
+                }
+                continue;
+            }
+            if config.skip_submodules && is_submodule(&path, &submodules) {
+                report_ignored(&path, "submodule", config, reporter);
+                if verbose {
+                    reporter.info(format_args!("  Skipped (submodule): {:?}", path));
+                }
+                continue;
+            }
+            if config.max_depth.is_some_and(|max| depth >= max) {
+                report_ignored(&path, "max depth", config, reporter);
+                if verbose {
+                    reporter.info(format_args!("  Skipped (max depth): {:?}", path));
+                }
+                continue;
+            }
+            walk_folder(
+                &path,
+                config,
+                session,
+                Some(root),
+                depth + 1,
+                &submodules,
+                &gitignores,
+                on_file,
+            )?;
+        } else if entry_metadata.is_file() && !config.extension_allowed(&path) {
+            let outcome = Outcome::Skipped(SkipReason::ExcludedExtension);
+            session.record(Some(outcome));
+            report_ignored(
+                &path,
+                SkipReason::ExcludedExtension.describe(),
+                config,
+                reporter,
+            );
+            if verbose {
+                reporter.info(format_args!("  Skipped (excluded extension): {:?}", path));
+            }
+            report_status(&path, Some(outcome), config, session);
+        } else if entry_metadata.is_file() {
+            on_file(path);
+        } else {
+            session.special_entries.fetch_add(1, Ordering::Relaxed);
+            report_ignored(
+                &path,
+                special_kind(&entry_metadata.file_type()),
+                config,
+                reporter,
+            );
+            if verbose {
+                reporter.info(format_args!(
+                    "  Skipped ({}): {:?}",
+                    special_kind(&entry_metadata.file_type()),
+                    path
+                ));
+            }
+            report_status(
+                &path,
+                Some(Outcome::Skipped(SkipReason::NotRegular)),
+                config,
+                session,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Names the type of an entry that is neither a file, a directory nor a
+/// symlink. Types std cannot name, such as overlay whiteouts on some
+/// systems, are "unknown type".
+#[cfg(unix)]
+fn special_kind(file_type: &FileType) -> &'static str {
+    if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block device"
+    } else if file_type.is_char_device() {
+        "character device"
+    } else {
+        "unknown type"
+    }
+}
+
+#[cfg(not(unix))]
+fn special_kind(_file_type: &FileType) -> &'static str {
+    "unknown type"
+}
+
+/// Attempts at finding an unused temporary file name before giving up.
+const TEMP_ATTEMPTS: usize = 16;
+
+/// Per-process counter keeping temp names distinct across worker threads.
+static TEMP_SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+
+/// A random value for temp names, so they cannot be predicted from the pid
+/// and the clock. std seeds each `RandomState` from the OS.
+fn temp_name_nonce() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Generates a unique temporary file name in the same directory.
+fn generate_temp_path(original: &Path) -> PathBuf {
+    let parent = original.parent().unwrap_or(Path::new("."));
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let pid = process::id();
+
+    let original_name = original
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("file");
+
+    let sequence = TEMP_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+
+    let temp_name = format!(
+        ".{}.{}.{}.{}.{:016x}.tmp",
+        original_name,
+        pid,
+        timestamp,
+        sequence,
+        temp_name_nonce()
+    );
+    parent.join(temp_name)
+}
+
+/// Whether `name` has the shape of a name made by `generate_temp_path`, or
+/// by earlier versions, whose names had no random field.
+fn is_temp_file_name(name: &OsStr) -> bool {
+    let Some(inner) = name
+        .to_str()
+        .and_then(|name| name.strip_prefix('.'))
+        .and_then(|name| name.strip_suffix(".tmp"))
+    else {
+        return false;
+    };
+    let inner = match inner.rsplit_once('.') {
+        Some((rest, nonce))
+            if nonce.len() == 16 && nonce.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            rest
+        }
+        _ => inner,
+    };
+    let mut fields = inner.rsplitn(4, '.');
+    let numeric = fields
+        .by_ref()
+        .take(3)
+        .filter(|field| !field.is_empty() && field.bytes().all(|b| b.is_ascii_digit()))
+        .count();
+    numeric == 3 && fields.next().is_some_and(|original| !original.is_empty())
+}
+
+/// Creates a fresh temporary file next to `original`. `create_new` checks for
+/// and creates the file in one atomic step, so concurrent rtrim runs never
+/// share a temp file; a name that is already taken is regenerated.
+fn create_temp_file(original: &Path) -> io::Result<(PathBuf, File)> {
+    for _ in 0..TEMP_ATTEMPTS {
+        let temp_path = generate_temp_path(original);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "no unused temporary file name after {} attempts",
+            TEMP_ATTEMPTS
+        ),
+    ))
+}
+
+/// Error action of a failed temp file creation, which `--on-temp-fail` acts on.
+const TEMP_CREATE_FAILED: &str = "failed to create temporary file";
+
+/// A trimmed file written to a temp file by `--group-commit`, waiting for the
+/// rename over its target. What only holds once the target is replaced waits
+/// with it.
+#[derive(Debug)]
+struct StagedWrite {
+    temp: TempFile,
+    target: PathBuf,
+    /// Per-file counts for the `Processed` line.
+    counts: String,
+    /// Hash cache key of the trimmed content.
+    clean_hash: Option<u64>,
+}
+
+/// A temporary file that is removed when dropped, unless it has been renamed
+/// over its target by `persist`. Every early return on the way to the rename,
+/// including a failed write or sync, therefore cleans up after itself.
+#[derive(Debug)]
+struct TempFile {
+    path: PathBuf,
+    file: File,
+    persisted: bool,
+}
+
+impl TempFile {
+    fn create(original: &Path) -> io::Result<TempFile> {
+        let (path, file) = create_temp_file(original)?;
+        Ok(TempFile {
+            path,
+            file,
+            persisted: false,
+        })
+    }
+
+    /// Atomically renames the temp file over `target`.
+    fn persist(mut self, target: &Path) -> io::Result<()> {
+        fs::rename(&self.path, target)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Whether `content` looks like binary data: it has a NUL byte, or more than
+/// a third of its bytes are control characters other than the usual text
+/// ones (tab, newlines, form feed and the escape of terminal colors).
+fn looks_binary(content: &[u8]) -> bool {
+    if content.contains(&0) {
+        return true;
+    }
+    let controls = content
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || b == 0x7f)
+        .count();
+    controls * 3 > content.len()
+}
+
+/// Reads single-byte text (`--encoding latin1`): every byte becomes the
+/// character with the same code point.
+fn decode_latin1(content: &[u8]) -> String {
+    content.iter().map(|&b| char::from(b)).collect()
+}
+
+/// Inverse of `decode_latin1`. Trimming only removes characters, so every
+/// character still fits in one byte.
+fn encode_latin1(content: &str) -> Vec<u8> {
+    content
+        .chars()
+        .map(|c| u8::try_from(c).unwrap_or(b'?'))
+        .collect()
+}
+
+/// The `--backup` copy of `path`: `<name>.bak` next to it.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Whether `name` is a `--backup` copy, which folder walks leave alone.
+fn is_backup_file_name(name: &OsStr) -> bool {
+    Path::new(name).extension() == Some(OsStr::new("bak"))
+}
+
+/// Writes `original` to the backup of `path` with the permissions from
+/// `metadata`. An existing backup is replaced; it is removed first so a
+/// symlink in its place is never followed. With `sync`, the backup and its
+/// directory entry are on disk before this returns, as a `--journal` that
+/// names the backup requires.
+fn write_backup(
+    path: &Path,
+    original: &[u8],
+    metadata: &Metadata,
+    sync: bool,
+) -> io::Result<PathBuf> {
+    let backup = backup_path(path);
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&backup)?;
+    file.write_all(original)?;
+    file.set_permissions(metadata.permissions())?;
+    if sync {
+        file.sync_all()?;
+        sync_dir(match backup.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        })?;
+    }
+    Ok(backup)
+}
+
+/// Restores every file listed in a `--journal` from its backup, the most
+/// recent change first. Files that still hold their original content, such
+/// as those a crash interrupted before the rename, are left alone.
+fn run_rollback(journal_path: &Path, config: &Config, session: &Session) -> io::Result<()> {
+    let reporter = &session.reporter;
+    for entry in journal::load(journal_path)?.iter().rev() {
+        match rollback_file(&entry.path, entry.original) {
+            Ok(true) => {
+                session.record(Some(Outcome::Modified));
+                if !config.porcelain && !config.json_output && !config.quiet {
+                    reporter.info(format_args!("  Restored: {:?}", entry.path));
+                }
+            }
+            Ok(false) => {
+                session.record(Some(Outcome::Unchanged));
+                if config.verbose {
+                    reporter.info(format_args!("  Already original: {:?}", entry.path));
+                }
+            }
+            Err(e) => {
+                session.record(None);
+                report_error(&entry.path, &e, config, reporter);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Puts the backup of `path` back in place if `path` does not hold the
+/// content with hash `original`. Returns whether the file was restored.
+fn rollback_file(path: &Path, original: u64) -> Result<bool, RtrimError> {
+    if fs::read(path).is_ok_and(|content| journal::content_hash(&content) == original) {
+        return Ok(false);
+    }
+    let backup = backup_path(path);
+    let content = fs::read(&backup).context("failed to read backup", path)?;
+    if journal::content_hash(&content) != original {
+        return Err(RtrimError::new(
+            "failed to restore backup",
+            path,
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?} does not hold the journaled original", backup),
+            ),
+        ));
+    }
+    let metadata = fs::metadata(&backup).context("failed to read metadata", &backup)?;
+    write_atomically(path, &content, Some(&metadata))?;
+    Ok(true)
+}
+
+/// Replaces `path` with `content` through a synced temporary file and a rename,
+/// so readers only ever see the old or the new file. The original permissions
+/// are copied over when `original_metadata` is given.
+fn write_atomically(
+    path: &Path,
+    content: &[u8],
+    original_metadata: Option<&Metadata>,
+) -> Result<(), RtrimError> {
+    let temp = write_temp_content(path, content, original_metadata)?;
+    // Atomic rename
+    temp.persist(path).context("failed to replace file", path)
+}
+
+/// Writes and syncs `content` to a new temp file next to `path`, ready to be
+/// renamed over it.
+fn write_temp_content(
+    path: &Path,
+    content: &[u8],
+    original_metadata: Option<&Metadata>,
+) -> Result<TempFile, RtrimError> {
+    write_temp_file(path, original_metadata, |file| {
+        file.write_all(content)
+            .context("failed to write temporary file", path)?;
+        file.sync_all()
+            .context("failed to sync temporary file", path)
+    })
+}
+
+/// Creates the temp file for `path` and fills it with `write`. The temp file
+/// is removed whenever this returns an error.
+fn write_temp_file(
+    path: &Path,
+    original_metadata: Option<&Metadata>,
+    write: impl FnOnce(&mut File) -> Result<(), RtrimError>,
+) -> Result<TempFile, RtrimError> {
+    // Exclusively create a uniquely named temp file (prevents collisions
+    // with concurrent runs and symlink attacks)
+    let mut temp = TempFile::create(path).context(TEMP_CREATE_FAILED, path)?;
+    write(&mut temp.file)?;
+
+    // Preserve original file permissions
+    if let Some(metadata) = original_metadata {
+        preserve_permissions(&temp.path, metadata)
+            .context("failed to preserve permissions", path)?;
+    }
+    Ok(temp)
+}
+
+/// Overwrites `path` through its existing inode for `--keep-inode`: hard links,
+/// open handles and watchers keep pointing at the file. Unlike
+/// `write_atomically` this is not atomic; an interruption can leave the file
+/// partially written. Without `sync` (`--atomic off`) the new content may
+/// also still be lost on a crash.
+fn rewrite_in_place(path: &Path, content: &[u8], sync: bool) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(content)?;
+    file.set_len(content.len() as u64)?;
+    if sync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Sets the modification time of the rewritten `path` back to the one in
+/// `original_metadata`, for `--preserve-mtime`. Only the owner may do this.
+fn restore_mtime(path: &Path, original_metadata: &Metadata) -> io::Result<()> {
+    File::open(path)?.set_modified(original_metadata.modified()?)
+}
+
+/// Applies the original file permissions to the new file.
+#[cfg(unix)]
+fn preserve_permissions(temp_path: &Path, original_metadata: &Metadata) -> io::Result<()> {
+    let permissions = Permissions::from_mode(original_metadata.mode());
+    fs::set_permissions(temp_path, permissions)?;
+    Ok(())
+}
+
+/// Outside Unix only the readonly attribute carries over.
+#[cfg(not(unix))]
+fn preserve_permissions(temp_path: &Path, original_metadata: &Metadata) -> io::Result<()> {
+    let mut permissions = fs::metadata(temp_path)?.permissions();
+    permissions.set_readonly(original_metadata.permissions().readonly());
+    fs::set_permissions(temp_path, permissions)
+}
+
+/// Flushes a directory entry change, such as a rename, to disk.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories cannot be opened as files outside Unix; the rename itself is
+/// all that can be done there.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// What happened to a single file during processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Modified,
+    Unchanged,
+    Skipped(SkipReason),
+}
+
+/// Why a file was left untouched without being trimmed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Symlink,
+    NotRegular,
+    Binary,
+    TooManyLines,
+    UnsupportedLanguage,
+    Duplicate,
+    ReadOnly,
+    #[cfg(feature = "regex")]
+    NoContentMatch,
+    #[cfg(feature = "ipynb")]
+    MalformedNotebook,
+    BelowDirtyThreshold,
+    NoBlame,
+    IgnoredDir,
+    Outlier,
+    TooManyChanges,
+    UnchangedSince,
+    NotStaged,
+    PartiallyStaged,
+    EditorConfig,
+    NoTempFile,
+    Conflicted,
+    ExcludedExtension,
+    Gitignored,
+}
+
+impl SkipReason {
+    /// Short reason shown by `--list-ignored`.
+    fn describe(self) -> &'static str {
+        match self {
+            SkipReason::Symlink => "symlink",
+            SkipReason::NotRegular => "not a regular file",
+            SkipReason::Binary => "binary",
+            SkipReason::TooManyLines => "too many lines",
+            SkipReason::UnsupportedLanguage => "unsupported language",
+            SkipReason::Duplicate => "duplicate",
+            SkipReason::ReadOnly => "read-only",
+            #[cfg(feature = "regex")]
+            SkipReason::NoContentMatch => "no content match",
+            #[cfg(feature = "ipynb")]
+            SkipReason::MalformedNotebook => "malformed notebook",
+            SkipReason::BelowDirtyThreshold => "below dirty-line threshold",
+            SkipReason::NoBlame => "no git blame",
+            SkipReason::IgnoredDir => "ignored dir",
+            SkipReason::Outlier => "size outlier",
+            SkipReason::TooManyChanges => "too many changes",
+            SkipReason::UnchangedSince => "unchanged since revision",
+            SkipReason::NotStaged => "not staged",
+            SkipReason::PartiallyStaged => "unstaged changes",
+            SkipReason::EditorConfig => "editorconfig",
+            SkipReason::NoTempFile => "no temporary file",
+            SkipReason::Conflicted => "merge conflict",
+            SkipReason::ExcludedExtension => "excluded extension",
+            SkipReason::Gitignored => "gitignore",
+        }
+    }
+}
+
+impl Outcome {
+    /// Status code used by `--porcelain`. These codes are a stable interface
+    /// for scripts: never change an existing one, only add new ones.
+    pub fn porcelain_code(self) -> &'static str {
+        match self {
+            Outcome::Modified => "M",
+            Outcome::Unchanged => "U",
+            Outcome::Skipped(reason) => match reason {
+                SkipReason::Binary => "Bb",
+                SkipReason::Symlink => "Sl",
+                SkipReason::NotRegular => "Sn",
+                SkipReason::TooManyLines => "Sx",
+                SkipReason::UnsupportedLanguage => "Su",
+                SkipReason::Duplicate => "Sd",
+                SkipReason::ReadOnly => "Sr",
+                #[cfg(feature = "regex")]
+                SkipReason::NoContentMatch => "Sc",
+                #[cfg(feature = "ipynb")]
+                SkipReason::MalformedNotebook => "Sj",
+                SkipReason::BelowDirtyThreshold => "St",
+                SkipReason::NoBlame => "Sg",
+                SkipReason::IgnoredDir => "Si",
+                SkipReason::Outlier => "So",
+                SkipReason::TooManyChanges => "Sf",
+                SkipReason::UnchangedSince => "Sv",
+                SkipReason::NotStaged => "Sa",
+                SkipReason::PartiallyStaged => "Sp",
+                SkipReason::EditorConfig => "Sk",
+                SkipReason::NoTempFile => "Sw",
+                SkipReason::Conflicted => "Sm",
+                SkipReason::ExcludedExtension => "Se",
+                SkipReason::Gitignored => "Sh",
+            },
+        }
+    }
+}
+
+/// Counts lines in a raw buffer, including a final unterminated line.
+fn count_lines(buffer: &[u8]) -> usize {
+    let newlines = buffer.iter().filter(|&&b| b == b'\n').count();
+    match buffer.last() {
+        Some(b'\n') | None => newlines,
+        Some(_) => newlines + 1,
+    }
+}
+
+/// Whether `content` has the opening and closing markers of an unresolved
+/// merge conflict. `=======` alone is not enough: it also underlines headings.
+fn has_conflict_markers(content: &str) -> bool {
+    let is_marker = |line: &str, marker: &str| {
+        line.strip_prefix(marker)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    };
+    let mut lines = content.lines();
+    lines.any(|line| is_marker(line, "<<<<<<<")) && lines.any(|line| is_marker(line, ">>>>>>>"))
+}
+
+/// Trailing whitespace characters grouped by kind.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WhitespaceBreakdown {
+    pub spaces: usize,
+    pub tabs: usize,
+    pub other: usize,
+    /// Number of lines the whitespace was removed from.
+    pub lines: usize,
+}
+
+impl WhitespaceBreakdown {
+    /// Records the whitespace removed from one line.
+    fn count(&mut self, removed: &str) {
+        self.lines += 1;
+        for c in removed.chars() {
+            match c {
+                ' ' => self.spaces += 1,
+                '\t' => self.tabs += 1,
+                _ => self.other += 1,
+            }
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.spaces + self.tabs + self.other
+    }
+
+    /// Formats the counts at the requested granularity.
+    fn describe(&self, granularity: TrimReport) -> String {
+        let lines = format!("{} lines", self.lines);
+        let chars = format!(
+            "{} spaces, {} tabs, {} other ({} total)",
+            self.spaces,
+            self.tabs,
+            self.other,
+            self.total()
+        );
+        match granularity {
+            TrimReport::Lines => lines,
+            TrimReport::Chars => chars,
+            TrimReport::Both => format!("{}, {}", lines, chars),
+        }
+    }
+}
+
+impl AddAssign for WhitespaceBreakdown {
+    fn add_assign(&mut self, rhs: Self) {
+        self.spaces += rhs.spaces;
+        self.tabs += rhs.tabs;
+        self.other += rhs.other;
+        self.lines += rhs.lines;
+    }
+}
+
+/// Result of trimming operation.
+#[derive(Debug, PartialEq)]
+pub struct TrimResult {
+    pub content: String,
+    pub modified: bool,
+    /// The trailing whitespace that was removed, by kind.
+    pub removed: WhitespaceBreakdown,
+    /// Number of lines whitespace was removed from.
+    pub lines_modified: usize,
+    /// Number of whitespace characters removed.
+    pub chars_removed: usize,
+    /// Whether the line ending after the last line changed: added, dropped,
+    /// or turned from CRLF into LF.
+    pub trailing_newline_changed: bool,
+}
+
+impl TrimResult {
+    /// Builds the result of turning `original` into `content`, filling in
+    /// the counts from `removed`.
+    fn new(original: &str, content: String, modified: bool, removed: WhitespaceBreakdown) -> Self {
+        TrimResult {
+            lines_modified: removed.lines,
+            chars_removed: removed.total(),
+            trailing_newline_changed: final_newline(original) != final_newline(&content),
+            content,
+            modified,
+            removed,
+        }
+    }
+}
+
+/// The line ending `text` finishes with, if any.
+fn final_newline(text: &str) -> Option<&'static str> {
+    if text.ends_with("\r\n") {
+        Some("\r\n")
+    } else if text.ends_with('\n') {
+        Some("\n")
+    } else {
+        None
+    }
+}
+
+/// Which whitespace is removed from a line that gets trimmed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Strip {
+    #[default]
+    Trailing,
+    /// Leading and trailing (`--strip-both`).
+    Both,
+    /// Trailing ASCII whitespace only, for text decoded byte by byte from a
+    /// single-byte encoding, where U+0085 and U+00A0 are printable characters.
+    Ascii,
+}
+
+impl Strip {
+    fn for_config(config: &Config) -> Strip {
+        if config.strip_both {
+            Strip::Both
+        } else {
+            Strip::Trailing
+        }
+    }
+
+    /// Byte range of `line` that is kept.
+    fn kept_range(self, line: &str) -> Range<usize> {
+        let end = line.trim_end().len();
+        match self {
+            Strip::Trailing => 0..end,
+            Strip::Both => end - line[..end].trim_start().len()..end,
+            Strip::Ascii => {
+                0..line
+                    .trim_end_matches([' ', '\t', '\x0b', '\x0c', '\r'])
+                    .len()
+            }
+        }
+    }
+}
+
+/// Removes trailing whitespace from each line of the input.
+/// Returns the trimmed content and whether any modifications were made.
+pub fn trim_trailing_whitespace(content: &str) -> TrimResult {
+    trim_lines_with(content, Strip::Trailing, |_, _| true)
+}
+
+/// Like `trim_trailing_whitespace`, but strips the whitespace selected by
+/// `strip`, and only from the lines for which `should_trim(index, line)`
+/// returns true; other lines are kept verbatim.
+fn trim_lines_with(
+    content: &str,
+    strip: Strip,
+    mut should_trim: impl FnMut(usize, &str) -> bool,
+) -> TrimResult {
+    let mut output = String::with_capacity(content.len());
+    let mut modified = false;
+    let mut removed = WhitespaceBreakdown::default();
+    // Every line gets a newline back, except a last line that had none.
+    let ends_with_newline = content.ends_with('\n');
+    let mut lines = content.lines().enumerate().peekable();
+
+    while let Some((index, line)) = lines.next() {
+        let kept = if should_trim(index, line) {
+            strip.kept_range(line)
+        } else {
+            0..line.len()
+        };
+        let trimmed = &line[kept.clone()];
+        if trimmed.len() != line.len() {
+            modified = true;
+            if kept.start > 0 {
+                removed.count(&[&line[..kept.start], &line[kept.end..]].concat());
+            } else {
+                removed.count(&line[kept.end..]);
+            }
+        }
+        output.push_str(trimmed);
+        if ends_with_newline || lines.peek().is_some() {
+            output.push('\n');
+        }
+    }
+
+    TrimResult::new(content, output, modified, removed)
+}
+
+/// Reverts every line of `result` whose flag in `allowed` is not set back to
+/// its `original` text. Lines past the end of `allowed` are reverted too.
+fn restrict_to_lines(original: &str, result: TrimResult, allowed: &[bool]) -> TrimResult {
+    let mut output = String::with_capacity(result.content.len());
+    let mut removed = WhitespaceBreakdown::default();
+    for (index, (before, after)) in original.lines().zip(result.content.lines()).enumerate() {
+        let line = if allowed.get(index).copied().unwrap_or(false) {
+            after
+        } else {
+            before
+        };
+        let had = &before[before.trim_end().len()..];
+        let kept = line.len() - line.trim_end().len();
+        match had.get(kept..) {
+            Some(tail) if !tail.is_empty() => removed.count(tail),
+            _ => {}
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    if !result.content.ends_with('\n') && !output.is_empty() {
+        output.pop();
+    }
+
+    let modified = output != original;
+    TrimResult::new(original, output, modified, removed)
+}
+
+/// Default columns per indentation level for `--normalize-indent`.
+const DEFAULT_INDENT_SIZE: usize = 4;
+
+/// Default spaces before an inline comment for `--normalize-comment-spacing`.
+const DEFAULT_COMMENT_SPACES: usize = 1;
+
+/// Applies the trimming mode selected in `config` to the content of `path`,
+/// followed by indentation and comment spacing normalization when requested.
+/// Returns the reason to skip the file when the mode does not apply to it.
+fn trim_for_config(path: &Path, content: &str, config: &Config) -> Result<TrimResult, SkipReason> {
+    let mut result = trim_trailing_for_config(path, content, config)?;
+    // Notebook indentation belongs to the JSON layout, which --ipynb keeps.
+    #[cfg(feature = "ipynb")]
+    let notebook = config.ipynb && has_extension(path, &["ipynb"]);
+    #[cfg(not(feature = "ipynb"))]
+    let notebook = false;
+    if config.normalize_indent && !notebook {
+        if let Some(style) = config
+            .indent_style
+            .or_else(|| indent::detect(&result.content))
+        {
+            let size = config.indent_size.unwrap_or(DEFAULT_INDENT_SIZE);
+            let (normalized, changed) =
+                indent::normalize(&result.content, style, size, config.keep_alignment);
+            if changed > 0 {
+                result.content = normalized;
+                result.modified = true;
+            }
+        }
+    }
+    if config.normalize_comment_spacing {
+        if let Some(lang) = Language::from_path(path) {
+            let spaces = config.comment_spaces.unwrap_or(DEFAULT_COMMENT_SPACES);
+            let (normalized, changed) =
+                lang::normalize_comment_spacing(&result.content, lang, spaces);
+            if changed > 0 {
+                result.content = normalized;
+                result.modified = true;
+            }
+        }
+    }
+    if config.squeeze_blank_eof && !notebook {
+        if let Some(squeezed) = without_blank_eof(&result.content) {
+            result.content = squeezed;
+            result.modified = true;
+        }
+    }
+    if config.final_newline && !notebook {
+        if let Some(ended) = with_final_newline(&result.content) {
+            result.content = ended;
+            result.modified = true;
+        }
+    }
+    Ok(result)
+}
+
+/// Removes trailing whitespace as selected by the trimming mode in `config`.
+fn trim_trailing_for_config(
+    path: &Path,
+    content: &str,
+    config: &Config,
+) -> Result<TrimResult, SkipReason> {
+    let strip = Strip::for_config(config);
+    if config.comments_only {
+        let lang = Language::from_path(path).ok_or(SkipReason::UnsupportedLanguage)?;
+        let ends = lang::line_ends(content, lang);
+        return Ok(trim_lines_with(content, strip, |index, line| {
+            ends.get(index) == Some(&LineEnd::Comment) && !is_protected(line, config)
+        }));
+    }
+    if config.preserve_strings {
+        if let Some(lang) = Language::from_path(path) {
+            let ends = lang::line_ends(content, lang);
+            return Ok(trim_lines_with(content, strip, |index, line| {
+                ends.get(index).is_some_and(|end| *end != LineEnd::String)
+                    && !is_protected(line, config)
+            }));
+        }
+    }
+    if config.yaml_aware && has_extension(path, &["yaml", "yml"]) {
+        let protected = significant::yaml_block_scalars(content);
+        return Ok(trim_lines_with(content, strip, |index, line| {
+            !protected[index] && !is_protected(line, config)
+        }));
+    }
+    if config.shell_aware && has_extension(path, &["sh", "bash"]) {
+        let protected = significant::shell_heredocs(content);
+        return Ok(trim_lines_with(content, strip, |index, line| {
+            !protected[index] && !is_protected(line, config)
+        }));
+    }
+    if config.csv_aware && has_extension(path, &["csv"]) {
+        let protected = significant::csv_quoted_line_ends(content);
+        return Ok(trim_lines_with(content, strip, |index, line| {
+            !protected[index] && !is_protected(line, config)
+        }));
+    }
+    if config.html_aware && has_extension(path, &["html", "htm"]) {
+        let protected = significant::html_preformatted(content);
+        return Ok(trim_lines_with(content, strip, |index, line| {
+            !protected[index] && !is_protected(line, config)
+        }));
+    }
+    #[cfg(feature = "ipynb")]
+    if config.ipynb && has_extension(path, &["ipynb"]) {
+        return trim_notebook(content, config);
+    }
+    if protects_lines(config) {
+        return Ok(trim_lines_with(content, strip, |_, line| {
+            !is_protected(line, config)
+        }));
+    }
+    if strip == Strip::Both {
+        return Ok(trim_lines_with(content, strip, |_, _| true));
+    }
+    Ok(trim_trailing_whitespace(content))
+}
+
+/// Whether any option can keep a line verbatim, see `is_protected`.
+fn protects_lines(config: &Config) -> bool {
+    #[cfg(feature = "regex")]
+    if config.protect_regex.is_some() {
+        return true;
+    }
+    config.preserve_continuations
+}
+
+/// Whether `line` must be kept verbatim: it matches `--protect-regex`, or it
+/// ends in a backslash under `--preserve-continuations`. Such a line is kept
+/// whole, whitespace after the backslash included: trimming `foo \   ` would
+/// turn an escaped space into a line continuation.
+fn is_protected(line: &str, config: &Config) -> bool {
+    if config.preserve_continuations && line.trim_end().ends_with('\\') {
+        return true;
+    }
+    #[cfg(feature = "regex")]
+    if let Some(pattern) = &config.protect_regex {
+        return pattern.is_match(line);
+    }
+    false
+}
+
+/// Trims the code cell sources of a notebook, splicing each changed string
+/// back into the original JSON so the rest of the document is byte-identical.
+#[cfg(feature = "ipynb")]
+fn trim_notebook(content: &str, config: &Config) -> Result<TrimResult, SkipReason> {
+    let sources =
+        notebook::code_cell_sources(content).map_err(|_| SkipReason::MalformedNotebook)?;
+
+    let mut output = String::with_capacity(content.len());
+    let mut removed = WhitespaceBreakdown::default();
+    let mut copied = 0;
+    for source in sources {
+        let result = trim_lines_with(&source.text, Strip::for_config(config), |_, line| {
+            !is_protected(line, config)
+        });
+        if !result.modified {
+            continue;
+        }
+        removed += result.removed;
+        output.push_str(&content[copied..source.span.start]);
+        output.push_str(&json::encode_string(&result.content));
+        copied = source.span.end;
+    }
+    output.push_str(&content[copied..]);
+
+    let modified = output != content;
+    Ok(TrimResult::new(content, output, modified, removed))
+}
+
+/// Hash cache key for `content`. The extension, the trimming mode, the
+/// declared line ending and whether `.editorconfig` asks for a final newline
+/// are part of the key, since all decide whether the same content counts as
+/// clean.
+fn hash_cache_key(
+    path: &Path,
+    content: &str,
+    config: &Config,
+    eol: Option<Eol>,
+    insert_final_newline: bool,
+) -> u64 {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    #[cfg(feature = "ipynb")]
+    let ipynb = config.ipynb;
+    #[cfg(not(feature = "ipynb"))]
+    let ipynb = false;
+    #[cfg(feature = "regex")]
+    let protect = config.protect_regex.as_ref().map_or("", Pattern::as_str);
+    #[cfg(not(feature = "regex"))]
+    let protect = "";
+    let indent = match (config.normalize_indent, config.indent_style) {
+        (false, _) => String::new(),
+        (true, style) => format!(
+            "{:?}/{:?}/{}",
+            style, config.indent_size, config.keep_alignment
+        ),
+    };
+    let comments = match config.normalize_comment_spacing {
+        false => String::new(),
+        true => format!("{:?}", config.comment_spaces),
+    };
+    let tag = format!(
+        "{}|{:?}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+        extension,
+        eol,
+        insert_final_newline,
+        config.final_newline,
+        config.squeeze_blank_eof,
+        config.comments_only,
+        config.preserve_strings,
+        config.preserve_continuations,
+        config.yaml_aware,
+        config.shell_aware,
+        config.csv_aware,
+        config.html_aware,
+        config.strip_both,
+        config.with_rustfmt,
+        ipynb,
+        protect,
+        indent,
+        comments
+    );
+    cache::content_key(&tag, content.as_bytes())
+}
+
+/// Checks the file extension against a list of lowercase extensions.
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// `a/` and `b/` labels for a patch entry, as `git diff` writes them. A leading
+/// `./` is dropped, and absolute paths become relative to the root directory.
+fn patch_labels(path: &Path) -> (String, String) {
+    let display = path.to_string_lossy();
+    let relative = display.strip_prefix("./").unwrap_or(&display);
+    let relative = relative.trim_start_matches('/');
+    (format!("a/{}", relative), format!("b/{}", relative))
+}
+
+/// Suffix for a per-file line with the removed whitespace, when `--trim-report`
+/// is given.
+fn per_file_counts(removed: &WhitespaceBreakdown, config: &Config) -> String {
+    match config.trim_report {
+        Some(granularity) => format!(" [{}]", removed.describe(granularity)),
+        None => String::new(),
+    }
+}
+
+/// `--dry-run-write-report` text: one path per line, as `--check` prints them.
+fn dry_run_text_report(files: &[(PathBuf, WhitespaceBreakdown)], config: &Config) -> String {
+    files
+        .iter()
+        .map(|(path, removed)| format!("{}{}\n", path.display(), per_file_counts(removed, config)))
+        .collect()
+}
+
+/// `--dry-run-write-report` JSON: every file with its whitespace counts, and
+/// the totals.
+fn dry_run_json_report(files: &[(PathBuf, WhitespaceBreakdown)]) -> String {
+    let counts = |removed: &WhitespaceBreakdown| {
+        format!(
+            "\"lines\": {}, \"spaces\": {}, \"tabs\": {}, \"other\": {}",
+            removed.lines, removed.spaces, removed.tabs, removed.other
+        )
+    };
+    let mut total = WhitespaceBreakdown::default();
+    let mut entries = Vec::with_capacity(files.len());
+    for (path, removed) in files {
+        total += *removed;
+        entries.push(format!(
+            "    {{\"path\": {}, {}}}",
+            json::encode_string(&path.to_string_lossy()),
+            counts(removed)
+        ));
+    }
+    format!(
+        "{{\n  \"files\": [\n{}\n  ],\n  \"total\": {{\"files\": {}, {}}}\n}}\n",
+        entries.join(",\n"),
+        files.len(),
+        counts(&total)
+    )
+}
+
+/// `content` without the blank lines at its end, for `--squeeze-blank-eof`:
+/// text keeps the newline after its last line, and text with nothing but
+/// newlines becomes empty. A missing final newline is not added. Returns
+/// `None` when nothing changes.
+fn without_blank_eof(content: &str) -> Option<String> {
+    let text = content.trim_end_matches(['\n', '\r']);
+    let squeezed = if text.is_empty() || text.len() == content.len() {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    };
+    (squeezed != content).then_some(squeezed)
+}
+
+/// `content` ending with exactly one newline, for `--final-newline`: a
+/// missing one is added and trailing blank lines are dropped. Text without
+/// anything but newlines becomes empty. Returns `None` when nothing changes.
+fn with_final_newline(content: &str) -> Option<String> {
+    let text = content.trim_end_matches(['\n', '\r']);
+    let ended = if text.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", text)
+    };
+    (ended != content).then_some(ended)
+}
+
+/// Reports whether the output differs from the input in ending with a newline.
+fn eof_newline_changed(input: &str, output: &str) -> bool {
+    input.ends_with('\n') != output.ends_with('\n')
+}
+
+fn describe_eof(content: &str) -> &'static str {
+    if content.ends_with('\n') {
+        "ends with newline"
+    } else {
+        "has no final newline"
+    }
+}
+
+/// Runs the `--on-modify-exec` command for a modified file. The path replaces
+/// every `{}` argument, or is appended when no placeholder is present.
+fn run_on_modify(command: &[String], path: &Path) -> io::Result<()> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+
+    let mut cmd = Command::new(program);
+    let mut placeholder = false;
+    for arg in args {
+        if arg == "{}" {
+            cmd.arg(path);
+            placeholder = true;
+        } else {
+            cmd.arg(arg);
+        }
+    }
+    if !placeholder {
+        cmd.arg(path);
+    }
+
+    let status = cmd.stdin(Stdio::null()).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("command exited with {}", status)))
+    }
+}
+
+/// Edition passed to rustfmt, which cannot see the crate's `Cargo.toml` when
+/// reading from stdin.
+const RUSTFMT_EDITION: &str = "2021";
+
+/// Replaces the trimmed content of a Rust file with rustfmt's output. When
+/// rustfmt is missing or rejects the file, the trimmed content is kept.
+fn format_with_rustfmt(path: &Path, original: &str, result: &mut TrimResult, session: &Session) {
+    match rustfmt(&result.content) {
+        Ok(formatted) => {
+            result.modified = formatted != original;
+            result.content = formatted;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if !session.rustfmt_missing.swap(true, Ordering::Relaxed) {
+                session
+                    .reporter
+                    .warn(format_args!("rustfmt not found; --with-rustfmt only trims"));
+            }
+        }
+        Err(e) => {
+            session.reporter.warn(format_args!(
+                "rustfmt failed for {:?}, only trimming: {}",
+                path, e
+            ));
+        }
+    }
+}
+
+/// Formats Rust source by piping it through `rustfmt`.
+fn rustfmt(source: &str) -> io::Result<String> {
+    let mut child = Command::new("rustfmt")
+        .args(["--emit", "stdout", "--edition", RUSTFMT_EDITION])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Feed stdin from another thread so a large output cannot fill the pipe
+    // while rustfmt is still waiting for input.
+    let output = thread::scope(|scope| {
+        scope.spawn(move || stdin.write_all(source.as_bytes()));
+        child.wait_with_output()
+    })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "rustfmt exited with {}",
+            output.status
+        )));
+    }
+    String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Individual file processing with security validation and atomicity.
+pub fn process_file(
+    path: &Path,
+    config: &Config,
+    session: &Session,
+) -> Result<Outcome, RtrimError> {
+    let reporter = &session.reporter;
+    let verbose = config.verbose;
+
+    // Double-check it's not a symlink (defense in depth). A followed link
+    // is processed as its target, which must stay in the link's directory.
+    let original_metadata = fs::symlink_metadata(path).context("failed to read metadata", path)?;
+    if original_metadata.file_type().is_symlink() {
+        let reason = if config.follow_symlinks {
+            let dir = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let root = fs::canonicalize(dir).context("failed to resolve directory", path)?;
+            match resolve_symlink(path, &root, config) {
+                Ok((target, _)) => {
+                    let outcome = process_file(&target, config, session)?;
+                    // `--format json` reports the lines under the link's path.
+                    let mut lines_trimmed = session.lines_trimmed.lock().unwrap();
+                    if let Some(lines) = lines_trimmed.remove(&target) {
+                        lines_trimmed.insert(path.to_path_buf(), lines);
+                    }
+                    return Ok(outcome);
+                }
+                Err(reason) => reason,
+            }
+        } else {
+            SkipReason::Symlink.describe()
+        };
+        if verbose {
+            reporter.info(format_args!("  Skipped ({}): {:?}", reason, path));
+        }
+        return Ok(Outcome::Skipped(SkipReason::Symlink));
+    }
+
+    if !original_metadata.is_file() {
+        return Ok(Outcome::Skipped(SkipReason::NotRegular));
+    }
+
+    if let Some(&limit) = session.size_limit.get() {
+        if original_metadata.len() > limit {
+            reporter.warn(format_args!(
+                "Skipped {:?}: {} bytes is above the --skip-outliers limit of {} bytes",
+                path,
+                original_metadata.len(),
+                limit
+            ));
+            return Ok(Outcome::Skipped(SkipReason::Outlier));
+        }
+    }
+
+    // The rename would succeed regardless, but a read-only mode usually
+    // signals that the file is not meant to be edited.
+    if config.skip_readonly && original_metadata.permissions().readonly() {
+        if verbose {
+            reporter.info(format_args!("  Skipped (read-only): {:?}", path));
+        }
+        return Ok(Outcome::Skipped(SkipReason::ReadOnly));
+    }
+
+    if config.dedupe && !session.first_visit(path)? {
+        if verbose {
+            reporter.info(format_args!("  Skipped (duplicate): {:?}", path));
+        }
+        return Ok(Outcome::Skipped(SkipReason::Duplicate));
+    }
+
+    if session.unchanged_since(path)? {
+        if verbose {
+            reporter.info(format_args!(
+                "  Skipped (unchanged since revision): {:?}",
+                path
+            ));
+        }
+        return Ok(Outcome::Skipped(SkipReason::UnchangedSince));
+    }
+
+    if let Some(reason) = session.staged_skip(path)? {
+        if verbose {
+            reporter.info(format_args!(
+                "  Skipped ({}): {:?}",
+                reason.describe(),
+                path
+            ));
+        }
+        return Ok(Outcome::Skipped(reason));
+    }
+
+    let editor = match config.editorconfig {
+        true => session.editorconfigs.properties_for(path),
+        false => Default::default(),
+    };
+    if config.editorconfig && editor.trim_trailing_whitespace != Some(true) {
+        if verbose {
+            reporter.info(format_args!("  Skipped (editorconfig): {:?}", path));
+        }
+        return Ok(Outcome::Skipped(SkipReason::EditorConfig));
+    }
+    let insert_final_newline = editor.insert_final_newline == Some(true);
+
+    if verbose {
+        reporter.info(format_args!("  Checking: {:?}", path));
+    }
+
+    // Diagnostic only, and before the UTF-8 check: UTF-16 files are not
+    // valid UTF-8, and the first bytes are all this report needs.
+    if config.bom_report {
+        let mut prefix = Vec::with_capacity(Bom::MAX_LEN);
+        File::open(path)
+            .context("failed to open file", path)?
+            .take(Bom::MAX_LEN as u64)
+            .read_to_end(&mut prefix)
+            .context("failed to read file", path)?;
+        session
+            .boms
+            .lock()
+            .unwrap()
+            .record(Bom::detect(&prefix), path);
+        return Ok(Outcome::Unchanged);
+    }
+
+    let mut buffer = Vec::new();
+    {
+        let mut file = File::open(path).context("failed to open file", path)?;
+        file.read_to_end(&mut buffer)
+            .context("failed to read file", path)?;
+    }
+
+    // Binary file protection: NUL bytes or a mostly-control-character file
+    // are binary. Other text must be UTF-8 unless --lossy or --encoding says
+    // how to read it.
+    let decoded;
+    let mut single_byte = false;
+    let content = match std::str::from_utf8(&buffer) {
+        _ if looks_binary(&buffer) => None,
+        Ok(s) => Some(s),
+        Err(_) if config.lossy => {
+            reporter.warn(format_args!(
+                "{:?} is not valid UTF-8; --lossy replaces invalid bytes with U+FFFD if it is rewritten",
+                path
+            ));
+            decoded = String::from_utf8_lossy(&buffer).into_owned();
+            Some(decoded.as_str())
+        }
+        Err(_) if config.encoding == Encoding::Latin1 => {
+            single_byte = true;
+            decoded = decode_latin1(&buffer);
+            Some(decoded.as_str())
+        }
+        Err(_) => None,
+    };
+    let Some(content) = content else {
+        if verbose {
+            reporter.info(format_args!("  Skipped (binary): {:?}", path));
+        }
+        return Ok(Outcome::Skipped(SkipReason::Binary));
+    };
+
+    // Diagnostic only: the final byte is all this report needs. Empty files
+    // have no line to terminate and are left out of both lists.
+    if config.eof_newline_report {
+        if let Some(&last) = buffer.last() {
+            let mut eof = session.eof_newlines.lock().unwrap();
+            let list = if last == b'\n' {
+                &mut eof.with_newline
+            } else {
+                &mut eof.without_newline
+            };
+            list.push(path.to_path_buf());
+        }
+        return Ok(Outcome::Unchanged);
+    }
+
+    if config.longest_lines_report.is_some() {
+        if let Some(longest) = LongestLine::find(path, content) {
+            session.longest_lines.lock().unwrap().push(longest);
+        }
+        return Ok(Outcome::Unchanged);
+    }
+
+    if let Some(max_lines) = config.max_lines {
+        let lines = count_lines(&buffer);
+        if lines > max_lines {
+            reporter.warn(format_args!(
+                "Skipped {:?}: {} lines exceeds --max-lines {}",
+                path, lines, max_lines
+            ));
+            return Ok(Outcome::Skipped(SkipReason::TooManyLines));
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    if let Some(pattern) = &config.content_match {
+        if !content.lines().any(|line| pattern.is_match(line)) {
+            if verbose {
+                reporter.info(format_args!(
+                    "  Skipped (no match for {:?}): {:?}",
+                    pattern.as_str(),
+                    path
+                ));
+            }
+            return Ok(Outcome::Skipped(SkipReason::NoContentMatch));
+        }
+    }
+
+    if !config.allow_conflicts && has_conflict_markers(content) {
+        reporter.warn(format_args!(
+            "Skipped {:?}: unresolved merge conflict markers (use --allow-conflicts to trim it)",
+            path
+        ));
+        return Ok(Outcome::Skipped(SkipReason::Conflicted));
+    }
+
+    let eol = config
+        .respect_gitattributes_eol
+        .then(|| session.eol_rules.eol_for(path))
+        .flatten();
+
+    // Blame, not content, decides what --author-lines trims, so content
+    // hashes say nothing about whether the file is clean. Decoded
+    // single-byte text could collide with UTF-8 text that trims differently.
+    let cache_key = config
+        .hash_cache
+        .as_ref()
+        .filter(|_| config.author_lines.is_none() && !single_byte)
+        .map(|_| hash_cache_key(path, content, config, eol, insert_final_newline));
+    if let Some(key) = cache_key {
+        if session.clean_hashes.lock().unwrap().contains(&key) {
+            if verbose {
+                reporter.info(format_args!("  Unchanged (cached): {:?}", path));
+            }
+            return Ok(Outcome::Unchanged);
+        }
+    }
+
+    let authored = match &config.author_lines {
+        Some(author) => match blame::authored_lines(path, author) {
+            Ok(authored) => Some(authored),
+            Err(e) => {
+                if verbose {
+                    reporter.info(format_args!("  Skipped (git blame: {}): {:?}", e, path));
+                }
+                return Ok(Outcome::Skipped(SkipReason::NoBlame));
+            }
+        },
+        None => None,
+    };
+
+    // Source-aware options need Unicode text; single-byte files only lose
+    // their trailing ASCII whitespace.
+    let trimmed = if single_byte {
+        Ok(trim_lines_with(content, Strip::Ascii, |_, _| true))
+    } else {
+        trim_for_config(path, content, config)
+    };
+    let mut result = match trimmed {
+        Ok(result) => match &authored {
+            Some(authored) => restrict_to_lines(content, result, authored),
+            None => result,
+        },
+        Err(reason) => {
+            if verbose {
+                let why = match reason {
+                    #[cfg(feature = "ipynb")]
+                    SkipReason::MalformedNotebook => "malformed notebook",
+                    _ => "unsupported language",
+                };
+                reporter.info(format_args!("  Skipped ({}): {:?}", why, path));
+            }
+            return Ok(Outcome::Skipped(reason));
+        }
+    };
+
+    if config.with_rustfmt && has_extension(path, &["rs"]) {
+        format_with_rustfmt(path, content, &mut result, session);
+    }
+
+    if insert_final_newline && !result.content.is_empty() && !result.content.ends_with('\n') {
+        result.content.push('\n');
+        result.modified = true;
+    }
+
+    // Trimming leaves LF endings; a declared CRLF policy puts them back, and
+    // a declared LF policy also rewrites clean CRLF files.
+    if let Some(eol) = eol {
+        let converted = eol.apply(&result.content);
+        result.modified = converted != content;
+        result.content = converted;
+    }
+
+    // Diagnostic only: the debt is what trimming would remove, bucketed by
+    // the mtime read above.
+    if config.age_report {
+        let modified = original_metadata
+            .modified()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        session
+            .ages
+            .lock()
+            .unwrap()
+            .record(AgeBucket::of(modified, SystemTime::now()), &result.removed);
+        return Ok(Outcome::Unchanged);
+    }
+
+    if let Some(min) = config.min_dirty_lines {
+        if result.modified && result.removed.lines < min {
+            session.below_threshold.fetch_add(1, Ordering::Relaxed);
+            if verbose {
+                reporter.info(format_args!(
+                    "  Skipped ({} dirty lines, below --min-dirty-lines {}): {:?}",
+                    result.removed.lines, min, path
+                ));
+            }
+            return Ok(Outcome::Skipped(SkipReason::BelowDirtyThreshold));
+        }
+    }
+
+    if let Some(max) = config.max_changed_fraction {
+        let total = content.lines().count();
+        let changed = content
+            .lines()
+            .zip(result.content.lines())
+            .filter(|(before, after)| before != after)
+            .count();
+        if total > 0 && changed as f64 / total as f64 > max {
+            reporter.warn(format_args!(
+                "Skipped {:?}: trimming would change {} of {} lines, more than --max-changed-fraction {}",
+                path, changed, total, max
+            ));
+            return Ok(Outcome::Skipped(SkipReason::TooManyChanges));
+        }
+    }
+
+    // --final-newline, --squeeze-blank-eof and .editorconfig's
+    // insert_final_newline change the end of file on purpose.
+    if (verbose || config.warn_eof_newline)
+        && !config.final_newline
+        && !config.squeeze_blank_eof
+        && !insert_final_newline
+        && eof_newline_changed(content, &result.content)
+    {
+        reporter.warn(format_args!(
+            "Trimming changed the end-of-file newline of {:?} (input {}, output {})",
+            path,
+            describe_eof(content),
+            describe_eof(&result.content),
+        ));
+    }
+
+    *session.removed.lock().unwrap() += result.removed;
+    if result.modified {
+        session.modified.store(true, Ordering::Relaxed);
+        if config.json_output {
+            session
+                .lines_trimmed
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), result.lines_modified);
+        }
+    }
+
+    if result.modified && config.preview.is_some() {
+        let limit = config.preview.unwrap_or(0);
+        // Claim a slot first so concurrent workers never exceed the limit.
+        if session.previews.fetch_add(1, Ordering::Relaxed) < limit {
+            let label = path.display().to_string();
+            let diff = diff::unified_diff(&label, &label, content, &result.content);
+            reporter.info(diff.trim_end());
+        }
+        Ok(Outcome::Modified)
+    } else if result.modified && !config.writes_files() {
+        if config.output_patch.is_some() {
+            let (old_label, new_label) = patch_labels(path);
+            let diff = diff::unified_diff(&old_label, &new_label, content, &result.content);
+            session.patch.lock().unwrap().push_str(&diff);
+        }
+        if verbose {
+            reporter.info(format_args!(
+                "  Would modify: {:?}{}",
+                path,
+                per_file_counts(&result.removed, config)
+            ));
+        } else if config.check && !config.porcelain && !config.json_output {
+            reporter.info(format_args!("{}", path.display()));
+        }
+        if config.dry_run_report.is_some() {
+            session
+                .would_modify
+                .lock()
+                .unwrap()
+                .push((path.to_path_buf(), result.removed));
+        }
+        Ok(Outcome::Modified)
+    } else if result.modified {
+        let journal = session.journal.get();
+        if config.writes_backups() {
+            let backup = write_backup(path, &buffer, &original_metadata, journal.is_some())
+                .context("failed to write backup", path)?;
+            if verbose {
+                reporter.info(format_args!("  Backed up: {:?} -> {:?}", path, backup));
+            }
+        }
+        if let Some(journal) = journal {
+            journal
+                .plan(path, journal::content_hash(&buffer))
+                .context("failed to write journal", path)?;
+        }
+        let permissions = (!config.reset_permissions).then_some(&original_metadata);
+        let encoded;
+        let bytes = if single_byte {
+            encoded = encode_latin1(&result.content);
+            &encoded
+        } else {
+            result.content.as_bytes()
+        };
+        let clean_hash = cache_key
+            .map(|_| hash_cache_key(path, &result.content, config, eol, insert_final_newline));
+        let mut staged = false;
+        let written = match config.write_path() {
+            WritePath::InPlace { sync } => {
+                rewrite_in_place(path, bytes, sync).context("failed to rewrite file in place", path)
+            }
+            WritePath::Grouped(size) => write_temp_content(path, bytes, permissions).map(|temp| {
+                staged = true;
+                let staged = StagedWrite {
+                    temp,
+                    target: path.to_path_buf(),
+                    counts: per_file_counts(&result.removed, config),
+                    clean_hash,
+                };
+                session.stage(staged, size, config)
+            }),
+            WritePath::Atomic => write_atomically(path, bytes, permissions),
+        };
+        match (written, config.on_temp_fail) {
+            (Err(e), TempFailPolicy::Skip) if e.action() == TEMP_CREATE_FAILED => {
+                reporter.warn(format_args!(
+                    "Skipped {:?}: {} (--on-temp-fail skip)",
+                    path, e
+                ));
+                return Ok(Outcome::Skipped(SkipReason::NoTempFile));
+            }
+            (Err(e), TempFailPolicy::InPlace) if e.action() == TEMP_CREATE_FAILED => {
+                if verbose {
+                    reporter.info(format_args!("  Rewriting in place ({}): {:?}", e, path));
+                }
+                rewrite_in_place(path, bytes, true)
+                    .context("failed to rewrite file in place", path)?;
+            }
+            (written, _) => written?,
+        }
+        if staged {
+            // The rest happens once the group is committed.
+            return Ok(Outcome::Modified);
+        }
+        if config.preserve_mtime {
+            if let Err(e) = restore_mtime(path, &original_metadata) {
+                reporter.warn(format_args!(
+                    "Could not preserve the modification time of {:?}: {}",
+                    path, e
+                ));
+            }
+        }
+        if let Some(journal) = journal {
+            journal
+                .done(path)
+                .context("failed to write journal", path)?;
+        }
+
+        if !config.porcelain && !config.json_output && !config.quiet {
+            reporter.info(format_args!(
+                "  Processed: {:?}{}",
+                path,
+                per_file_counts(&result.removed, config)
+            ));
+        }
+        if let Some(key) = clean_hash {
+            session.clean_hashes.lock().unwrap().insert(key);
+        }
+
+        if !config.on_modify_exec.is_empty() {
+            if let Err(e) = run_on_modify(&config.on_modify_exec, path) {
+                session.hook_failures.fetch_add(1, Ordering::Relaxed);
+                reporter.warn(format_args!(
+                    "--on-modify-exec failed for {:?}: {}",
+                    path, e
+                ));
+            }
+        }
+        Ok(Outcome::Modified)
+    } else {
+        if let Some(key) = cache_key {
+            session.clean_hashes.lock().unwrap().insert(key);
+        }
+        if verbose {
+            reporter.info(format_args!("  Unchanged: {:?}", path));
+        }
+        Ok(Outcome::Unchanged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static TEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn create_test_dir() -> PathBuf {
+        let counter = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!(
+            "rtrim_test_{}_{}_{}",
+            process::id(),
+            counter,
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cleanup_test_dir(dir: &Path) {
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    fn verbose_config() -> Config {
+        Config {
+            verbose: true,
+            ..Config::default()
+        }
+    }
+
+    // ==================== Argument Parsing Tests ====================
+
+    #[test]
+    fn test_parse_config_help_long() {
+        let args = vec!["rtrim".to_string(), "--help".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::Help);
+    }
+
+    #[test]
+    fn test_parse_config_help_short() {
+        let args = vec!["rtrim".to_string(), "-h".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::Help);
+    }
+
+    #[test]
+    fn test_parse_config_file_mode() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--file".to_string(),
+            "test.txt".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::File);
+        assert_eq!(config.path, PathBuf::from("test.txt"));
+    }
+
+    #[test]
+    fn test_parse_config_folder_mode() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--folder".to_string(),
+            "./src".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::Folder);
+        assert_eq!(config.path, PathBuf::from("./src"));
+    }
+
+    #[test]
+    fn test_parse_config_no_args() {
+        let args = vec!["rtrim".to_string()];
+        let result = parse_config(&args);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_config_invalid_flag() {
+        let args = vec!["rtrim".to_string(), "--invalid".to_string()];
+        let result = parse_config(&args);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Invalid flag"));
+    }
+
+    #[test]
+    fn test_parse_config_multiple_paths() {
+        let args = |list: &[&str]| -> Vec<String> {
+            std::iter::once("rtrim")
+                .chain(list.iter().copied())
+                .map(String::from)
+                .collect()
+        };
+        let paths = |list: &[&str]| -> Vec<PathBuf> { list.iter().map(PathBuf::from).collect() };
+
+        let config = parse_config(&args(&["a.rs", "src", "--verbose"])).unwrap();
+        assert_eq!(config.mode, Mode::Paths);
+        assert_eq!(config.paths, paths(&["a.rs", "src"]));
+        assert!(config.verbose);
+
+        let config = parse_config(&args(&["docs/notes.md"])).unwrap();
+        assert_eq!(config.mode, Mode::Paths);
+        assert_eq!(config.paths, paths(&["docs/notes.md"]));
+
+        let config = parse_config(&args(&["--file", "a.rs", "--file", "b.rs"])).unwrap();
+        assert_eq!(config.mode, Mode::Paths);
+        assert_eq!(config.paths, paths(&["a.rs", "b.rs"]));
+
+        let config = parse_config(&args(&["--folder", "src", "docs"])).unwrap();
+        assert_eq!(config.mode, Mode::Paths);
+        assert_eq!(config.paths, paths(&["src", "docs"]));
+
+        // A single --file stays a plain file run.
+        let config = parse_config(&args(&["--file", "a.rs"])).unwrap();
+        assert_eq!(config.mode, Mode::File);
+        assert!(config.paths.is_empty());
+
+        assert!(parse_config(&args(&["--connect", "s", "--file", "a.rs", "b.rs"])).is_err());
+        assert!(parse_config(&args(&["a.rs", "http://example.com/b.rs"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_config_file_missing_path() {
+        let args = vec!["rtrim".to_string(), "--file".to_string()];
+        let result = parse_config(&args);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_config_folder_missing_path() {
+        let args = vec!["rtrim".to_string(), "--folder".to_string()];
+        let result = parse_config(&args);
+        assert!(result.is_err());
+    }
+
+    // ==================== Trim Logic Tests ====================
+
+    #[test]
+    fn test_trim_trailing_spaces() {
+        let input = "hello world   \n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "hello world\n");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_trailing_tabs() {
+        let input = "hello world\t\t\n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "hello world\n");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_mixed_whitespace() {
+        let input = "hello world \t \t\n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "hello world\n");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_multiple_lines() {
+        let input = "line1   \nline2\t\nline3 \t \n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "line1\nline2\nline3\n");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_no_changes_needed() {
+        let input = "hello world\nno trailing\n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "hello world\nno trailing\n");
+        assert!(!result.modified);
+    }
+
+    #[test]
+    fn test_trim_preserves_no_final_newline() {
+        let input = "no newline at end   ";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "no newline at end");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_last_line_without_newline_combinations() {
+        // (input, trimmed) pairs for what comes before the last line.
+        let prefixes = [
+            ("", ""),
+            ("a\n", "a\n"),
+            ("a  \n", "a\n"),
+            ("\n", "\n"),
+            ("\t\n", "\n"),
+        ];
+        let bodies = ["", "x", "  x", "x y"];
+        let whitespace = ["", " ", "\t", " \t ", "\u{a0}"];
+
+        for (prefix, trimmed_prefix) in prefixes {
+            for body in bodies {
+                for trailing in whitespace {
+                    let input = format!("{}{}{}", prefix, body, trailing);
+                    let expected = format!("{}{}", trimmed_prefix, body);
+                    let result = trim_trailing_whitespace(&input);
+                    assert_eq!(result.content, expected, "input {:?}", input);
+                    assert_eq!(result.modified, input != expected, "input {:?}", input);
+                    // Trimming never adds or drops a newline.
+                    assert_eq!(
+                        result.content.matches('\n').count(),
+                        input.matches('\n').count()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_trim_last_line_without_newline_counts_whitespace() {
+        let result = trim_trailing_whitespace("a \nb \t");
+        assert_eq!(result.content, "a\nb");
+        assert_eq!(result.removed.lines, 2);
+        assert_eq!(result.removed.spaces, 2);
+        assert_eq!(result.removed.tabs, 1);
+    }
+
+    #[test]
+    fn test_trim_result_counts() {
+        // (input, lines_modified, chars_removed, trailing_newline_changed)
+        let cases = [
+            ("clean\n", 0, 0, false),
+            ("a  \nb\nc\t\n", 2, 3, false),
+            ("a \u{a0}\n \n", 2, 3, false),
+            ("last  ", 1, 2, false),
+            ("a\r\nb  \r\n", 1, 2, true),
+            ("", 0, 0, false),
+        ];
+        for (input, lines, chars, newline) in cases {
+            let result = trim_trailing_whitespace(input);
+            assert_eq!(result.lines_modified, lines, "input {:?}", input);
+            assert_eq!(result.chars_removed, chars, "input {:?}", input);
+            assert_eq!(
+                result.trailing_newline_changed, newline,
+                "input {:?}",
+                input
+            );
+            assert_eq!(result.lines_modified, result.removed.lines);
+            assert_eq!(result.chars_removed, result.removed.total());
+        }
+
+        let restricted = restrict_to_lines(
+            "a  \nb  \n",
+            trim_trailing_whitespace("a  \nb  \n"),
+            &[false, true],
+        );
+        assert_eq!(restricted.content, "a  \nb\n");
+        assert_eq!(restricted.lines_modified, 1);
+        assert_eq!(restricted.chars_removed, 2);
+        assert!(!restricted.trailing_newline_changed);
+    }
+
+    #[test]
+    fn test_trim_last_line_without_newline_kept_or_stripped_both() {
+        let kept = trim_lines_with("a  \nb  ", Strip::Trailing, |index, _| index == 0);
+        assert_eq!(kept.content, "a\nb  ");
+
+        let both = trim_lines_with("  a  \n  b  ", Strip::Both, |_, _| true);
+        assert_eq!(both.content, "a\nb");
+
+        let whitespace_only = trim_lines_with(" \t ", Strip::Both, |_, _| true);
+        assert_eq!(whitespace_only.content, "");
+        assert!(whitespace_only.modified);
+    }
+
+    #[test]
+    fn test_trim_preserves_final_newline() {
+        let input = "has newline   \n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "has newline\n");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_empty_file() {
+        let input = "";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "");
+        assert!(!result.modified);
+    }
+
+    #[test]
+    fn test_trim_only_newline() {
+        let input = "\n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "\n");
+        assert!(!result.modified);
+    }
+
+    #[test]
+    fn test_trim_only_whitespace_line() {
+        let input = "   \n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "\n");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_multiple_empty_lines() {
+        let input = "text\n\n\n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "text\n\n\n");
+        assert!(!result.modified);
+    }
+
+    #[test]
+    fn test_trim_preserves_leading_whitespace() {
+        let input = "    indented line   \n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "    indented line\n");
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_trim_carriage_return() {
+        // Note: Rust's lines() treats \r\n as a line separator,
+        // so \r is not part of the line content to be trimmed.
+        // This test verifies that CRLF files are handled correctly.
+        let input = "windows line\r\n";
+        let result = trim_trailing_whitespace(input);
+        // lines() already strips \r from line endings
+        assert_eq!(result.content, "windows line\n");
+        // No trailing whitespace in the line itself, so not modified
+        assert!(!result.modified);
+    }
+
+    #[test]
+    fn test_trim_crlf_with_trailing_spaces() {
+        // CRLF with actual trailing spaces before \r\n
+        let input = "windows line   \r\n";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "windows line\n");
+        assert!(result.modified);
+    }
+
+    // ==================== Directory Ignore Tests ====================
+
+    #[test]
+    fn test_should_ignore_git() {
+        assert!(should_ignore_dir(Path::new("/project/.git")));
+    }
+
+    #[test]
+    fn test_should_ignore_node_modules() {
+        assert!(should_ignore_dir(Path::new("/project/node_modules")));
+    }
+
+    #[test]
+    fn test_should_ignore_target() {
+        assert!(should_ignore_dir(Path::new("/project/target")));
+    }
+
+    #[test]
+    fn test_should_ignore_idea() {
+        assert!(should_ignore_dir(Path::new("/project/.idea")));
+    }
+
+    #[test]
+    fn test_should_ignore_vscode() {
+        assert!(should_ignore_dir(Path::new("/project/.vscode")));
+    }
+
+    #[test]
+    fn test_should_ignore_hidden_dirs() {
+        assert!(should_ignore_dir(Path::new("/project/.hidden")));
+        assert!(should_ignore_dir(Path::new("/project/.config")));
+    }
+
+    #[test]
+    fn test_should_not_ignore_src() {
+        assert!(!should_ignore_dir(Path::new("/project/src")));
+    }
+
+    #[test]
+    fn test_should_not_ignore_regular_dir() {
+        assert!(!should_ignore_dir(Path::new("/project/lib")));
+    }
+
+    // ==================== Special Entry Tests ====================
+
+    #[test]
+    fn test_special_entries_are_classified_and_counted() {
+        let test_dir = create_test_dir();
+        let fifo = test_dir.join("pipe");
+        let made = Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .is_ok_and(|status| status.success());
+        if !made {
+            cleanup_test_dir(&test_dir);
+            return;
+        }
+        let socket = test_dir.join("sock");
+        let _listener = UnixListener::bind(&socket).unwrap();
+        let file_type = |path: &Path| fs::symlink_metadata(path).unwrap().file_type();
+        assert_eq!(special_kind(&file_type(&fifo)), "fifo");
+        assert_eq!(special_kind(&file_type(&socket)), "socket");
+        assert_eq!(
+            special_kind(&file_type(Path::new("/dev/null"))),
+            "character device"
+        );
+
+        fs::write(test_dir.join("plain.txt"), "plain   \n").unwrap();
+        let session = Session::default();
+        process_folder(&test_dir, &Config::default(), &session).unwrap();
+        assert_eq!(session.special_entries.load(Ordering::Relaxed), 2);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Temp Path Generation Tests ====================
+
+    #[test]
+    fn test_generate_temp_path_format() {
+        let original = Path::new("/tmp/test.txt");
+        let temp = generate_temp_path(original);
+
+        let temp_name = temp.file_name().unwrap().to_str().unwrap();
+        assert!(temp_name.starts_with(".test.txt."));
+        assert!(temp_name.ends_with(".tmp"));
+        assert_eq!(temp.parent().unwrap(), Path::new("/tmp"));
+    }
+
+    #[test]
+    fn test_generate_temp_path_uniqueness() {
+        let original = Path::new("/tmp/test.txt");
+        let temp1 = generate_temp_path(original);
+        std::thread::sleep(std::time::Duration::from_nanos(1));
+        let temp2 = generate_temp_path(original);
+
+        // Should generate different names due to timestamp
+        assert_ne!(temp1, temp2);
+    }
+
+    #[test]
+    fn test_generate_temp_path_has_random_field() {
+        let original = Path::new("/tmp/test.txt");
+        let nonce = |temp: &Path| {
+            let name = temp.file_name().unwrap().to_str().unwrap();
+            let inner = name.strip_suffix(".tmp").unwrap();
+            inner.rsplit_once('.').unwrap().1.to_string()
+        };
+        let nonces: HashSet<String> = (0..100)
+            .map(|_| nonce(&generate_temp_path(original)))
+            .collect();
+        assert_eq!(nonces.len(), 100);
+        assert!(nonces.iter().all(|n| n.len() == 16));
+    }
+
+    #[test]
+    fn test_generate_temp_path_unique_without_delay() {
+        let original = Path::new("/tmp/test.txt");
+        let names: HashSet<PathBuf> = (0..1000).map(|_| generate_temp_path(original)).collect();
+        assert_eq!(names.len(), 1000);
+    }
+
+    #[test]
+    fn test_is_temp_file_name() {
+        let temp = generate_temp_path(Path::new("/tmp/a.b.txt"));
+        assert!(is_temp_file_name(temp.file_name().unwrap()));
+        assert!(!is_temp_file_name(OsStr::new(".cache.tmp")));
+        assert!(!is_temp_file_name(OsStr::new("a.txt.1.2.3.tmp")));
+        assert!(!is_temp_file_name(OsStr::new(".1.2.3.tmp")));
+        assert!(!is_temp_file_name(OsStr::new(".a.1.x.3.tmp")));
+        assert!(is_temp_file_name(OsStr::new(".a.txt.1.2.3.tmp")));
+        assert!(is_temp_file_name(OsStr::new(
+            ".a.txt.1.2.3.00ff00ff00ff00ff.tmp"
+        )));
+        assert!(!is_temp_file_name(OsStr::new(
+            ".a.txt.1.2.00ff00ff00ff00ff.tmp"
+        )));
+    }
+
+    #[test]
+    fn test_create_temp_file_is_exclusive() {
+        let test_dir = create_test_dir();
+        let original = test_dir.join("target.txt");
+
+        let (first, _) = create_temp_file(&original).unwrap();
+        let (second, _) = create_temp_file(&original).unwrap();
+        assert_ne!(first, second);
+        assert!(first.exists() && second.exists());
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_failed_write_removes_temp_file() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("target.txt");
+        fs::write(&test_file, "original   \n").unwrap();
+
+        // Stands in for a disk filling up after part of the content is written.
+        let err = write_temp_file(&test_file, None, |file| {
+            file.write_all(b"partial").unwrap();
+            Err(io::Error::new(
+                io::ErrorKind::StorageFull,
+                "no space left on device",
+            ))
+            .context("failed to write temporary file", &test_file)
+        })
+        .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "original   \n");
+        let entries: Vec<_> = fs::read_dir(&test_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![OsStr::new("target.txt")]);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_failed_rename_removes_temp_file() {
+        let test_dir = create_test_dir();
+        // Renaming a file over a non-empty directory fails.
+        let target = test_dir.join("dir");
+        fs::create_dir(&target).unwrap();
+        fs::write(target.join("inner.txt"), "x").unwrap();
+
+        assert!(write_atomically(&target, b"content\n", None).is_err());
+        let entries: Vec<_> = fs::read_dir(&test_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![OsStr::new("dir")]);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_concurrent_runs_on_same_file_do_not_corrupt() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("shared.txt");
+        let dirty: String = (0..200).map(|i| format!("line {}   \n", i)).collect();
+        let clean: String = (0..200).map(|i| format!("line {}\n", i)).collect();
+
+        for _ in 0..10 {
+            fs::write(&test_file, &dirty).unwrap();
+            // Each thread stands in for a separate rtrim invocation.
+            thread::scope(|scope| {
+                for _ in 0..4 {
+                    scope.spawn(|| {
+                        process_file(&test_file, &Config::default(), &Session::default()).unwrap()
+                    });
+                }
+            });
+            assert_eq!(fs::read_to_string(&test_file).unwrap(), clean);
+        }
+
+        let leftovers: Vec<_> = fs::read_dir(&test_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .filter(|name| name != "shared.txt")
+            .collect();
+        assert!(leftovers.is_empty(), "temp files left: {:?}", leftovers);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_generate_temp_path_hidden() {
+        let original = Path::new("/tmp/test.txt");
+        let temp = generate_temp_path(original);
+
+        let temp_name = temp.file_name().unwrap().to_str().unwrap();
+        assert!(temp_name.starts_with('.'), "Temp file should be hidden");
+    }
+
+    // ==================== Integration Tests ====================
+
+    #[test]
+    fn test_process_file_removes_trailing_spaces() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("test.txt");
+
+        fs::write(&test_file, "hello   \nworld\t\n").unwrap();
+        process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+
+        let content = fs::read_to_string(&test_file).unwrap();
+        assert_eq!(content, "hello\nworld\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_file_preserves_permissions() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("test.txt");
+
+        fs::write(&test_file, "hello   \n").unwrap();
+        fs::set_permissions(&test_file, Permissions::from_mode(0o754)).unwrap();
+
+        process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+
+        let metadata = fs::metadata(&test_file).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o754);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_preserve_permissions_off_uses_default_mode() {
+        let test_dir = create_test_dir();
+        let kept = test_dir.join("kept.sh");
+        let reset = test_dir.join("reset.sh");
+        for file in [&kept, &reset] {
+            fs::write(file, "echo hi   \n").unwrap();
+            fs::set_permissions(file, Permissions::from_mode(0o700)).unwrap();
+        }
+
+        let config = Config {
+            reset_permissions: true,
+            ..Config::default()
+        };
+        process_file(&kept, &Config::default(), &Session::default()).unwrap();
+        process_file(&reset, &config, &Session::default()).unwrap();
+
+        // Newly created files never get execute bits from the default 0666 mode.
+        let kept_mode = fs::metadata(&kept).unwrap().permissions().mode() & 0o777;
+        let reset_mode = fs::metadata(&reset).unwrap().permissions().mode() & 0o777;
+        assert_eq!(kept_mode, 0o700);
+        assert_eq!(reset_mode & 0o111, 0);
+        assert_ne!(reset_mode, kept_mode);
+        assert_eq!(fs::read_to_string(&reset).unwrap(), "echo hi\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_preserve_permissions() {
+        let parse = |flags: &[&str]| {
+            let mut args = vec!["rtrim".to_string()];
+            args.extend(flags.iter().map(|flag| flag.to_string()));
+            args.extend(["--folder".to_string(), ".".to_string()]);
+            parse_config(&args)
+        };
+        assert!(!parse(&[]).unwrap().reset_permissions);
+        assert!(
+            !parse(&["--preserve-permissions", "on"])
+                .unwrap()
+                .reset_permissions
+        );
+        assert!(
+            parse(&["--preserve-permissions", "off"])
+                .unwrap()
+                .reset_permissions
+        );
+        assert!(
+            parse(&["--preserve-permissions=off"])
+                .unwrap()
+                .reset_permissions
+        );
+        assert!(parse(&["--preserve-permissions=no"]).is_err());
+    }
+
+    #[test]
+    fn test_process_file_ignores_binary() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("binary.bin");
+
+        // Write invalid UTF-8
+        fs::write(&test_file, [0xFF, 0xFE, 0x00, 0x01]).unwrap();
+        let original_content = fs::read(&test_file).unwrap();
+
+        process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+
+        let new_content = fs::read(&test_file).unwrap();
+        assert_eq!(original_content, new_content);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_file_no_changes_when_clean() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("clean.txt");
+
+        let original = "hello\nworld\n";
+        fs::write(&test_file, original).unwrap();
+        let mtime_before = fs::metadata(&test_file).unwrap().modified().unwrap();
+
+        // Small delay to ensure mtime would change if file is rewritten
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+
+        let mtime_after = fs::metadata(&test_file).unwrap().modified().unwrap();
+        // File should not have been modified
+        assert_eq!(mtime_before, mtime_after);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_preserve_mtime_keeps_modification_time() {
+        let test_dir = create_test_dir();
+        let day_ago = SystemTime::now() - std::time::Duration::from_secs(24 * 60 * 60);
+        let args = vec![
+            "rtrim".to_string(),
+            "--preserve-mtime".to_string(),
+            ".".to_string(),
+        ];
+        assert!(parse_config(&args).unwrap().preserve_mtime);
+
+        for keep_inode in [false, true] {
+            let test_file = test_dir.join(format!("dirty_{}.txt", keep_inode));
+            fs::write(&test_file, "dirty   \n").unwrap();
+            File::options()
+                .write(true)
+                .open(&test_file)
+                .unwrap()
+                .set_modified(day_ago)
+                .unwrap();
+
+            let config = Config {
+                preserve_mtime: true,
+                keep_inode,
+                ..Config::default()
+            };
+            assert_eq!(
+                process_file(&test_file, &config, &Session::default()).unwrap(),
+                Outcome::Modified
+            );
+            assert_eq!(fs::read_to_string(&test_file).unwrap(), "dirty\n");
+            assert_eq!(
+                fs::metadata(&test_file).unwrap().modified().unwrap(),
+                day_ago
+            );
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_file_ignores_symlink() {
+        let test_dir = create_test_dir();
+        let original_file = test_dir.join("original.txt");
+        let symlink_file = test_dir.join("symlink.txt");
+
+        fs::write(&original_file, "hello   \n").unwrap();
+        std::os::unix::fs::symlink(&original_file, &symlink_file).unwrap();
+
+        process_file(&symlink_file, &Config::default(), &Session::default()).unwrap();
+
+        // Original file should not have been modified
+        let content = fs::read_to_string(&original_file).unwrap();
+        assert_eq!(content, "hello   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_follow_symlinks_processes_targets_inside_root() {
+        let test_dir = create_test_dir();
+        let outside_dir = create_test_dir();
+        let src = test_dir.join("src");
+        let lib = test_dir.join("lib");
+        fs::create_dir_all(&src).unwrap();
+        fs::create_dir_all(&lib).unwrap();
+        let shared = lib.join("shared.txt");
+        let outside = outside_dir.join("outside.txt");
+        fs::write(&shared, "shared   \n").unwrap();
+        fs::write(&outside, "outside   \n").unwrap();
+        std::os::unix::fs::symlink(&lib, src.join("lib")).unwrap();
+        std::os::unix::fs::symlink(&test_dir, src.join("loop")).unwrap();
+        std::os::unix::fs::symlink(&outside, src.join("outside.txt")).unwrap();
+        std::os::unix::fs::symlink(src.join("missing"), src.join("broken.txt")).unwrap();
+
+        let config = Config {
+            follow_symlinks: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_folder(&src, &config, &session).unwrap();
+        // Only the walked folder counts as inside: lib/ is outside src/.
+        assert_eq!(fs::read_to_string(&shared).unwrap(), "shared   \n");
+
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+        assert_eq!(fs::read_to_string(&shared).unwrap(), "shared\n");
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "outside   \n");
+        assert!(fs::symlink_metadata(src.join("lib")).unwrap().is_symlink());
+        // outside.txt and broken.txt are refused; the loop ends at test_dir.
+        assert_eq!(session.stats().symlinks, 2);
+
+        let config = Config {
+            follow_symlinks: true,
+            allow_symlinks_outside_root: true,
+            ..Config::default()
+        };
+        process_folder(&src, &config, &Session::default()).unwrap();
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "outside\n");
+        assert!(fs::symlink_metadata(src.join("outside.txt"))
+            .unwrap()
+            .is_symlink());
+
+        cleanup_test_dir(&test_dir);
+        cleanup_test_dir(&outside_dir);
+    }
+
+    #[test]
+    fn test_process_file_follows_symlink_in_its_directory() {
+        let test_dir = create_test_dir();
+        let original_file = test_dir.join("original.txt");
+        let symlink_file = test_dir.join("symlink.txt");
+        fs::write(&original_file, "hello   \n").unwrap();
+        std::os::unix::fs::symlink(&original_file, &symlink_file).unwrap();
+
+        let config = Config {
+            follow_symlinks: true,
+            ..Config::default()
+        };
+        let outcome = process_file(&symlink_file, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(fs::read_to_string(&original_file).unwrap(), "hello\n");
+        assert!(fs::symlink_metadata(&symlink_file).unwrap().is_symlink());
+
+        let nested = test_dir.join("nested");
+        fs::create_dir(&nested).unwrap();
+        fs::write(&original_file, "again   \n").unwrap();
+        std::os::unix::fs::symlink(&original_file, nested.join("up.txt")).unwrap();
+        let outcome = process_file(&nested.join("up.txt"), &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::Symlink));
+        assert_eq!(fs::read_to_string(&original_file).unwrap(), "again   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_folder_recursive() {
+        let test_dir = create_test_dir();
+        let sub_dir = test_dir.join("subdir");
+        fs::create_dir(&sub_dir).unwrap();
+
+        let file1 = test_dir.join("file1.txt");
+        let file2 = sub_dir.join("file2.txt");
+
+        fs::write(&file1, "line1   \n").unwrap();
+        fs::write(&file2, "line2\t\n").unwrap();
+
+        process_folder(&test_dir, &Config::default(), &Session::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(&file1).unwrap(), "line1\n");
+        assert_eq!(fs::read_to_string(&file2).unwrap(), "line2\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_folder_ignores_git() {
+        let test_dir = create_test_dir();
+        let git_dir = test_dir.join(".git");
+        fs::create_dir(&git_dir).unwrap();
+
+        let git_file = git_dir.join("config");
+        fs::write(&git_file, "content   \n").unwrap();
+
+        process_folder(&test_dir, &Config::default(), &Session::default()).unwrap();
+
+        // File inside .git should not have been modified
+        let content = fs::read_to_string(&git_file).unwrap();
+        assert_eq!(content, "content   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_no_temp_file_left_on_success() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("test.txt");
+
+        fs::write(&test_file, "hello   \n").unwrap();
+        process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+
+        // Check no .tmp files are left
+        let entries: Vec<_> = fs::read_dir(&test_dir)
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .map(|ext| ext == "tmp")
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        assert!(entries.is_empty(), "No temp files should remain");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Verbose Flag Tests ====================
+
+    #[test]
+    fn test_parse_config_verbose_long() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--file".to_string(),
+            "test.txt".to_string(),
+            "--verbose".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::File);
+        assert!(config.verbose);
+    }
+
+    #[test]
+    fn test_parse_config_verbose_short() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--folder".to_string(),
+            "./src".to_string(),
+            "-v".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::Folder);
+        assert!(config.verbose);
+    }
+
+    #[test]
+    fn test_parse_config_verbose_before_mode() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--verbose".to_string(),
+            "--file".to_string(),
+            "test.txt".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::File);
+        assert!(config.verbose);
+    }
+
+    #[test]
+    fn test_parse_config_no_verbose() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--file".to_string(),
+            "test.txt".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert!(!config.verbose);
+    }
+
+    #[test]
+    fn test_parse_config_double_dash_path() {
+        // Path "-v" after "--" should be treated as literal path, not as verbose flag
+        let args = vec![
+            "rtrim".to_string(),
+            "--file".to_string(),
+            "--".to_string(),
+            "-v".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::File);
+        assert_eq!(config.path, PathBuf::from("-v"));
+        assert!(!config.verbose);
+    }
+
+    #[test]
+    fn test_parse_config_verbose_before_double_dash() {
+        // -v before "--" is recognized as verbose, "--verbose" after "--" is the path
+        let args = vec![
+            "rtrim".to_string(),
+            "-v".to_string(),
+            "--file".to_string(),
+            "--".to_string(),
+            "--verbose".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::File);
+        assert_eq!(config.path, PathBuf::from("--verbose"));
+        assert!(config.verbose);
+    }
+
+    #[test]
+    fn test_process_file_verbose_mode() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("test.txt");
+
+        fs::write(&test_file, "hello   \n").unwrap();
+        // Should not panic in verbose mode
+        process_file(&test_file, &verbose_config(), &Session::default()).unwrap();
+
+        let content = fs::read_to_string(&test_file).unwrap();
+        assert_eq!(content, "hello\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_folder_verbose_mode() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("test.txt");
+
+        fs::write(&test_file, "hello   \n").unwrap();
+        // Should not panic in verbose mode
+        process_folder(&test_dir, &verbose_config(), &Session::default()).unwrap();
+
+        let content = fs::read_to_string(&test_file).unwrap();
+        assert_eq!(content, "hello\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Warning Cap Tests ====================
+
+    #[test]
+    fn test_parse_config_max_warnings() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--max-warnings".to_string(),
+            "5".to_string(),
+            "--folder".to_string(),
+            "./src".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::Folder);
+        assert_eq!(config.path, PathBuf::from("./src"));
+        assert_eq!(config.max_warnings, Some(5));
+    }
+
+    #[test]
+    fn test_parse_config_max_warnings_invalid() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--folder".to_string(),
+            "./src".to_string(),
+            "--max-warnings".to_string(),
+            "many".to_string(),
+        ];
+        let result = parse_config(&args);
+        assert!(result.unwrap_err().contains("--max-warnings"));
+    }
+
+    #[test]
+    fn test_reporter_suppresses_warnings_beyond_cap() {
+        let reporter = Reporter::new(Some(2));
+
+        assert!(reporter.warn("first"));
+        assert!(reporter.warn("second"));
+        assert!(!reporter.warn("third"));
+        assert!(!reporter.warn("fourth"));
+
+        assert_eq!(reporter.suppressed(), 2);
+    }
+
+    #[test]
+    fn test_reporter_without_cap_prints_everything() {
+        let reporter = Reporter::default();
+        for _ in 0..10 {
+            assert!(reporter.warn("warning"));
+        }
+        assert_eq!(reporter.suppressed(), 0);
+    }
+
+    // ==================== EOF Newline Safeguard Tests ====================
+
+    #[test]
+    fn test_eof_newline_changed_detects_dropped_whitespace_line() {
+        // The last line is only whitespace and has no newline, so trimming
+        // leaves the previous line's newline as the final byte.
+        let input = "text\n   ";
+        let result = trim_trailing_whitespace(input);
+        assert_eq!(result.content, "text\n");
+        assert!(eof_newline_changed(input, &result.content));
+    }
+
+    #[test]
+    fn test_with_final_newline() {
+        assert_eq!(with_final_newline("a"), Some("a\n".to_string()));
+        assert_eq!(with_final_newline("a\n"), None);
+        assert_eq!(with_final_newline("a\n\n\n"), Some("a\n".to_string()));
+        assert_eq!(with_final_newline("a\r\n\r\n"), Some("a\n".to_string()));
+        assert_eq!(with_final_newline("a\n\nb"), Some("a\n\nb\n".to_string()));
+        assert_eq!(with_final_newline(""), None);
+        assert_eq!(with_final_newline("\n\n"), Some(String::new()));
+    }
+
+    #[test]
+    fn test_without_blank_eof() {
+        assert_eq!(without_blank_eof("text\n\n\n"), Some("text\n".to_string()));
+        assert_eq!(without_blank_eof("text\n"), None);
+        assert_eq!(without_blank_eof("text"), None);
+        assert_eq!(without_blank_eof("a\n\n\nb\n"), None);
+        assert_eq!(without_blank_eof("\n\n"), Some(String::new()));
+        assert_eq!(without_blank_eof(""), None);
+    }
+
+    #[test]
+    fn test_squeeze_blank_eof_after_trimming() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--squeeze-blank-eof".to_string(),
+            ".".to_string(),
+        ];
+        assert!(parse_config(&args).unwrap().squeeze_blank_eof);
+
+        let config = Config {
+            squeeze_blank_eof: true,
+            ..Config::default()
+        };
+        let trim = |input: &str| trim_for_config(Path::new("notes.txt"), input, &config).unwrap();
+
+        let result = trim("text\n\n\n");
+        assert_eq!(result.content, "text\n");
+        assert!(result.modified);
+        // Blank lines holding whitespace are emptied by the trimming pass first.
+        assert_eq!(trim("a  \n\n  \n\t\n").content, "a\n");
+        assert_eq!(trim(" \n\n").content, "");
+        let interior = trim("a\n\n\nb\n");
+        assert_eq!(interior.content, "a\n\n\nb\n");
+        assert!(!interior.modified);
+        assert!(!trim("a").modified);
+    }
+
+    #[test]
+    fn test_final_newline_after_trimming() {
+        let config = Config {
+            final_newline: true,
+            ..Config::default()
+        };
+        let trim = |input: &str, config: &Config| {
+            trim_for_config(Path::new("notes.txt"), input, config).unwrap()
+        };
+
+        let result = trim("a  \nb  ", &config);
+        assert_eq!(result.content, "a\nb\n");
+        assert!(result.modified);
+        assert_eq!(trim("a\n  \n\t\n", &config).content, "a\n");
+        let empty = trim("", &config);
+        assert_eq!(empty.content, "");
+        assert!(!empty.modified);
+        assert!(!trim("a\n", &config).modified);
+
+        // Without the flag the end of the file is kept as it is.
+        assert_eq!(trim("a  \nb  ", &Config::default()).content, "a\nb");
+        assert_eq!(trim("a\n\n\n", &Config::default()).content, "a\n\n\n");
+    }
+
+    #[test]
+    fn test_eof_newline_unchanged_for_regular_files() {
+        for input in ["text   \n", "text   ", "", "a\nb\t"] {
+            let result = trim_trailing_whitespace(input);
+            assert!(!eof_newline_changed(input, &result.content), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_process_file_warns_on_eof_newline_change() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("test.txt");
+        fs::write(&test_file, "text\n \t").unwrap();
+
+        let config = Config {
+            warn_eof_newline: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_file(&test_file, &config, &session).unwrap();
+
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "text\n");
+        assert_eq!(session.reporter.warning_count(), 1);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_file_no_eof_warning_without_flag() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("test.txt");
+        fs::write(&test_file, "text\n   ").unwrap();
+
+        let session = Session::default();
+        process_file(&test_file, &Config::default(), &session).unwrap();
+
+        assert_eq!(session.reporter.warning_count(), 0);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Line Count Threshold Tests ====================
+
+    #[test]
+    fn test_count_lines() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"one"), 1);
+        assert_eq!(count_lines(b"one\n"), 1);
+        assert_eq!(count_lines(b"one\ntwo"), 2);
+        assert_eq!(count_lines(b"\n\n\n"), 3);
+    }
+
+    #[test]
+    fn test_parse_config_max_lines() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--folder".to_string(),
+            ".".to_string(),
+            "--max-lines".to_string(),
+            "1000".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.max_lines, Some(1000));
+    }
+
+    #[test]
+    fn test_process_file_skips_file_over_max_lines() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("big.sql");
+        fs::write(&test_file, "a \nb \nc \nd \n").unwrap();
+
+        let config = Config {
+            max_lines: Some(3),
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::TooManyLines));
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "a \nb \nc \nd \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_file_trims_file_at_max_lines() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("small.sql");
+        fs::write(&test_file, "a \nb \nc \n").unwrap();
+
+        let config = Config {
+            max_lines: Some(3),
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "a\nb\nc\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Conflict Marker Tests ====================
+
+    #[test]
+    fn test_has_conflict_markers() {
+        assert!(has_conflict_markers(
+            "a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> topic\n"
+        ));
+        assert!(has_conflict_markers("<<<<<<<\n>>>>>>>\n"));
+        assert!(!has_conflict_markers("Title\n=======\n"));
+        assert!(!has_conflict_markers(">>>>>>> topic\n<<<<<<< HEAD\n"));
+        assert!(!has_conflict_markers("<<<<<<<< not a marker\n>>>>>>>>\n"));
+    }
+
+    #[test]
+    fn test_process_file_skips_conflicted_file() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("merge.rs");
+        let conflicted =
+            "fn a() {   \n<<<<<<< HEAD\n    one();  \n=======\n    two();\n>>>>>>> topic\n}\n";
+        fs::write(&test_file, conflicted).unwrap();
+
+        let outcome = process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::Conflicted));
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), conflicted);
+
+        let config = Config {
+            allow_conflicts: true,
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Modified);
+        assert!(fs::read_to_string(&test_file)
+            .unwrap()
+            .starts_with("fn a() {\n<<<<<<< HEAD\n    one();\n"));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Output Channel Tests ====================
+
+    /// In-memory sink that can be shared with the printer thread.
+    #[derive(Clone, Default)]
+    struct SharedBuf(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuf {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn capturing_reporter(max_warnings: Option<usize>) -> (Reporter, SharedBuf, SharedBuf) {
+        let out = SharedBuf::default();
+        let err = SharedBuf::default();
+        let reporter =
+            Reporter::with_sinks(max_warnings, Box::new(out.clone()), Box::new(err.clone()));
+        (reporter, out, err)
+    }
+
+    /// Runs `config` with its output discarded and returns the exit status.
+    fn run_silently(config: Config) -> i32 {
+        run(&config, Box::new(io::sink()), Box::new(io::sink()))
+    }
+
+    #[test]
+    fn test_reporter_parallel_lines_are_not_interleaved() {
+        let (reporter, out, _err) = capturing_reporter(None);
+        let threads = 8;
+        let lines_per_thread = 200;
+
+        thread::scope(|scope| {
+            for t in 0..threads {
+                let reporter = &reporter;
+                scope.spawn(move || {
+                    for i in 0..lines_per_thread {
+                        reporter.info(format_args!("  Processed: \"worker{}/file{}.txt\"", t, i));
+                    }
+                });
+            }
+        });
+        reporter.finish();
+
+        let output = out.contents();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), threads * lines_per_thread);
+
+        let mut next_index = vec![0; threads];
+        for line in lines {
+            let rest = line
+                .strip_prefix("  Processed: \"worker")
+                .and_then(|r| r.strip_suffix(".txt\""))
+                .unwrap_or_else(|| panic!("garbled line: {:?}", line));
+            let (worker, file) = rest.split_once("/file").unwrap();
+            let worker: usize = worker.parse().unwrap();
+            let file: usize = file.parse().unwrap();
+            // Each worker's lines arrive whole and in the order they were sent.
+            assert_eq!(file, next_index[worker]);
+            next_index[worker] += 1;
+        }
+    }
+
+    #[test]
+    fn test_reporter_routes_streams_and_suppression_note() {
+        let (reporter, out, err) = capturing_reporter(Some(1));
+        reporter.info("hello");
+        reporter.warn("first");
+        reporter.warn("second");
+        reporter.warn("third");
+        reporter.finish();
+
+        assert_eq!(out.contents(), "hello\n");
+        assert_eq!(
+            err.contents(),
+            "Warning: first\n(2 more warnings suppressed)\n"
+        );
+    }
+
+    #[test]
+    fn test_reporter_writes_raw_bytes_in_order() {
+        let (reporter, out, _err) = capturing_reporter(None);
+        reporter.raw(b"no newline  \r\n\tend".to_vec());
+        reporter.info("after");
+        reporter.finish();
+
+        assert_eq!(out.contents(), "no newline  \r\n\tendafter\n");
+    }
+
+    // ==================== Comments-Only Mode Tests ====================
+
+    #[test]
+    fn test_parse_config_comments_only() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--trim-doc-comments-only".to_string(),
+            "--folder".to_string(),
+            "./src".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert!(config.comments_only);
+    }
+
+    #[test]
+    fn test_comments_only_trims_rust_comments() {
+        let config = Config {
+            comments_only: true,
+            ..Config::default()
+        };
+        let input = "fn main() {   \n    // line comment   \n    /* block   \n       more   \n    */   \n    let s = \"x\";   \n}\n";
+        let result = trim_for_config(Path::new("main.rs"), input, &config).unwrap();
+        assert_eq!(
+            result.content,
+            "fn main() {   \n    // line comment\n    /* block\n       more\n    */   \n    let s = \"x\";   \n}\n"
+        );
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_comments_only_handles_escaped_newline_in_triple_quotes() {
+        let config = Config {
+            comments_only: true,
+            ..Config::default()
+        };
+        let input = "s = \"\"\"abc\\\ndef   \n\"\"\"\n# c   \n";
+        let result = trim_for_config(Path::new("d.py"), input, &config).unwrap();
+        assert_eq!(result.content, "s = \"\"\"abc\\\ndef   \n\"\"\"\n# c\n");
+    }
+
+    #[test]
+    fn test_comments_only_skips_unsupported_language() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("notes.md");
+        fs::write(&test_file, "text   \n").unwrap();
+
+        let config = Config {
+            comments_only: true,
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::UnsupportedLanguage));
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "text   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== String Literal Tests ====================
+
+    #[test]
+    fn test_parse_config_preserve_strings() {
+        let args: Vec<String> = [
+            "rtrim",
+            "--preserve-trailing-whitespace-in-strings",
+            "--folder",
+            "./src",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert!(parse_config(&args).unwrap().preserve_strings);
+    }
+
+    #[test]
+    fn test_preserve_strings_keeps_rust_raw_string() {
+        let config = Config {
+            preserve_strings: true,
+            ..Config::default()
+        };
+        let input = "let sql = r#\"SELECT *   \n  FROM t  \n\"#;   \nlet s = \"a  \nb\";  \n";
+        let result = trim_for_config(Path::new("query.rs"), input, &config).unwrap();
+        assert_eq!(
+            result.content,
+            "let sql = r#\"SELECT *   \n  FROM t  \n\"#;\nlet s = \"a  \nb\";\n"
+        );
+        assert!(result.modified);
+    }
+
+    #[test]
+    fn test_preserve_strings_keeps_python_triple_quoted_string() {
+        let config = Config {
+            preserve_strings: true,
+            ..Config::default()
+        };
+        let input = "def usage():   \n    return \"\"\"\n    rtrim   \n    \"\"\"  \n";
+        let result = trim_for_config(Path::new("cli.py"), input, &config).unwrap();
+        assert_eq!(
+            result.content,
+            "def usage():\n    return \"\"\"\n    rtrim   \n    \"\"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_preserve_strings_handles_escaped_newline_in_triple_quotes() {
+        let config = Config {
+            preserve_strings: true,
+            ..Config::default()
+        };
+        let input = "s = \"\"\"abc\\\ndef   \n\"\"\"   \n# c   \n";
+        let result = trim_for_config(Path::new("d.py"), input, &config).unwrap();
+        assert_eq!(result.content, "s = \"\"\"abc\\\ndef   \n\"\"\"\n# c\n");
+    }
+
+    #[test]
+    fn test_preserve_strings_trims_unknown_languages_normally() {
+        let config = Config {
+            preserve_strings: true,
+            ..Config::default()
+        };
+        let result = trim_for_config(Path::new("notes.md"), "\"open   \n", &config).unwrap();
+        assert_eq!(result.content, "\"open\n");
+    }
+
+    // ==================== Dedupe Tests ====================
+
+    #[test]
+    fn test_dedupe_overlapping_inputs() {
+        let test_dir = create_test_dir();
+        let sub_dir = test_dir.join("a");
+        fs::create_dir(&sub_dir).unwrap();
+        let test_file = sub_dir.join("b.txt");
+        fs::write(&test_file, "hello   \n").unwrap();
+
+        let config = Config {
+            dedupe: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+
+        // "--file a/b.txt" followed by "--folder a" (spelled differently).
+        let first = process_file(&test_file, &config, &session).unwrap();
+        let again = process_file(&sub_dir.join(".").join("b.txt"), &config, &session).unwrap();
+        process_folder(&sub_dir, &config, &session).unwrap();
+
+        assert_eq!(first, Outcome::Modified);
+        assert_eq!(again, Outcome::Skipped(SkipReason::Duplicate));
+        assert_eq!(session.seen.lock().unwrap().len(), 1);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_no_dedupe_by_default() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("b.txt");
+        fs::write(&test_file, "hello\n").unwrap();
+
+        let session = Session::default();
+        process_file(&test_file, &Config::default(), &session).unwrap();
+        let again = process_file(&test_file, &Config::default(), &session).unwrap();
+
+        assert_eq!(again, Outcome::Unchanged);
+        assert!(session.seen.lock().unwrap().is_empty());
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Trailing Type Report Tests ====================
+
+    #[test]
+    fn test_trim_categorizes_removed_whitespace() {
+        let result = trim_trailing_whitespace("a  \t\nb\t\t\nc \u{a0}\nd\n");
+        assert_eq!(
+            result.removed,
+            WhitespaceBreakdown {
+                spaces: 3,
+                tabs: 3,
+                other: 1,
+                lines: 3,
+            }
+        );
+        assert_eq!(result.removed.total(), 7);
+    }
+
+    #[test]
+    fn test_report_trailing_types_accumulates_without_writing() {
+        let test_dir = create_test_dir();
+        let spaces = test_dir.join("spaces.txt");
+        let tabs = test_dir.join("tabs.txt");
+        fs::write(&spaces, "a   \nb \t\n").unwrap();
+        fs::write(&tabs, "c\t\t\n").unwrap();
+
+        let config = Config {
+            report_trailing_types: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+
+        assert_eq!(
+            *session.removed.lock().unwrap(),
+            WhitespaceBreakdown {
+                spaces: 4,
+                tabs: 3,
+                other: 0,
+                lines: 3,
+            }
+        );
+        assert_eq!(fs::read_to_string(&spaces).unwrap(), "a   \nb \t\n");
+        assert_eq!(fs::read_to_string(&tabs).unwrap(), "c\t\t\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Stdin Paths Tests ====================
+
+    #[test]
+    fn test_parse_config_stdin_paths0() {
+        let args = vec!["rtrim".to_string(), "--stdin-paths0".to_string()];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.mode, Mode::StdinPaths0);
+    }
+
+    #[test]
+    fn test_process_paths0_trims_each_path() {
+        let test_dir = create_test_dir();
+        let first = test_dir.join("first.txt");
+        let second = test_dir.join("with space.txt");
+        let untouched = test_dir.join("untouched.txt");
+        fs::write(&first, "one   \n").unwrap();
+        fs::write(&second, "two\t\n").unwrap();
+        fs::write(&untouched, "three   \n").unwrap();
+
+        let mut input = Vec::new();
+        for path in [&first, &second] {
+            input.extend_from_slice(path.as_os_str().as_bytes());
+            input.push(0);
+        }
+        // A missing path only produces a warning.
+        input.extend_from_slice(b"missing.txt\0");
+
+        let session = Session::default();
+        process_paths0(&input[..], &Config::default(), &session).unwrap();
+
+        assert_eq!(fs::read_to_string(&first).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "two\n");
+        assert_eq!(fs::read_to_string(&untouched).unwrap(), "three   \n");
+        assert_eq!(session.reporter.warning_count(), 1);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Stdin Filter Tests ====================
+
+    #[test]
+    fn test_parse_config_stdin_filter() {
+        for flag in ["-", "--stdin"] {
+            let args = vec!["rtrim".to_string(), flag.to_string()];
+            assert_eq!(parse_config(&args).unwrap().mode, Mode::Stdin);
+        }
+    }
+
+    #[test]
+    fn test_filter_trims_stdin_to_stdout() {
+        let session = Session::default();
+        let mut output = Vec::new();
+        let input: &[u8] = b"messy   \nlines\t\n  kept indent\n";
+        filter(input, &mut output, &Config::default(), &session).unwrap();
+        assert_eq!(output, b"messy\nlines\n  kept indent\n");
+        assert!(session.modified.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_filter_rejects_binary_input() {
+        let mut output = Vec::new();
+        let input: &[u8] = b"\xff\xfe  \n";
+        let err = filter(input, &mut output, &Config::default(), &Session::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_filter_passes_binary_input_through() {
+        let config = Config {
+            stdin_passthrough: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        let mut output = Vec::new();
+        let input: &[u8] = b"\xff\xfe  \n\x00tail \t";
+        filter(input, &mut output, &config, &session).unwrap();
+        assert_eq!(output, input);
+        assert!(!session.modified.load(Ordering::Relaxed));
+        assert_eq!(session.stats().unchanged, 1);
+
+        // Valid UTF-8 with a NUL byte is binary all the same.
+        let mut output = Vec::new();
+        let input: &[u8] = b"text  \n\x00data  \n";
+        filter(input, &mut output, &config, &session).unwrap();
+        assert_eq!(output, input);
+        assert_eq!(session.stats().unchanged, 2);
+
+        let mut output = Vec::new();
+        filter(&b"text   \n"[..], &mut output, &config, &session).unwrap();
+        assert_eq!(output, b"text\n");
+    }
+
+    #[test]
+    fn test_filter_keeps_crlf_of_unchanged_input() {
+        let session = Session::default();
+        let mut output = Vec::new();
+        let input: &[u8] = b"clean\r\nlines\r\n";
+        filter(input, &mut output, &Config::default(), &session).unwrap();
+        assert_eq!(output, input);
+        assert!(!session.modified.load(Ordering::Relaxed));
+        assert_eq!(session.stats().unchanged, 1);
+    }
+
+    #[test]
+    fn test_filter_check_writes_nothing() {
+        let config = Config {
+            check: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        let mut output = Vec::new();
+        let input: &[u8] = b"dirty  \n";
+        filter(input, &mut output, &config, &session).unwrap();
+        assert!(output.is_empty());
+        assert!(session.modified.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_filter_json_response() {
+        let args: Vec<String> = ["rtrim", "--stdin-json"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(parse_config(&args).unwrap().mode, Mode::StdinJson);
+
+        let mut output = Vec::new();
+        let input: &[u8] = b"fn main() {  \n    \"quoted\"\n}\t\n";
+        filter_json(input, &mut output, &Config::default(), &Session::default()).unwrap();
+        let response = String::from_utf8(output).unwrap();
+
+        // The value of `name`, up to the field that follows it.
+        let field = |name: &str, next: &str| {
+            let start = response.find(&format!("\"{}\": ", name)).unwrap() + name.len() + 4;
+            let end = start + response[start..].find(next).unwrap();
+            response[start..end].to_string()
+        };
+        assert_eq!(field("changed", ", \"content\""), "true");
+        assert_eq!(
+            field("content", ", \"lines_trimmed\""),
+            json::encode_string("fn main() {\n    \"quoted\"\n}\n")
+        );
+        assert_eq!(field("lines_trimmed", "}"), "[1, 3]");
+        assert!(response.starts_with('{') && response.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_filter_json_clean_input() {
+        let mut output = Vec::new();
+        filter_json(
+            &b"clean\n"[..],
+            &mut output,
+            &Config::default(),
+            &Session::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"changed\": false, \"content\": \"clean\\n\", \"lines_trimmed\": []}\n"
+        );
+
+        // Line endings alone do not count as a change.
+        let mut output = Vec::new();
+        filter_json(
+            &b"clean\r\n"[..],
+            &mut output,
+            &Config::default(),
+            &Session::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"changed\": false, \"content\": \"clean\\r\\n\", \"lines_trimmed\": []}\n"
+        );
+    }
+
+    // ==================== Extension Filter Tests ====================
+
+    #[test]
+    fn test_parse_config_include_exclude() {
+        let args: Vec<String> = [
+            "rtrim",
+            "--folder",
+            ".",
+            "--include",
+            "rs",
+            "--include",
+            ".TOML",
+            "--exclude",
+            "md",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.include, vec!["rs", "toml"]);
+        assert_eq!(config.exclude, vec!["md"]);
+
+        let args: Vec<String> = ["rtrim", "--folder", ".", "--include", "."]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(parse_config(&args).is_err());
+    }
+
+    #[test]
+    fn test_folder_include_and_exclude_extensions() {
+        let test_dir = create_test_dir();
+        let nested = test_dir.join("nested");
+        fs::create_dir(&nested).unwrap();
+        for name in ["main.rs", "Cargo.TOML", "README.md", "Makefile"] {
+            fs::write(test_dir.join(name), "x  \n").unwrap();
+        }
+        fs::write(nested.join("lib.rs"), "y  \n").unwrap();
+        let contents = |name: &str| fs::read_to_string(test_dir.join(name)).unwrap();
+
+        let included = Config {
+            include: vec!["rs".to_string(), "toml".to_string()],
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_folder(&test_dir, &included, &session).unwrap();
+        assert_eq!(contents("main.rs"), "x\n");
+        assert_eq!(contents("Cargo.TOML"), "x\n");
+        assert_eq!(contents("nested/lib.rs"), "y\n");
+        assert_eq!(contents("README.md"), "x  \n");
+        assert_eq!(contents("Makefile"), "x  \n");
+        assert_eq!(session.stats().skipped, 2);
+
+        let excluded = Config {
+            exclude: vec!["md".to_string()],
+            ..Config::default()
+        };
+        process_folder(&test_dir, &excluded, &Session::default()).unwrap();
+        assert_eq!(contents("Makefile"), "x\n");
+        assert_eq!(contents("README.md"), "x  \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== List Ignored Tests ====================
+
+    #[test]
+    fn test_list_ignored_reports_reasons_without_modifying() {
+        let test_dir = create_test_dir();
+        let modules = test_dir.join("node_modules");
+        fs::create_dir(&modules).unwrap();
+        fs::write(modules.join("index.js"), "x  \n").unwrap();
+        fs::write(test_dir.join("image.bin"), b"\xff\xfe  \n").unwrap();
+        fs::write(test_dir.join("dirty.txt"), "dirty  \n").unwrap();
+        std::os::unix::fs::symlink("dirty.txt", test_dir.join("link.txt")).unwrap();
+
+        let args: Vec<String> = ["rtrim", "--folder", ".", "--list-ignored"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config {
+            path: test_dir.clone(),
+            ..parse_config(&args).unwrap()
+        };
+        assert!(!config.writes_files());
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        execute(&config, &session).unwrap();
+        session.finish();
+
+        let mut lines: Vec<String> = out.contents().lines().map(String::from).collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                format!("binary\t{}", test_dir.join("image.bin").display()),
+                format!("ignored dir\t{}", modules.display()),
+                format!("symlink\t{}", test_dir.join("link.txt").display()),
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(test_dir.join("dirty.txt")).unwrap(),
+            "dirty  \n"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Quiet Tests ====================
+
+    #[test]
+    fn test_quiet_and_quiet_errors_control_each_stream() {
+        let test_dir = create_test_dir();
+        let dirty = test_dir.join("dirty.txt");
+        let paths = vec![dirty.clone(), test_dir.join("missing.txt")];
+
+        for (quiet, quiet_errors) in [(false, false), (true, false), (false, true), (true, true)] {
+            fs::write(&dirty, "dirty  \n").unwrap();
+            let config = Config {
+                quiet,
+                quiet_errors,
+                ..Config::default()
+            };
+            let (reporter, out, err) = capturing_reporter(None);
+            let session = Session::new(reporter);
+            process_paths(&paths, &config, &session).unwrap();
+            let summary = session.summary(&config);
+            session.finish();
+
+            assert_eq!(out.contents().contains("Processed: "), !quiet);
+            assert_eq!(err.contents().contains("missing.txt"), !quiet_errors);
+            assert!(summary.failed && summary.modified);
+            assert_eq!(fs::read_to_string(&dirty).unwrap(), "dirty\n");
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_quiet_flags() {
+        let args = |list: &[&str]| -> Vec<String> {
+            std::iter::once("rtrim")
+                .chain(list.iter().copied())
+                .map(String::from)
+                .collect()
+        };
+        let config = parse_config(&args(&["-q", "--folder", "."])).unwrap();
+        assert!(config.quiet && !config.quiet_errors);
+        let config = parse_config(&args(&["--quiet-errors", "--folder", "."])).unwrap();
+        assert!(!config.quiet && config.quiet_errors);
+        assert!(parse_config(&args(&["--quiet", "--verbose", "--folder", "."])).is_err());
+    }
+
+    // ==================== Summary Tests ====================
+
+    #[test]
+    fn test_folder_run_prints_summary_across_subdirectories() {
+        let test_dir = create_test_dir();
+        let nested = test_dir.join("nested").join("deeper");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(test_dir.join("dirty.txt"), "dirty  \n").unwrap();
+        fs::write(test_dir.join("clean.txt"), "clean\n").unwrap();
+        fs::write(test_dir.join("image.bin"), b"\xff\xfe  \n").unwrap();
+        fs::write(nested.join("deep.txt"), "deep\t\n").unwrap();
+        std::os::unix::fs::symlink("dirty.txt", nested.join("link.txt")).unwrap();
+
+        let config = Config::new(Mode::Folder, &test_dir);
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        execute(&config, &session).unwrap();
+        let stats = session.stats();
+        session.finish();
+
+        assert_eq!(
+            stats,
+            Stats {
+                scanned: 5,
+                modified: 2,
+                unchanged: 1,
+                binary: 1,
+                symlinks: 1,
+                skipped: 0,
+                failed: 0,
+            }
+        );
+        assert!(out
+            .contents()
+            .ends_with("\nScanned 5, modified 2, unchanged 1, skipped 1 binary, 1 symlink\n"));
+
+        // Porcelain output stays one line per file.
+        let config = Config {
+            porcelain: true,
+            ..Config::new(Mode::Folder, &test_dir)
+        };
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        execute(&config, &session).unwrap();
+        session.finish();
+        assert!(!out.contents().contains("Scanned"));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_stats_describe_mentions_other_skips_and_failures() {
+        let stats = Stats {
+            scanned: 9,
+            modified: 1,
+            unchanged: 2,
+            binary: 0,
+            symlinks: 2,
+            skipped: 3,
+            failed: 1,
+        };
+        assert_eq!(
+            stats.describe(),
+            "Scanned 9, modified 1, unchanged 2, skipped 0 binary, 2 symlinks, 3 other, failed 1"
+        );
+    }
+
+    // ==================== Exit Code Tests ====================
+
+    #[test]
+    fn test_parse_config_modified_exit_code_range() {
+        let args = |code: &str| {
+            vec![
+                "rtrim".to_string(),
+                "--modified-exit-code".to_string(),
+                code.to_string(),
+                "--folder".to_string(),
+                ".".to_string(),
+            ]
+        };
+        let codes = parse_config(&args("3")).unwrap().exit_codes;
+        assert_eq!((codes.modified, codes.would_modify), (3, 3));
+        assert!(parse_config(&args("126")).is_err());
+        assert!(parse_config(&args("-1")).is_err());
+    }
+
+    #[test]
+    fn test_run_returns_modified_exit_code() {
+        let test_dir = create_test_dir();
+        let dirty = test_dir.join("dirty.txt");
+        fs::write(&dirty, "dirty   \n").unwrap();
+
+        let config = || Config {
+            mode: Mode::File,
+            path: dirty.clone(),
+            exit_codes: ExitCodes {
+                modified: 3,
+                ..ExitCodes::default()
+            },
+            ..Config::default()
+        };
+        assert_eq!(run_silently(config()), 3);
+        // Now clean: nothing modified, so the default status is returned.
+        assert_eq!(run_silently(config()), 0);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_run_default_exit_code_is_zero_when_modified() {
+        let test_dir = create_test_dir();
+        let dirty = test_dir.join("dirty.txt");
+        fs::write(&dirty, "dirty   \n").unwrap();
+
+        let config = Config {
+            mode: Mode::Folder,
+            path: test_dir.clone(),
+            ..Config::default()
+        };
+        assert_eq!(run_silently(config), 0);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_check_mode_exit_status_and_output() {
+        let test_dir = create_test_dir();
+        let nested = test_dir.join("nested");
+        fs::create_dir(&nested).unwrap();
+        fs::write(test_dir.join("clean.txt"), "clean\n").unwrap();
+        fs::write(test_dir.join("image.bin"), b"\xff\xfe  \n").unwrap();
+        std::os::unix::fs::symlink("dirty.txt", test_dir.join("link.txt")).unwrap();
+
+        let args: Vec<String> = ["rtrim", "--folder", ".", "--check"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let parsed = parse_config(&args).unwrap();
+        assert!(parsed.check && !parsed.writes_files());
+        let check = || Config {
+            path: test_dir.clone(),
+            ..parse_config(&args).unwrap()
+        };
+        // Binary files and symlinks are not changes.
+        assert_eq!(run_silently(check()), 0);
+
+        let dirty = nested.join("dirty.txt");
+        fs::write(&dirty, "dirty   \n").unwrap();
+        assert_eq!(run_silently(check()), 1);
+        assert_eq!(fs::read_to_string(&dirty).unwrap(), "dirty   \n");
+        let entries: Vec<_> = fs::read_dir(&nested).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        process_folder(&test_dir, &check(), &session).unwrap();
+        session.finish();
+        assert_eq!(out.contents(), format!("{}\n", dirty.display()));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_dry_run_write_report_matches_check() {
+        let test_dir = create_test_dir();
+        let tree = test_dir.join("tree");
+        fs::create_dir(&tree).unwrap();
+        let first = tree.join("a \"quoted\".txt");
+        let second = tree.join("b.txt");
+        fs::write(&first, "one  \ntwo\t\n").unwrap();
+        fs::write(&second, "three \n").unwrap();
+        fs::write(tree.join("clean.txt"), "clean\n").unwrap();
+
+        let report_config = |report: &str, flags: &[&str]| {
+            let mut args = vec!["rtrim".to_string(), "--folder".to_string()];
+            args.push(tree.to_string_lossy().into_owned());
+            args.push("--dry-run-write-report".to_string());
+            args.push(test_dir.join(report).to_string_lossy().into_owned());
+            args.extend(flags.iter().map(|flag| flag.to_string()));
+            parse_config(&args).unwrap()
+        };
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = report_config("report.txt", &[]);
+        assert!(config.check);
+        execute(&config, &session).unwrap();
+        session.finish();
+        let text = fs::read_to_string(test_dir.join("report.txt")).unwrap();
+        assert_eq!(text, format!("{}\n{}\n", first.display(), second.display()));
+        let mut printed: Vec<_> = out.contents().lines().map(String::from).collect();
+        printed.sort();
+        assert_eq!(printed.join("\n") + "\n", text);
+
+        assert_eq!(run_silently(report_config("report.json", &[])), 1);
+        assert_eq!(
+            fs::read_to_string(test_dir.join("report.json")).unwrap(),
+            format!(
+                "{{\n  \"files\": [\n    {{\"path\": {}, \"lines\": 2, \"spaces\": 2, \"tabs\": 1, \"other\": 0}},\n    {{\"path\": {}, \"lines\": 1, \"spaces\": 1, \"tabs\": 0, \"other\": 0}}\n  ],\n  \"total\": {{\"files\": 2, \"lines\": 3, \"spaces\": 3, \"tabs\": 1, \"other\": 0}}\n}}\n",
+                json::encode_string(&first.to_string_lossy()),
+                json::encode_string(&second.to_string_lossy())
+            )
+        );
+
+        let config = report_config("report.out", &["--dry-run-report-format", "json"]);
+        assert_eq!(run_silently(config), 1);
+        let json = fs::read_to_string(test_dir.join("report.out")).unwrap();
+        assert!(json.starts_with("{\n  \"files\": ["));
+        assert_eq!(fs::read_to_string(&second).unwrap(), "three \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_exit_code_matrix() {
+        let codes = ExitCodes {
+            clean: 10,
+            modified: 11,
+            would_modify: 12,
+            binary_only: 13,
+            error: 14,
+        };
+        let summary = |failed, modified, writes_files, files, binary_files| RunSummary {
+            failed,
+            modified,
+            writes_files,
+            files,
+            binary_files,
+        };
+        let cases = [
+            (summary(false, false, true, 0, 0), 10),
+            (summary(false, false, true, 3, 1), 10),
+            (summary(false, false, false, 3, 0), 10),
+            (summary(false, true, true, 3, 0), 11),
+            (summary(false, true, false, 3, 0), 12),
+            (summary(false, false, true, 2, 2), 13),
+            (summary(false, false, false, 2, 2), 13),
+            (summary(true, true, true, 3, 0), 14),
+            (summary(true, false, true, 2, 2), 14),
+            (summary(true, false, true, 0, 0), 14),
+        ];
+        for (summary, expected) in cases {
+            assert_eq!(exit_code(&summary, &codes), expected, "{:?}", summary);
+        }
+        assert_eq!(exit_code(&RunSummary::default(), &ExitCodes::default()), 0);
+        let failed = RunSummary {
+            failed: true,
+            ..RunSummary::default()
+        };
+        assert_eq!(exit_code(&failed, &ExitCodes::default()), 1);
+    }
+
+    #[test]
+    fn test_parse_config_exit_code() {
+        let parse = |flags: &[&str]| {
+            let mut args = vec!["rtrim".to_string()];
+            args.extend(flags.iter().map(|flag| flag.to_string()));
+            args.extend(["--folder".to_string(), ".".to_string()]);
+            parse_config(&args).map(|config| config.exit_codes)
+        };
+        assert_eq!(
+            parse(&["--exit-code", "would-modify=1", "--exit-code=binary-only=4"]),
+            Ok(ExitCodes {
+                would_modify: 1,
+                binary_only: 4,
+                ..ExitCodes::default()
+            })
+        );
+        assert_eq!(parse(&["--exit-code=error=2"]).unwrap().error, 2);
+        assert!(parse(&["--exit-code", "dirty=1"]).is_err());
+        assert!(parse(&["--exit-code", "clean=126"]).is_err());
+        assert!(parse(&["--exit-code", "clean"]).is_err());
+
+        // --check defaults would-modify to 1, unless it is set explicitly.
+        assert_eq!(parse(&["--check"]).unwrap().would_modify, 1);
+        assert_eq!(
+            parse(&["--check", "--exit-code", "would-modify=0"])
+                .unwrap()
+                .would_modify,
+            0
+        );
+        assert_eq!(
+            parse(&["--exit-code=would-modify=3", "--check"])
+                .unwrap()
+                .would_modify,
+            3
+        );
+    }
+
+    #[test]
+    fn test_run_exit_code_scenarios() {
+        let test_dir = create_test_dir();
+        let binary = test_dir.join("image.bin");
+        fs::write(&binary, b"\xff\xfe\x00").unwrap();
+        let codes = ExitCodes {
+            clean: 10,
+            modified: 11,
+            would_modify: 12,
+            binary_only: 13,
+            error: 14,
+        };
+        let folder = |config: Config| Config {
+            mode: Mode::Folder,
+            path: test_dir.clone(),
+            exit_codes: codes,
+            ..config
+        };
+
+        assert_eq!(run_silently(folder(Config::default())), 13);
+        let dirty = test_dir.join("dirty.txt");
+        fs::write(&dirty, "dirty   \n").unwrap();
+        let report_only = Config {
+            report_trailing_types: true,
+            ..Config::default()
+        };
+        assert_eq!(run_silently(folder(report_only)), 12);
+        assert_eq!(run_silently(folder(Config::default())), 11);
+        assert_eq!(run_silently(folder(Config::default())), 10);
+
+        // A file that cannot be processed fails the run.
+        let missing = Config {
+            mode: Mode::File,
+            path: test_dir.join("missing.txt"),
+            exit_codes: codes,
+            ..Config::default()
+        };
+        assert_eq!(run_silently(missing), 14);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_multiple_paths_continue_after_a_failure() {
+        let test_dir = create_test_dir();
+        let file = test_dir.join("one.txt");
+        let folder = test_dir.join("folder");
+        fs::create_dir(&folder).unwrap();
+        fs::write(&file, "one   \n").unwrap();
+        fs::write(folder.join("two.txt"), "two\t\n").unwrap();
+
+        let (reporter, _out, err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let paths = vec![test_dir.join("missing.txt"), file.clone(), folder.clone()];
+        process_paths(&paths, &Config::default(), &session).unwrap();
+        let summary = session.summary(&Config::default());
+        session.finish();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(folder.join("two.txt")).unwrap(), "two\n");
+        assert!(err.contents().contains("missing.txt"));
+        assert!(summary.failed && summary.modified);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_run_prints_execution_error_to_err_sink() {
+        let out = SharedBuf::default();
+        let err = SharedBuf::default();
+        let config = Config::new(Mode::Folder, "/nonexistent/rtrim-test-dir");
+        let status = run(&config, Box::new(out.clone()), Box::new(err.clone()));
+        assert_eq!(status, 1);
+        assert_eq!(out.contents(), "");
+        assert!(err.contents().starts_with("Execution Error: "));
+    }
+
+    // ==================== Read-Only Tests ====================
+
+    #[test]
+    fn test_process_file_skip_readonly() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("readonly.txt");
+        fs::write(&test_file, "locked   \n").unwrap();
+        fs::set_permissions(&test_file, Permissions::from_mode(0o444)).unwrap();
+
+        let config = Config {
+            skip_readonly: true,
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::ReadOnly));
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "locked   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_file_readonly_processed_by_default() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("readonly.txt");
+        fs::write(&test_file, "locked   \n").unwrap();
+        fs::set_permissions(&test_file, Permissions::from_mode(0o444)).unwrap();
+
+        let outcome = process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "locked\n");
+        let mode = fs::metadata(&test_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o444);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== On-Modify Hook Tests ====================
+
+    #[test]
+    fn test_parse_config_on_modify_exec() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--on-modify-exec".to_string(),
+            "git add".to_string(),
+            "--folder".to_string(),
+            ".".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.on_modify_exec, vec!["git", "add"]);
+    }
+
+    #[test]
+    fn test_on_modify_exec_receives_modified_path() {
+        let test_dir = create_test_dir();
+        let modified = test_dir.join("modified.txt");
+        let clean = test_dir.join("clean.txt");
+        let copy = test_dir.join("copy.out");
+        fs::write(&modified, "dirty   \n").unwrap();
+        fs::write(&clean, "clean\n").unwrap();
+
+        let config = Config {
+            on_modify_exec: vec!["cp".into(), "{}".into(), copy.display().to_string()],
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_file(&modified, &config, &session).unwrap();
+        process_file(&clean, &config, &session).unwrap();
+
+        // Only the modified file was handed to the command, after trimming.
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "dirty\n");
+        assert_eq!(session.hook_failures.load(Ordering::Relaxed), 0);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_on_modify_exec_failures_are_collected() {
+        let test_dir = create_test_dir();
+        let modified = test_dir.join("modified.txt");
+        fs::write(&modified, "dirty   \n").unwrap();
+
+        let config = Config {
+            on_modify_exec: vec!["false".into()],
+            ..Config::default()
+        };
+        let session = Session::default();
+        let outcome = process_file(&modified, &config, &session).unwrap();
+
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(session.hook_failures.load(Ordering::Relaxed), 1);
+        assert_eq!(session.reporter.warning_count(), 1);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Content Match Tests ====================
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_parse_config_content_match() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--content-match".to_string(),
+            "^// SPDX".to_string(),
+            "--folder".to_string(),
+            ".".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.content_match.unwrap().as_str(), "^// SPDX");
+
+        let mut bad = args.clone();
+        bad[2] = "(unclosed".to_string();
+        assert!(parse_config(&bad).is_err());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_content_match_filters_files() {
+        let test_dir = create_test_dir();
+        let licensed = test_dir.join("licensed.rs");
+        let other = test_dir.join("other.rs");
+        fs::write(
+            &licensed,
+            "// SPDX-License-Identifier: MIT   \nfn a() {}  \n",
+        )
+        .unwrap();
+        fs::write(&other, "fn b() {}  \n").unwrap();
+
+        let config = Config {
+            content_match: Some(Pattern::new("^// SPDX-License-Identifier").unwrap()),
+            ..Config::default()
+        };
+        let session = Session::default();
+
+        let matched = process_file(&licensed, &config, &session).unwrap();
+        let unmatched = process_file(&other, &config, &session).unwrap();
+
+        assert_eq!(matched, Outcome::Modified);
+        assert_eq!(unmatched, Outcome::Skipped(SkipReason::NoContentMatch));
+        assert_eq!(
+            fs::read_to_string(&licensed).unwrap(),
+            "// SPDX-License-Identifier: MIT\nfn a() {}\n"
+        );
+        assert_eq!(fs::read_to_string(&other).unwrap(), "fn b() {}  \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Protect Regex Tests ====================
+
+    #[test]
+    fn test_preserve_continuations_keeps_backslash_lines() {
+        let config = Config {
+            preserve_continuations: true,
+            ..Config::default()
+        };
+        let input = "all: \\\n\tfoo \\   \n\tfoo\\\n\tdone  \n";
+        let result = trim_for_config(Path::new("Makefile"), input, &config).unwrap();
+        assert_eq!(result.content, "all: \\\n\tfoo \\   \n\tfoo\\\n\tdone\n");
+        assert_eq!(result.removed.lines, 1);
+
+        // Only the continuation lines, which need no trimming, are left.
+        let input = "foo \\\nfoo \\   \nfoo\\";
+        let result = trim_for_config(Path::new("run.sh"), input, &config).unwrap();
+        assert_eq!(result.content, input);
+        assert!(!result.modified);
+    }
+
+    #[test]
+    fn test_continuations_are_trimmed_by_default() {
+        let input = "foo \\\nfoo \\   \nfoo\\";
+        let result = trim_for_config(Path::new("run.sh"), input, &Config::default()).unwrap();
+        assert_eq!(result.content, "foo \\\nfoo \\\nfoo\\");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_protect_regex_keeps_matching_lines() {
+        let config = Config {
+            protect_regex: Some(Pattern::new("KEEP").unwrap()),
+            ..Config::default()
+        };
+        let input = "trim me   \nKEEP this  \nand me\t\n";
+        let result = trim_for_config(Path::new("a.txt"), input, &config).unwrap();
+        assert_eq!(result.content, "trim me\nKEEP this  \nand me\n");
+        assert_eq!(result.removed.total(), 4);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_protect_regex_only_protected_lines_do_not_modify() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("sentinel.md");
+        fs::write(&test_file, "line break  <!-- keep -->  \nclean\n").unwrap();
+
+        let config = Config {
+            protect_regex: Some(Pattern::new("<!-- keep -->").unwrap()),
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Unchanged);
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "line break  <!-- keep -->  \nclean\n"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_protect_regex_combines_with_yaml_mode() {
+        let config = Config {
+            yaml_aware: true,
+            protect_regex: Some(Pattern::new("^# keep").unwrap()),
+            ..Config::default()
+        };
+        let input = "# keep  \nkey: value  \n";
+        let result = trim_for_config(Path::new("ci.yml"), input, &config).unwrap();
+        assert_eq!(result.content, "# keep  \nkey: value\n");
+    }
+
+    // ==================== Verbose Error Tests ====================
+
+    #[test]
+    fn test_parse_config_verbose_errors() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--verbose-errors".to_string(),
+            "--file".to_string(),
+            "a.txt".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert!(config.verbose_errors);
+        assert!(!config.verbose);
+    }
+
+    #[test]
+    fn test_process_file_error_carries_context_and_kind() {
+        let test_dir = create_test_dir();
+        let missing = test_dir.join("missing.txt");
+
+        let err = process_file(&missing, &Config::default(), &Session::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(err.path(), missing.as_path());
+
+        let terse = error::describe(&err, false);
+        assert!(terse.starts_with("failed to read metadata: "));
+        assert!(!terse.contains('\n'));
+
+        let verbose = error::describe(&err, true);
+        assert!(verbose.contains("(kind: NotFound)"));
+        assert!(verbose.contains("\n  caused by: "));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== YAML-Aware Mode Tests ====================
+
+    #[test]
+    fn test_yaml_mode_preserves_block_scalars() {
+        let config = Config {
+            yaml_aware: true,
+            ..Config::default()
+        };
+        let input = "name: app  \nmessage: |\n  keep this  \n  and this\t\nafter: 1   \n";
+        let result = trim_for_config(Path::new("ci.yml"), input, &config).unwrap();
+        assert_eq!(
+            result.content,
+            "name: app\nmessage: |\n  keep this  \n  and this\t\nafter: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_yaml_mode_only_applies_to_yaml_files() {
+        let config = Config {
+            yaml_aware: true,
+            ..Config::default()
+        };
+        let input = "message: |\n  trimmed  \n";
+        let result = trim_for_config(Path::new("notes.txt"), input, &config).unwrap();
+        assert_eq!(result.content, "message: |\n  trimmed\n");
+    }
+
+    #[test]
+    fn test_yaml_block_scalars_trimmed_without_flag() {
+        let input = "message: |\n  trimmed  \n";
+        let result = trim_for_config(Path::new("ci.yaml"), input, &Config::default()).unwrap();
+        assert_eq!(result.content, "message: |\n  trimmed\n");
+    }
+
+    // ==================== Shell Here-Doc Mode Tests ====================
+
+    #[test]
+    fn test_shell_mode_preserves_quoted_and_unquoted_heredocs() {
+        let config = Config {
+            shell_aware: true,
+            ..Config::default()
+        };
+        let input =
+            "#!/bin/sh  \ncat <<'EOF'\nraw $x  \nEOF\ncat <<EOF > out\nline\t\nEOF\necho done  \n";
+        let result = trim_for_config(Path::new("build.sh"), input, &config).unwrap();
+        assert_eq!(
+            result.content,
+            "#!/bin/sh\ncat <<'EOF'\nraw $x  \nEOF\ncat <<EOF > out\nline\t\nEOF\necho done\n"
+        );
+    }
+
+    #[test]
+    fn test_shell_mode_only_applies_to_shell_scripts() {
+        let config = Config {
+            shell_aware: true,
+            ..Config::default()
+        };
+        let input = "cat <<EOF\ntrimmed  \nEOF\n";
+        let result = trim_for_config(Path::new("notes.txt"), input, &config).unwrap();
+        assert_eq!(result.content, "cat <<EOF\ntrimmed\nEOF\n");
+    }
+
+    #[test]
+    fn test_shell_heredocs_trimmed_without_flag() {
+        let input = "cat <<'EOF'\ntrimmed  \nEOF\n";
+        let result = trim_for_config(Path::new("run.bash"), input, &Config::default()).unwrap();
+        assert_eq!(result.content, "cat <<'EOF'\ntrimmed\nEOF\n");
+    }
+
+    // ==================== CSV Mode Tests ====================
+
+    #[test]
+    fn test_csv_mode_preserves_whitespace_in_quoted_fields() {
+        let config = Config {
+            csv_aware: true,
+            ..Config::default()
+        };
+        let input = "name,comment  \nann,\"kept   \nacross lines  \"\t\nbob,\"  padded  \"  \n";
+        let result = trim_for_config(Path::new("data.csv"), input, &config).unwrap();
+        assert_eq!(
+            result.content,
+            "name,comment\nann,\"kept   \nacross lines  \"\nbob,\"  padded  \"\n"
+        );
+
+        let plain = trim_for_config(Path::new("data.csv"), input, &Config::default()).unwrap();
+        assert!(plain.content.contains("\"kept\n"));
+    }
+
+    // ==================== HTML Mode Tests ====================
+
+    #[test]
+    fn test_html_mode_preserves_pre_content() {
+        let config = Config {
+            html_aware: true,
+            ..Config::default()
+        };
+        let input = "<body>  \n<pre>\n  x = 1   \n</pre>\t\n<p>text</p>   \n</body>\n";
+        let result = trim_for_config(Path::new("page.HTML"), input, &config).unwrap();
+        assert_eq!(
+            result.content,
+            "<body>\n<pre>\n  x = 1   \n</pre>\n<p>text</p>\n</body>\n"
+        );
+
+        let other = trim_for_config(Path::new("notes.md"), input, &config).unwrap();
+        assert!(other.content.contains("  x = 1\n"));
+    }
+
+    // ==================== Enforce Ignores Tests ====================
+
+    #[test]
+    fn test_ignored_ancestor() {
+        assert_eq!(
+            ignored_ancestor(Path::new(".git/config")),
+            Some(Path::new(".git"))
+        );
+        assert_eq!(
+            ignored_ancestor(Path::new("./a/node_modules/x/.cache/f.js")),
+            Some(Path::new("./a/node_modules"))
+        );
+        assert_eq!(ignored_ancestor(Path::new("./src/main.rs")), None);
+        assert_eq!(ignored_ancestor(Path::new("../src/.env")), None);
+    }
+
+    #[test]
+    fn test_enforce_ignores_on_file_refuses_git_files() {
+        let test_dir = create_test_dir();
+        fs::create_dir(test_dir.join(".git")).unwrap();
+        let test_file = test_dir.join(".git").join("config");
+        fs::write(&test_file, "[core]   \n").unwrap();
+
+        let (reporter, out, err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            enforce_ignores_on_file: true,
+            porcelain: true,
+            ..Config::default()
+        };
+        run_file(&test_file, &config, &session).unwrap();
+        let stats = session.stats();
+        assert_eq!((stats.scanned, stats.skipped), (1, 1));
+        session.finish();
+
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "[core]   \n");
+        assert_eq!(out.contents(), format!("Si\t{}\n", test_file.display()));
+        assert!(err.contents().contains("inside ignored directory"));
+
+        // Without the option the file is processed as before.
+        run_file(&test_file, &Config::default(), &Session::default()).unwrap();
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "[core]\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== One Filesystem Tests ====================
+
+    #[test]
+    fn test_on_other_filesystem_same_device() {
+        let test_dir = create_test_dir();
+        let sub_dir = test_dir.join("sub");
+        fs::create_dir(&sub_dir).unwrap();
+
+        let root_dev = fs::symlink_metadata(&test_dir).unwrap().dev();
+        let sub_metadata = fs::symlink_metadata(&sub_dir).unwrap();
+        assert!(!on_other_filesystem(root_dev, &sub_metadata));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_on_other_filesystem_different_device() {
+        let test_dir = create_test_dir();
+        let root_dev = fs::symlink_metadata(&test_dir).unwrap().dev();
+
+        // Guarded: only meaningful where /proc is a separate mount.
+        if let Ok(proc_metadata) = fs::symlink_metadata("/proc") {
+            if proc_metadata.dev() != root_dev {
+                assert!(on_other_filesystem(root_dev, &proc_metadata));
+            }
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_process_folder_one_filesystem_still_recurses_locally() {
+        let test_dir = create_test_dir();
+        let sub_dir = test_dir.join("sub");
+        fs::create_dir(&sub_dir).unwrap();
+        let nested = sub_dir.join("nested.txt");
+        fs::write(&nested, "nested   \n").unwrap();
+
+        let config = Config {
+            one_filesystem: true,
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &Session::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(&nested).unwrap(), "nested\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Max Depth Tests ====================
+
+    #[test]
+    fn test_parse_config_max_depth() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--max-depth".to_string(),
+            "2".to_string(),
+            ".".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.max_depth, Some(2));
+
+        for value in ["0", "-1", "deep"] {
+            let args = vec![
+                "rtrim".to_string(),
+                "--max-depth".to_string(),
+                value.to_string(),
+                ".".to_string(),
+            ];
+            assert!(parse_config(&args).unwrap_err().contains("--max-depth"));
+        }
+    }
+
+    #[test]
+    fn test_process_folder_stops_at_max_depth() {
+        let test_dir = create_test_dir();
+        let top = test_dir.join("top.txt");
+        fs::write(&top, "top   \n").unwrap();
+        fs::create_dir_all(test_dir.join("sub/deeper")).unwrap();
+        let sub = test_dir.join("sub/sub.txt");
+        fs::write(&sub, "sub   \n").unwrap();
+        let deeper = test_dir.join("sub/deeper/deeper.txt");
+        fs::write(&deeper, "deeper   \n").unwrap();
+        fs::create_dir(test_dir.join("node_modules")).unwrap();
+        let vendored = test_dir.join("node_modules/dep.txt");
+        fs::write(&vendored, "dep   \n").unwrap();
+
+        let config = Config {
+            max_depth: Some(2),
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &Session::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(&top).unwrap(), "top\n");
+        assert_eq!(fs::read_to_string(&sub).unwrap(), "sub\n");
+        assert_eq!(fs::read_to_string(&deeper).unwrap(), "deeper   \n");
+        // Ignored directories stay ignored within the allowed depth.
+        assert_eq!(fs::read_to_string(&vendored).unwrap(), "dep   \n");
+
+        let config = Config {
+            max_depth: Some(1),
+            ..Config::default()
+        };
+        fs::write(&sub, "sub   \n").unwrap();
+        process_folder(&test_dir, &config, &Session::default()).unwrap();
+        assert_eq!(fs::read_to_string(&sub).unwrap(), "sub   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Skip Submodules Tests ====================
+
+    #[test]
+    fn test_skip_submodules_stops_at_submodule_boundaries() {
+        let test_dir = create_test_dir();
+        fs::create_dir(test_dir.join(".git")).unwrap();
+        fs::write(
+            test_dir.join(".gitmodules"),
+            "[submodule \"vendor\"]\n\tpath = libs/vendor\n\turl = ../vendor.git\n",
+        )
+        .unwrap();
+        let main_file = test_dir.join("main.txt");
+        fs::write(&main_file, "main   \n").unwrap();
+        // A checked-out submodule, recognized by its `.git` file.
+        let checked_out = test_dir.join("sub");
+        fs::create_dir(&checked_out).unwrap();
+        fs::write(checked_out.join(".git"), "gitdir: ../.git/modules/sub\n").unwrap();
+        fs::write(checked_out.join("lib.txt"), "lib   \n").unwrap();
+        // A submodule listed in .gitmodules below an ordinary directory.
+        let libs = test_dir.join("libs");
+        fs::create_dir_all(libs.join("vendor")).unwrap();
+        fs::write(libs.join("own.txt"), "own   \n").unwrap();
+        fs::write(libs.join("vendor").join("v.txt"), "v   \n").unwrap();
+
+        assert_eq!(
+            gitmodules_paths(&test_dir),
+            vec![test_dir.join("libs/vendor")]
+        );
+
+        let config = Config {
+            skip_submodules: true,
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &Session::default()).unwrap();
+
+        assert_eq!(fs::read_to_string(&main_file).unwrap(), "main\n");
+        assert_eq!(fs::read_to_string(libs.join("own.txt")).unwrap(), "own\n");
+        assert_eq!(
+            fs::read_to_string(libs.join("vendor").join("v.txt")).unwrap(),
+            "v   \n"
+        );
+        assert_eq!(
+            fs::read_to_string(checked_out.join("lib.txt")).unwrap(),
+            "lib   \n"
+        );
+
+        process_folder(&test_dir, &Config::default(), &Session::default()).unwrap();
+        assert_eq!(
+            fs::read_to_string(checked_out.join("lib.txt")).unwrap(),
+            "lib\n"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Backup Tests ====================
+
+    #[test]
+    fn test_backup_keeps_original_next_to_rewritten_file() {
+        let test_dir = create_test_dir();
+        let file = test_dir.join("notes.txt");
+        let clean = test_dir.join("clean.txt");
+        fs::write(&file, "keep me   \n").unwrap();
+        fs::write(&clean, "clean\n").unwrap();
+        let backup = test_dir.join("notes.txt.bak");
+        fs::write(&backup, "stale backup   \n").unwrap();
+        #[cfg(unix)]
+        fs::set_permissions(&file, Permissions::from_mode(0o640)).unwrap();
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            backup: true,
+            verbose: true,
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &session).unwrap();
+        session.finish();
+
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep me\n");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "keep me   \n");
+        #[cfg(unix)]
+        assert_eq!(
+            fs::metadata(&backup).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+        assert!(!test_dir.join("clean.txt.bak").exists());
+        assert!(!test_dir.join("notes.txt.bak.bak").exists());
+        assert!(out
+            .contents()
+            .contains(&format!("Backed up: {:?} -> {:?}", file, backup)));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_backup_not_written_in_check_mode() {
+        let test_dir = create_test_dir();
+        let file = test_dir.join("notes.txt");
+        fs::write(&file, "keep me   \n").unwrap();
+
+        let config = Config {
+            backup: true,
+            check: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            process_file(&file, &config, &Session::default()).unwrap(),
+            Outcome::Modified
+        );
+        assert_eq!(fs::read_to_string(&file).unwrap(), "keep me   \n");
+        assert!(!test_dir.join("notes.txt.bak").exists());
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Gitattributes EOL Tests ====================
+
+    #[test]
+    fn test_respect_gitattributes_eol() {
+        let test_dir = create_test_dir();
+        fs::create_dir(test_dir.join(".git")).unwrap();
+        fs::write(
+            test_dir.join(".gitattributes"),
+            "*.bat text eol=crlf\n*.txt eol=lf\n",
+        )
+        .unwrap();
+        let scripts = test_dir.join("scripts");
+        fs::create_dir(&scripts).unwrap();
+        fs::write(scripts.join(".gitattributes"), "legacy.txt eol=crlf\n").unwrap();
+        let files = [
+            ("run.bat", "echo   \r\nrem\n", "echo\r\nrem\r\n"),
+            ("clean.bat", "clean\r\n", "clean\r\n"),
+            ("notes.txt", "clean\r\n", "clean\n"),
+            ("scripts/legacy.txt", "old  \n", "old\r\n"),
+            ("readme.md", "md  \r\n", "md\n"),
+        ];
+        for (name, before, _) in files {
+            fs::write(test_dir.join(name), before).unwrap();
+        }
+
+        let config = Config {
+            respect_gitattributes_eol: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+        assert_eq!(session.stats().modified, 4);
+        for (name, _, after) in files {
+            assert_eq!(
+                fs::read_to_string(test_dir.join(name)).unwrap(),
+                after,
+                "{}",
+                name
+            );
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Journal Tests ====================
+
+    #[test]
+    fn test_rollback_after_crash_restores_originals() {
+        let test_dir = create_test_dir();
+        let first = test_dir.join("a.txt");
+        let second = test_dir.join("b.txt");
+        fs::write(&first, "first   \n").unwrap();
+        fs::write(&second, "second\t\n").unwrap();
+        let journal_path = test_dir.join("run.journal");
+
+        let config = Config {
+            mode: Mode::Paths,
+            paths: vec![first.clone(), second.clone()],
+            journal: Some(journal_path.clone()),
+            ..Config::default()
+        };
+        assert_eq!(run_silently(config), 0);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first\n");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second\n");
+
+        // Simulate a crash between the plan and the rename of b.txt: its
+        // `done` line is cut off and the original is still in place.
+        let journal = fs::read_to_string(&journal_path).unwrap();
+        let cut = journal.rfind("done").unwrap() + 3;
+        fs::write(&journal_path, &journal[..cut]).unwrap();
+        fs::write(&second, "second\t\n").unwrap();
+        let entries = journal::load(&journal_path).unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.done).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config::new(Mode::Rollback, journal_path.clone());
+        execute(&config, &session).unwrap();
+        assert_eq!(session.stats().modified, 1);
+        assert_eq!(session.stats().unchanged, 1);
+        session.finish();
+
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first   \n");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second\t\n");
+        assert_eq!(out.contents(), format!("  Restored: {:?}\n", first));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_rollback_refuses_a_changed_backup() {
+        let test_dir = create_test_dir();
+        let file = test_dir.join("a.txt");
+        fs::write(&file, "original   \n").unwrap();
+        let journal_path = test_dir.join("run.journal");
+        let config = Config {
+            journal: Some(journal_path.clone()),
+            ..Config::new(Mode::File, file.clone())
+        };
+        assert_eq!(run_silently(config), 0);
+        fs::write(test_dir.join("a.txt.bak"), "tampered\n").unwrap();
+
+        assert_eq!(run_silently(Config::new(Mode::Rollback, journal_path)), 1);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "original\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_journal_leaves_backups_alone_on_rerun() {
+        let test_dir = create_test_dir();
+        let tree = test_dir.join("tree");
+        fs::create_dir(&tree).unwrap();
+        let file = tree.join("a.txt");
+        fs::write(&file, "original   \n").unwrap();
+        let journal_path = test_dir.join("run.journal");
+        let config = || Config {
+            journal: Some(journal_path.clone()),
+            ..Config::new(Mode::Folder, tree.clone())
+        };
+        assert_eq!(run_silently(config()), 0);
+        assert_eq!(run_silently(config()), 0);
+
+        // The second run neither trims the backup nor backs it up in turn.
+        let backup = tree.join("a.txt.bak");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "original   \n");
+        assert!(!tree.join("a.txt.bak.bak").exists());
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_journal_backs_up_then_plans_then_renames() {
+        let test_dir = create_test_dir();
+        let file = test_dir.join("a.txt");
+        fs::write(&file, "original   \n").unwrap();
+        let journal_path = test_dir.join("run.journal");
+        let config = Config {
+            journal: Some(journal_path.clone()),
+            group_commit: Some(10),
+            ..Config::default()
+        };
+        let session = Session::default();
+        let _ = session.journal.set(Journal::create(&journal_path).unwrap());
+
+        // --group-commit holds the rename back: the backup and the plan are
+        // already in place, the new content is not.
+        process_file(&file, &config, &session).unwrap();
+        let backup = test_dir.join("a.txt.bak");
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "original   \n");
+        let journal = fs::read_to_string(&journal_path).unwrap();
+        assert!(journal.contains("plan\t") && !journal.contains("done\t"));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "original   \n");
+
+        assert_eq!(session.commit_group(&config), 0);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "original\n");
+        assert!(fs::read_to_string(&journal_path)
+            .unwrap()
+            .contains("done\t"));
+
+        // A backup that cannot be written stops the file before its plan.
+        let other = test_dir.join("b.txt");
+        fs::write(&other, "other   \n").unwrap();
+        fs::create_dir(test_dir.join("b.txt.bak")).unwrap();
+        fs::write(test_dir.join("b.txt.bak").join("inner"), "x").unwrap();
+        assert!(process_file(&other, &config, &session).is_err());
+        assert_eq!(session.commit_group(&config), 0);
+        assert_eq!(fs::read_to_string(&other).unwrap(), "other   \n");
+        let journal = fs::read_to_string(&journal_path).unwrap();
+        assert!(!journal.contains("b.txt"));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_rollback() {
+        let config = parse_config(&[
+            "rtrim".to_string(),
+            "--rollback".to_string(),
+            "run.journal".to_string(),
+        ])
+        .unwrap();
+        assert_eq!(config.mode, Mode::Rollback);
+        assert_eq!(config.path, PathBuf::from("run.journal"));
+        assert!(parse_config(&["rtrim".to_string(), "--rollback".to_string()]).is_err());
+    }
+
+    // ==================== Gitignore Tests ====================
+
+    #[test]
+    fn test_respect_gitignore_skips_ignored_paths() {
+        let test_dir = create_test_dir();
+        fs::create_dir(test_dir.join(".git")).unwrap();
+        fs::write(test_dir.join(".gitignore"), "dist/\n*.gen.txt\n").unwrap();
+        let src = test_dir.join("src");
+        fs::create_dir_all(src.join("proto")).unwrap();
+        fs::write(src.join(".gitignore"), "proto/\n!keep.gen.txt\n").unwrap();
+        fs::create_dir(test_dir.join("dist")).unwrap();
+        let files = [
+            ("main.txt", "main   \n"),
+            ("dist/bundle.txt", "bundle   \n"),
+            ("a.gen.txt", "gen   \n"),
+            ("src/lib.txt", "lib   \n"),
+            ("src/proto/msg.txt", "msg   \n"),
+            ("src/keep.gen.txt", "keep   \n"),
+        ];
+        for (name, content) in files {
+            fs::write(test_dir.join(name), content).unwrap();
+        }
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            respect_gitignore: true,
+            verbose: true,
+            threads: Some(Threads::Count(1)),
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &session).unwrap();
+        assert_eq!(session.stats().skipped, 1);
+        session.finish();
+
+        let read = |name: &str| fs::read_to_string(test_dir.join(name)).unwrap();
+        assert_eq!(read("main.txt"), "main\n");
+        assert_eq!(read("src/lib.txt"), "lib\n");
+        assert_eq!(read("src/keep.gen.txt"), "keep\n");
+        assert_eq!(read("dist/bundle.txt"), "bundle   \n");
+        assert_eq!(read("a.gen.txt"), "gen   \n");
+        assert_eq!(read("src/proto/msg.txt"), "msg   \n");
+        assert!(out
+            .contents()
+            .contains(&format!("Skipped (gitignore): {:?}", test_dir.join("dist"))));
+
+        // Walks below the repository root honor the .gitignore files above.
+        fs::write(src.join("x.gen.txt"), "x   \n").unwrap();
+        process_folder(&src, &config, &Session::default()).unwrap();
+        assert_eq!(read("src/x.gen.txt"), "x   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== EditorConfig Tests ====================
+
+    #[test]
+    fn test_editorconfig_selects_files_to_trim() {
+        let test_dir = create_test_dir();
+        // Above the root file, so never read.
+        fs::write(
+            test_dir.join(".editorconfig"),
+            "[*]\ntrim_trailing_whitespace = false\n",
+        )
+        .unwrap();
+        let project = test_dir.join("project");
+        let docs = project.join("docs");
+        fs::create_dir_all(&docs).unwrap();
+        fs::write(
+            project.join(".editorconfig"),
+            "root = true\n\n[*]\ntrim_trailing_whitespace = true\n\n\
+             [*.md]\ntrim_trailing_whitespace = false\n\n\
+             [*.{c,h}]\ninsert_final_newline = true\n",
+        )
+        .unwrap();
+        fs::write(
+            docs.join(".editorconfig"),
+            "[keep.txt]\ntrim_trailing_whitespace = unset\n",
+        )
+        .unwrap();
+        let write = |name: &str, content: &str| {
+            let path = project.join(name);
+            fs::write(&path, content).unwrap();
+            path
+        };
+        let text = write("notes.txt", "a  \nb");
+        let markdown = write("docs/README.md", "line break  \n");
+        let unset = write("docs/keep.txt", "keep  \n");
+        let source = write("main.c", "int x;  ");
+
+        let args = vec![
+            "rtrim".to_string(),
+            "--editorconfig".to_string(),
+            ".".to_string(),
+        ];
+        assert!(parse_config(&args).unwrap().editorconfig);
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            editorconfig: true,
+            verbose: true,
+            threads: Some(Threads::Count(1)),
+            ..Config::default()
+        };
+        process_folder(&project, &config, &session).unwrap();
+        session.finish();
+
+        let read = |path: &Path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(&text), "a\nb");
+        assert_eq!(read(&markdown), "line break  \n");
+        assert_eq!(read(&unset), "keep  \n");
+        assert_eq!(read(&source), "int x;\n");
+        let output = out.contents();
+        assert!(output.contains(&format!("Skipped (editorconfig): {:?}", markdown)));
+        assert!(output.contains(&format!("Skipped (editorconfig): {:?}", unset)));
+        assert_eq!(
+            process_file(&markdown, &config, &Session::default()).unwrap(),
+            Outcome::Skipped(SkipReason::EditorConfig)
+        );
+
+        // Without a root file, nothing declares trimming outside the tree.
+        let outside = test_dir.join("outside.txt");
+        fs::write(&outside, "x  \n").unwrap();
+        assert_eq!(
+            process_file(&outside, &config, &Session::default()).unwrap(),
+            Outcome::Skipped(SkipReason::EditorConfig)
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Since Commit Tests ====================
+
+    #[test]
+    fn test_since_commit_only_processes_changed_files() {
+        let test_dir = create_test_dir();
+        let changed = test_dir.join("changed.txt");
+        let untouched = test_dir.join("untouched.txt");
+        fs::write(&changed, "changed   \n").unwrap();
+        fs::write(&untouched, "untouched   \n").unwrap();
+
+        let args: Vec<String> = ["rtrim", "--since-commit", "main", "--folder", "."]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            parse_config(&args).unwrap().since_commit.as_deref(),
+            Some("main")
+        );
+
+        // The change set `git diff --name-only` would have produced.
+        let session = Session::default();
+        let _ = session
+            .changed
+            .set(HashSet::from([fs::canonicalize(&changed).unwrap()]));
+        process_folder(&test_dir, &Config::default(), &session).unwrap();
+
+        assert_eq!(fs::read_to_string(&changed).unwrap(), "changed\n");
+        assert_eq!(fs::read_to_string(&untouched).unwrap(), "untouched   \n");
+        assert_eq!(
+            process_file(&untouched, &Config::default(), &session).unwrap(),
+            Outcome::Skipped(SkipReason::UnchangedSince)
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Staged Tests ====================
+
+    #[test]
+    fn test_staged_only_processes_staged_files() {
+        let test_dir = create_test_dir();
+        let staged = test_dir.join("staged.txt");
+        let unstaged = test_dir.join("unstaged.txt");
+        fs::write(&staged, "staged   \n").unwrap();
+        fs::write(&unstaged, "unstaged   \n").unwrap();
+
+        let args: Vec<String> = ["rtrim", "--staged", "--folder", "."]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(parse_config(&args).unwrap().staged);
+
+        // The file set `git diff --cached --name-only` would have produced.
+        let session = Session::default();
+        let _ = session
+            .staged
+            .set(HashSet::from([fs::canonicalize(&staged).unwrap()]));
+        process_folder(&test_dir, &Config::default(), &session).unwrap();
+
+        assert_eq!(fs::read_to_string(&staged).unwrap(), "staged\n");
+        assert_eq!(fs::read_to_string(&unstaged).unwrap(), "unstaged   \n");
+        assert_eq!(
+            process_file(&unstaged, &Config::default(), &session).unwrap(),
+            Outcome::Skipped(SkipReason::NotStaged)
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_staged_skips_files_with_unstaged_changes() {
+        let test_dir = create_test_dir();
+        let partial = test_dir.join("partial.txt");
+        fs::write(
+            &partial,
+            "staged   
+unstaged   
+",
+        )
+        .unwrap();
+
+        // Staged, but `git diff --name-only` also lists it.
+        let session = Session::default();
+        let canonical = fs::canonicalize(&partial).unwrap();
+        let _ = session.staged.set(HashSet::from([canonical.clone()]));
+        let _ = session.partially_staged.set(HashSet::from([canonical]));
+
+        assert_eq!(
+            process_file(&partial, &Config::default(), &session).unwrap(),
+            Outcome::Skipped(SkipReason::PartiallyStaged)
+        );
+        assert_eq!(
+            fs::read_to_string(&partial).unwrap(),
+            "staged   \nunstaged   \n"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_staged_conflicts_with_since_commit() {
+        let args: Vec<String> = ["rtrim", "--staged", "--since-commit", "main", "."]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(parse_config(&args).unwrap_err().contains("--staged"));
+    }
+
+    // ==================== Preview Tests ====================
+
+    #[test]
+    fn test_parse_config_preview() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--preview".to_string(),
+            "3".to_string(),
+            "--folder".to_string(),
+            ".".to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.preview, Some(3));
+        assert!(!config.writes_files());
+    }
+
+    #[test]
+    fn test_preview_stops_after_n_files() {
+        let test_dir = create_test_dir();
+        let files: Vec<PathBuf> = (0..5)
+            .map(|i| {
+                let file = test_dir.join(format!("file{}.txt", i));
+                fs::write(&file, "dirty   \n").unwrap();
+                file
+            })
+            .collect();
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            preview: Some(2),
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &session).unwrap();
+        assert!(session.stopped(&config));
+        assert_eq!(session.previews.load(Ordering::Relaxed), 2);
+        session.finish();
+
+        let output = out.contents();
+        assert_eq!(output.matches("\n+++ ").count(), 2);
+        assert!(output.contains("-dirty   \n+dirty\n"));
+        for file in &files {
+            assert_eq!(fs::read_to_string(file).unwrap(), "dirty   \n");
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Porcelain Tests ====================
+
+    #[test]
+    fn test_parse_config_porcelain_rejects_verbose() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--porcelain".to_string(),
+            "--verbose".to_string(),
+            "--folder".to_string(),
+            ".".to_string(),
+        ];
+        assert!(parse_config(&args).is_err());
+    }
+
+    #[test]
+    fn test_porcelain_lines_for_mixed_tree() {
+        let test_dir = create_test_dir();
+        fs::write(test_dir.join("dirty.txt"), "dirty   \n").unwrap();
+        fs::write(test_dir.join("clean.txt"), "clean\n").unwrap();
+        fs::write(test_dir.join("data.bin"), [0xFF, 0xFE, 0x00]).unwrap();
+        std::os::unix::fs::symlink(test_dir.join("clean.txt"), test_dir.join("link.txt")).unwrap();
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            porcelain: true,
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &session).unwrap();
+        session.finish();
+
+        let output = out.contents();
+        let mut lines: Vec<&str> = output.lines().collect();
+        lines.sort_unstable();
+        let dir = test_dir.display();
+        assert_eq!(
+            lines,
+            vec![
+                format!("Bb\t{}/data.bin", dir),
+                format!("M\t{}/dirty.txt", dir),
+                format!("Sl\t{}/link.txt", dir),
+                format!("U\t{}/clean.txt", dir),
+            ]
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== JSON Format Tests ====================
+
+    #[test]
+    fn test_format_json_records_for_mixed_tree() {
+        let test_dir = create_test_dir();
+        fs::write(test_dir.join("dirty.txt"), "a  \nb\t\nc\n").unwrap();
+        fs::write(test_dir.join("clean.txt"), "clean\n").unwrap();
+        fs::write(test_dir.join("data.bin"), [0xFF, 0xFE, 0x00]).unwrap();
+
+        let args: Vec<String> = ["rtrim", "--format", "json", "--folder"]
+            .iter()
+            .map(|s| s.to_string())
+            .chain([test_dir.display().to_string()])
+            .collect();
+        let config = parse_config(&args).unwrap();
+        let out = SharedBuf::default();
+        run(&config, Box::new(out.clone()), Box::new(io::sink()));
+
+        let output = out.contents();
+        let mut lines: Vec<&str> = output.lines().collect();
+        let summary = lines.pop().unwrap();
+        lines.sort_unstable();
+        let path = |name: &str| json::encode_string(&test_dir.join(name).to_string_lossy());
+        assert_eq!(
+            lines,
+            vec![
+                format!(
+                    "{{\"type\": \"file\", \"path\": {}, \"status\": \"unchanged\", \"lines\": 0}}",
+                    path("clean.txt")
+                ),
+                format!(
+                    "{{\"type\": \"file\", \"path\": {}, \"status\": \"skipped\", \"reason\": \"binary\"}}",
+                    path("data.bin")
+                ),
+                format!(
+                    "{{\"type\": \"file\", \"path\": {}, \"status\": \"modified\", \"lines\": 2}}",
+                    path("dirty.txt")
+                ),
+            ]
+        );
+        assert_eq!(
+            summary,
+            "{\"type\": \"summary\", \"scanned\": 3, \"modified\": 1, \"unchanged\": 1, \
+             \"binary\": 1, \"symlinks\": 0, \"skipped\": 0, \"failed\": 0}"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_format_json_counts_lines_of_followed_symlink() {
+        let test_dir = create_test_dir();
+        let target = test_dir.join("target.txt");
+        let link = test_dir.join("link.txt");
+        fs::write(&target, "a  \nb\t\n").unwrap();
+        std::os::unix::fs::symlink("target.txt", &link).unwrap();
+
+        let config = Config {
+            json_output: true,
+            follow_symlinks: true,
+            ..Config::new(Mode::File, link.clone())
+        };
+        let out = SharedBuf::default();
+        run(&config, Box::new(out.clone()), Box::new(io::sink()));
+
+        assert_eq!(
+            out.contents().lines().next().unwrap(),
+            format!(
+                "{{\"type\": \"file\", \"path\": {}, \"status\": \"modified\", \"lines\": 2}}",
+                json::encode_string(&link.to_string_lossy())
+            )
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_format_json_reports_errors_as_records() {
+        let test_dir = create_test_dir();
+        let missing = test_dir.join("missing.txt");
+        let args = vec![
+            "rtrim".to_string(),
+            "--format".to_string(),
+            "json".to_string(),
+            missing.display().to_string(),
+        ];
+        let config = parse_config(&args).unwrap();
+        let (out, err) = (SharedBuf::default(), SharedBuf::default());
+        assert_eq!(
+            run(&config, Box::new(out.clone()), Box::new(err.clone())),
+            1
+        );
+
+        let output = out.contents();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(&format!(
+            "{{\"type\": \"file\", \"path\": {}, \"status\": \"error\", \"error\": ",
+            json::encode_string(&missing.to_string_lossy())
+        )));
+        assert!(lines[1].starts_with("{\"type\": \"summary\""));
+        assert_eq!(err.contents(), "");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_format() {
+        let parse = |extra: &[&str]| {
+            let args: Vec<String> = ["rtrim"]
+                .iter()
+                .chain(extra)
+                .map(|s| s.to_string())
+                .collect();
+            parse_config(&args)
+        };
+        assert!(parse(&["--format", "json", "."]).unwrap().json_output);
+        assert!(!parse(&["--format", "text", "."]).unwrap().json_output);
+        assert!(parse(&["--format", "yaml", "."]).is_err());
+        assert!(parse(&["--format", "json", "--porcelain", "."]).is_err());
+        assert!(parse(&["--format", "json", "-"]).is_err());
+        assert!(parse(&["--format", "json", "--preview", "3", "."]).is_err());
+        assert!(parse(&["--format", "json", "--report-age", "."]).is_err());
+        assert!(parse(&["--format", "json", "--report-bom", "."]).is_err());
+    }
+
+    // ==================== Notebook Tests ====================
+
+    #[cfg(feature = "ipynb")]
+    const NOTEBOOK: &str = r#"{
+ "cells": [
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {"tags": ["keep  "]},
+   "outputs": [{"name": "stdout", "output_type": "stream", "text": ["out  \n"]}],
+   "source": ["x = 1  \n", "print(x)\t"]
+  },
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": ["Two spaces  \n", "is a line break"]
+  }
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"#;
+
+    #[cfg(feature = "ipynb")]
+    #[test]
+    fn test_ipynb_trims_only_code_cell_sources() {
+        let config = Config {
+            ipynb: true,
+            ..Config::default()
+        };
+        let result = trim_for_config(Path::new("analysis.ipynb"), NOTEBOOK, &config).unwrap();
+        assert!(result.modified);
+        assert_eq!(
+            result.content,
+            NOTEBOOK.replace(
+                r#"["x = 1  \n", "print(x)\t"]"#,
+                r#"["x = 1\n", "print(x)"]"#
+            )
+        );
+        assert_eq!(result.removed.spaces, 2);
+        assert_eq!(result.removed.tabs, 1);
+    }
+
+    #[cfg(feature = "ipynb")]
+    #[test]
+    fn test_ipynb_process_file_and_malformed_notebook() {
+        let test_dir = create_test_dir();
+        let config = Config {
+            ipynb: true,
+            ..Config::default()
+        };
+
+        let good = test_dir.join("good.ipynb");
+        fs::write(&good, NOTEBOOK).unwrap();
+        let outcome = process_file(&good, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Modified);
+        assert!(fs::read_to_string(&good)
+            .unwrap()
+            .contains(r#""text": ["out  \n"]"#));
+
+        let bad = test_dir.join("bad.ipynb");
+        fs::write(&bad, "{\"cells\": [  \n").unwrap();
+        let outcome = process_file(&bad, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::MalformedNotebook));
+        assert_eq!(fs::read_to_string(&bad).unwrap(), "{\"cells\": [  \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== EOF Newline Report Tests ====================
+
+    #[test]
+    fn test_eof_newline_report_classifies_files() {
+        let test_dir = create_test_dir();
+        let with_newline = test_dir.join("a.txt");
+        let without_newline = test_dir.join("b.txt");
+        fs::write(&with_newline, "ends well   \n").unwrap();
+        fs::write(&without_newline, "no newline   ").unwrap();
+        fs::write(test_dir.join("empty.txt"), "").unwrap();
+
+        let config = Config {
+            eof_newline_report: true,
+            ..Config::default()
+        };
+        assert!(!config.writes_files());
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+
+        let eof = session.eof_newlines.lock().unwrap();
+        assert_eq!(eof.with_newline, vec![with_newline.clone()]);
+        assert_eq!(eof.without_newline, vec![without_newline.clone()]);
+        assert_eq!(fs::read_to_string(&with_newline).unwrap(), "ends well   \n");
+        assert_eq!(
+            fs::read_to_string(&without_newline).unwrap(),
+            "no newline   "
+        );
+        drop(eof);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== BOM Report Tests ====================
+
+    #[test]
+    fn test_bom_report_classifies_files() {
+        let test_dir = create_test_dir();
+        let utf8 = test_dir.join("utf8.txt");
+        let utf16_le = test_dir.join("le.txt");
+        let utf16_be = test_dir.join("be.txt");
+        fs::write(&utf8, b"\xEF\xBB\xBFtext   \n").unwrap();
+        fs::write(&utf16_le, b"\xFF\xFEa\x00 \x00").unwrap();
+        fs::write(&utf16_be, b"\xFE\xFF\x00a\x00 ").unwrap();
+        fs::write(test_dir.join("plain.txt"), "plain   \n").unwrap();
+        fs::write(test_dir.join("empty.txt"), "").unwrap();
+
+        let config = Config {
+            bom_report: true,
+            ..Config::default()
+        };
+        assert!(!config.writes_files());
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+
+        let boms = session.boms.lock().unwrap();
+        assert_eq!(boms.utf8, vec![utf8.clone()]);
+        assert_eq!(boms.utf16_le, vec![utf16_le]);
+        assert_eq!(boms.utf16_be, vec![utf16_be]);
+        assert_eq!(boms.without_bom, 2);
+        assert_eq!(fs::read(&utf8).unwrap(), b"\xEF\xBB\xBFtext   \n".to_vec());
+        drop(boms);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Age Report Tests ====================
+
+    #[test]
+    fn test_age_report_buckets_files_by_mtime() {
+        let test_dir = create_test_dir();
+        let day = std::time::Duration::from_secs(24 * 60 * 60);
+        let now = SystemTime::now();
+        for (name, content, age) in [
+            ("fresh.txt", "a   \nb  \n", std::time::Duration::ZERO),
+            ("week.txt", "clean\n", day * 3),
+            ("month.txt", "c\t\n", day * 20),
+            ("old.txt", "d \ne \nf \n", day * 400),
+            ("old_clean.txt", "g\n", day * 400),
+        ] {
+            let path = test_dir.join(name);
+            fs::write(&path, content).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - age)
+                .unwrap();
+        }
+
+        let config = Config {
+            age_report: true,
+            ..Config::default()
+        };
+        assert!(!config.writes_files());
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+
+        let ages = *session.ages.lock().unwrap();
+        assert_eq!(ages.files, [1, 1, 1, 2]);
+        assert_eq!(ages.dirty_files, [1, 0, 1, 1]);
+        assert_eq!(ages.dirty_lines, [2, 0, 1, 3]);
+        assert_eq!(
+            fs::read_to_string(test_dir.join("fresh.txt")).unwrap(),
+            "a   \nb  \n"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_age_bucket_boundaries() {
+        let now = SystemTime::now();
+        let hours = |h: u64| now - std::time::Duration::from_secs(h * 60 * 60);
+        assert_eq!(
+            AgeBucket::of(now + std::time::Duration::from_secs(60), now),
+            AgeBucket::Day
+        );
+        assert_eq!(AgeBucket::of(hours(23), now), AgeBucket::Day);
+        assert_eq!(AgeBucket::of(hours(24), now), AgeBucket::Week);
+        assert_eq!(AgeBucket::of(hours(7 * 24), now), AgeBucket::Month);
+        assert_eq!(AgeBucket::of(hours(30 * 24), now), AgeBucket::Older);
+    }
+
+    // ==================== Longest Lines Report Tests ====================
+
+    #[test]
+    fn test_longest_lines_report_finds_longest_line() {
+        let test_dir = create_test_dir();
+        let minified = test_dir.join("app.min.js");
+        let source = test_dir.join("app.js");
+        fs::write(&minified, format!("/* v1 */\n{}\n", "x;".repeat(500))).unwrap();
+        fs::write(&source, "let a = 1;   \nlet longer = 2;\n").unwrap();
+        fs::write(test_dir.join("empty.txt"), "").unwrap();
+
+        let config = Config {
+            longest_lines_report: Some(1),
+            ..Config::default()
+        };
+        assert!(!config.writes_files());
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+
+        let mut longest = session.longest_lines.lock().unwrap().clone();
+        longest.sort_by_key(|entry| std::cmp::Reverse(entry.length));
+        assert_eq!(
+            longest,
+            vec![
+                LongestLine {
+                    length: 1000,
+                    line: 2,
+                    path: minified,
+                },
+                LongestLine {
+                    length: 15,
+                    line: 2,
+                    path: source.clone(),
+                },
+            ]
+        );
+        assert_eq!(
+            fs::read_to_string(&source).unwrap(),
+            "let a = 1;   \nlet longer = 2;\n"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Lossy Tests ====================
+
+    #[test]
+    fn test_lossy_trims_file_with_invalid_byte() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("almost_text.txt");
+        fs::write(&test_file, b"caf\xE9   \nok  \n").unwrap();
+
+        let outcome = process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::Binary));
+
+        let (reporter, _out, err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            lossy: true,
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &session).unwrap();
+        session.finish();
+
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "caf\u{FFFD}\nok\n");
+        assert!(err.contents().contains("--lossy replaces invalid bytes"));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_lossy_still_skips_files_with_nul_bytes() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("image.bin");
+        fs::write(&test_file, b"\x89PNG\x00\xFF  \n").unwrap();
+
+        let config = Config {
+            lossy: true,
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::Binary));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Encoding Tests ====================
+
+    #[test]
+    fn test_latin1_trims_single_byte_text_bytewise() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("legacy.txt");
+        // 0xE9 is "é" in Latin-1; 0xA0 (no-break space) and 0x85 ("…" in
+        // Windows-1252) are text, not whitespace to strip.
+        fs::write(&test_file, b"caf\xE9   \r\nend\xA0\t\nmore\x85 \n").unwrap();
+
+        let config = Config {
+            encoding: Encoding::Latin1,
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(
+            fs::read(&test_file).unwrap(),
+            b"caf\xE9\nend\xA0\nmore\x85\n".to_vec()
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_binary_detection_uses_nul_and_control_bytes() {
+        assert!(looks_binary(b"text\x00more"));
+        assert!(looks_binary(b"\x01\x02\x03ab"));
+        assert!(!looks_binary(b"caf\xE9   \n"));
+        assert!(!looks_binary(b"\x1b[31mred\x1b[0m\n"));
+        assert!(!looks_binary(b""));
+
+        let test_dir = create_test_dir();
+        let config = Config {
+            encoding: Encoding::Latin1,
+            ..Config::default()
+        };
+        for (name, content) in [
+            ("image.bin", &b"\x89PNG\x00\xFF  \n"[..]),
+            ("utf16.txt", &b"a\x00 \x00\n\x00"[..]),
+            ("controls.dat", &b"\x01\x02\x03\x04\xFF  \n"[..]),
+        ] {
+            let path = test_dir.join(name);
+            fs::write(&path, content).unwrap();
+            let outcome = process_file(&path, &config, &Session::default()).unwrap();
+            assert_eq!(outcome, Outcome::Skipped(SkipReason::Binary), "{}", name);
+            assert_eq!(fs::read(&path).unwrap(), content);
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_encoding() {
+        let parse = |extra: &[&str]| {
+            let mut args = vec!["rtrim".to_string()];
+            args.extend(extra.iter().map(|s| s.to_string()));
+            args.extend(["--folder".to_string(), ".".to_string()]);
+            parse_config(&args)
+        };
+        assert_eq!(
+            parse(&["--encoding", "latin1"]).unwrap().encoding,
+            Encoding::Latin1
+        );
+        assert_eq!(parse(&[]).unwrap().encoding, Encoding::Utf8);
+        assert!(parse(&["--encoding", "ebcdic"]).is_err());
+        assert!(parse(&["--encoding", "latin1", "--lossy"]).is_err());
+    }
+
+    // ==================== Hash Cache Tests ====================
+
+    #[test]
+    fn test_hash_cache_skips_known_clean_content_across_runs() {
+        let test_dir = create_test_dir();
+        let cache_file = test_dir.join("rtrim.cache");
+        let src_dir = test_dir.join("src");
+        fs::create_dir(&src_dir).unwrap();
+        let clean = src_dir.join("clean.txt");
+        let dirty = src_dir.join("dirty.txt");
+        fs::write(&clean, "clean\n").unwrap();
+        fs::write(&dirty, "dirty   \n").unwrap();
+
+        let config = Config {
+            hash_cache: Some(cache_file.clone()),
+            verbose: true,
+            ..Config::default()
+        };
+        let run_once = |config: &Config| {
+            let (reporter, out, _err) = capturing_reporter(None);
+            let session = Session::new(reporter);
+            *session.clean_hashes.lock().unwrap() = cache::load(&cache_file).unwrap();
+            process_folder(&src_dir, config, &session).unwrap();
+            cache::save(&cache_file, &session.clean_hashes.lock().unwrap()).unwrap();
+            session.finish();
+            out.contents()
+        };
+
+        // First run: nothing cached yet; the dirty file is trimmed and both
+        // resulting contents are recorded as clean.
+        let first = run_once(&config);
+        assert!(!first.contains("(cached)"));
+        assert_eq!(fs::read_to_string(&dirty).unwrap(), "dirty\n");
+        assert_eq!(cache::load(&cache_file).unwrap().len(), 2);
+
+        // Second run: both files hit the cache.
+        let second = run_once(&config);
+        assert_eq!(second.matches("Unchanged (cached)").count(), 2);
+
+        // Changing the content invalidates the entry through a new hash.
+        fs::write(&clean, "changed  \n").unwrap();
+        let third = run_once(&config);
+        assert_eq!(third.matches("Unchanged (cached)").count(), 1);
+        assert_eq!(fs::read_to_string(&clean).unwrap(), "changed\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== URL Tests ====================
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_parse_config_refuses_to_modify_url() {
+        let args = vec![
+            "rtrim".to_string(),
+            "--file".to_string(),
+            "http://example.com/a.txt".to_string(),
+        ];
+        assert!(parse_config(&args).is_err());
+
+        let mut report = args.clone();
+        report.push("--report-only".to_string());
+        let config = parse_config(&report).unwrap();
+        assert!(is_url(&config.path));
+        assert!(!config.writes_files());
+    }
+
+    /// Serves a single canned HTTP response on a local port.
+    #[cfg(feature = "http")]
+    fn mock_http_server(response: &'static str) -> (String, JoinHandle<String>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/remote.txt", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                request.push_str(&line);
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+            }
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request
+        });
+        (url, server)
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_run_url_reports_remote_whitespace() {
+        let (url, server) = mock_http_server(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 14\r\n\r\nspaces  \ntab\t\n",
+        );
+        let config = Config {
+            report_trailing_types: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        run_url(Path::new(&url), &config, &session).unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /remote.txt HTTP/1.1\r\n"));
+        assert!(session.modified.load(Ordering::Relaxed));
+        assert_eq!(
+            *session.removed.lock().unwrap(),
+            WhitespaceBreakdown {
+                spaces: 2,
+                tabs: 1,
+                other: 0,
+                lines: 2,
+            }
+        );
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_run_url_fails_on_error_status() {
+        let (url, server) = mock_http_server("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+        let config = Config {
+            report_trailing_types: true,
+            ..Config::default()
+        };
+        let err = run_url(Path::new(&url), &config, &Session::default()).unwrap_err();
+        assert!(err.to_string().contains("HTTP status 404"));
+        server.join().unwrap();
+    }
+
+    // ==================== Trim Report Granularity Tests ====================
+
+    #[test]
+    fn test_parse_config_trim_report() {
+        let parse = |flag: &str| {
+            parse_config(&[
+                "rtrim".to_string(),
+                flag.to_string(),
+                "--folder".to_string(),
+                ".".to_string(),
+            ])
+        };
+        assert_eq!(
+            parse("--trim-report=lines").unwrap().trim_report,
+            Some(TrimReport::Lines)
+        );
+        assert_eq!(
+            parse("--trim-report=chars").unwrap().trim_report,
+            Some(TrimReport::Chars)
+        );
+        assert_eq!(
+            parse("--trim-report=both").unwrap().trim_report,
+            Some(TrimReport::Both)
+        );
+        assert!(parse("--trim-report=words").is_err());
+        assert_eq!(parse("--verbose").unwrap().trim_report, None);
+    }
+
+    #[test]
+    fn test_trim_report_granularity_in_summary() {
+        let removed = trim_trailing_whitespace("a  \nb\t\nc\nd \u{a0}\n").removed;
+        assert_eq!(removed.describe(TrimReport::Lines), "3 lines");
+        assert_eq!(
+            removed.describe(TrimReport::Chars),
+            "3 spaces, 1 tabs, 1 other (5 total)"
+        );
+        assert_eq!(
+            removed.describe(TrimReport::Both),
+            "3 lines, 3 spaces, 1 tabs, 1 other (5 total)"
+        );
+    }
+
+    #[test]
+    fn test_trim_report_adds_per_file_counts() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("counts.txt");
+        fs::write(&test_file, "a  \nb\t\n").unwrap();
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            trim_report: Some(TrimReport::Lines),
+            ..Config::default()
+        };
+        process_file(&test_file, &config, &session).unwrap();
+        session.finish();
+
+        assert_eq!(
+            out.contents(),
+            format!("  Processed: {:?} [2 lines]\n", test_file)
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Group Commit Tests ====================
+
+    #[test]
+    fn test_group_commit_renames_in_groups() {
+        let test_dir = create_test_dir();
+        let names = ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"];
+        for name in names {
+            fs::write(test_dir.join(name), format!("{}   \n", name)).unwrap();
+        }
+
+        let config = Config {
+            group_commit: Some(2),
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_folder(&test_dir, &config, &session).unwrap();
+
+        // Two full groups were committed; the fifth file is still staged.
+        let staged = session.group.lock().unwrap()[0].target.clone();
+        assert_eq!(fs::read_to_string(&staged).unwrap().len(), 9);
+        assert_eq!(session.commit_group(&config), 0);
+
+        let mut entries: Vec<_> = fs::read_dir(&test_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, names.map(OsStr::new));
+        for name in names {
+            assert_eq!(
+                fs::read_to_string(test_dir.join(name)).unwrap(),
+                format!("{}\n", name)
+            );
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_group_commit_finishes_files_after_the_rename() {
+        let test_dir = create_test_dir();
+        let file = test_dir.join("a.txt");
+        fs::write(&file, "a   \n").unwrap();
+
+        let config = Config {
+            group_commit: Some(10),
+            hash_cache: Some(test_dir.join("cache")),
+            ..Config::default()
+        };
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        assert_eq!(
+            process_file(&file, &config, &session).unwrap(),
+            Outcome::Modified
+        );
+        // Not renamed yet, so not known to be clean either.
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a   \n");
+        assert!(session.clean_hashes.lock().unwrap().is_empty());
+
+        assert_eq!(session.commit_group(&config), 0);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "a\n");
+        assert_eq!(session.clean_hashes.lock().unwrap().len(), 1);
+        session.finish();
+        assert_eq!(out.contents(), format!("  Processed: {:?}\n", file));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    /// Times per-file renames against `--group-commit` on many tiny files.
+    /// Run with `cargo test --release bench_group_commit -- --ignored
+    /// --nocapture`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_group_commit() {
+        const FILES: usize = 2000;
+        for group_commit in [None, Some(256)] {
+            let test_dir = create_test_dir();
+            for i in 0..FILES {
+                fs::write(test_dir.join(format!("{}.txt", i)), "dirty   \n").unwrap();
+            }
+            let config = Config {
+                group_commit,
+                ..Config::new(Mode::Folder, test_dir.clone())
+            };
+            let start = std::time::Instant::now();
+            assert_eq!(run_silently(config), 0);
+            println!(
+                "--group-commit {:?}: {} files in {:?}",
+                group_commit,
+                FILES,
+                start.elapsed()
+            );
+            cleanup_test_dir(&test_dir);
+        }
+    }
+
+    #[test]
+    fn test_group_commit_failure_leaves_no_temp_files() {
+        let test_dir = create_test_dir();
+        let good = test_dir.join("good.txt");
+        fs::write(&good, "good  \n").unwrap();
+        // Renaming a file over a non-empty directory fails.
+        let bad = test_dir.join("bad");
+        fs::create_dir(&bad).unwrap();
+        fs::write(bad.join("inner.txt"), "x").unwrap();
+
+        let config = Config::default();
+        let session = Session::default();
+        for (target, content) in [(&bad, "bad\n"), (&good, "good\n")] {
+            let staged = StagedWrite {
+                temp: write_temp_content(target, content.as_bytes(), None).unwrap(),
+                target: target.to_path_buf(),
+                counts: String::new(),
+                clean_hash: None,
+            };
+            session.stage(staged, 10, &config);
+        }
+        assert_eq!(session.commit_group(&config), 1);
+        assert_eq!(session.stats().failed, 1);
+
+        assert_eq!(fs::read_to_string(&good).unwrap(), "good\n");
+        let mut entries: Vec<_> = fs::read_dir(&test_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, vec![OsStr::new("bad"), OsStr::new("good.txt")]);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_group_commit_conflicts() {
+        let parse = |flags: &[&str]| {
+            let mut args = vec!["rtrim".to_string()];
+            args.extend(flags.iter().map(|flag| flag.to_string()));
+            args.extend(["--folder".to_string(), ".".to_string()]);
+            parse_config(&args)
+        };
+        assert_eq!(
+            parse(&["--group-commit", "8"]).unwrap().group_commit,
+            Some(8)
+        );
+        assert!(parse(&["--group-commit", "0"]).is_err());
+        assert!(parse(&["--group-commit", "8", "--keep-inode"]).is_err());
+        assert!(parse(&["--group-commit", "8", "--on-modify-exec", "true"]).is_err());
+    }
+
+    // ==================== Rustfmt Tests ====================
+
+    fn rustfmt_available() -> bool {
+        Command::new("rustfmt")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    #[test]
+    fn test_with_rustfmt_formats_messy_rust_file() {
+        if !rustfmt_available() {
+            return;
+        }
+        let test_dir = create_test_dir();
+        let messy = test_dir.join("messy.rs");
+        let broken = test_dir.join("broken.rs");
+        fs::write(
+            &messy,
+            "fn  main( ) {   \n  let x=1;\t\n    println!(\"{}\",x);   \n}\n",
+        )
+        .unwrap();
+        fs::write(&broken, "fn main( {   \n").unwrap();
+
+        let config = Config {
+            with_rustfmt: true,
+            ..Config::default()
+        };
+        let session = Session::default();
+        assert_eq!(
+            process_file(&messy, &config, &session).unwrap(),
+            Outcome::Modified
+        );
+        assert_eq!(
+            process_file(&broken, &config, &session).unwrap(),
+            Outcome::Modified
+        );
+
+        assert_eq!(
+            fs::read_to_string(&messy).unwrap(),
+            "fn main() {\n    let x = 1;\n    println!(\"{}\", x);\n}\n"
+        );
+        // rustfmt rejects the file, so it is only trimmed.
+        assert_eq!(fs::read_to_string(&broken).unwrap(), "fn main( {\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_with_rustfmt_ignores_other_files() {
+        let config = Config {
+            with_rustfmt: true,
+            ..Config::default()
+        };
+        let test_dir = create_test_dir();
+        let notes = test_dir.join("notes.txt");
+        fs::write(&notes, "fn  main( ) {}  \n").unwrap();
+
+        process_file(&notes, &config, &Session::default()).unwrap();
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "fn  main( ) {}\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Max Changed Fraction Tests ====================
+
+    #[test]
+    fn test_max_changed_fraction_skips_fully_dirty_file() {
+        let test_dir = create_test_dir();
+        let dirty = test_dir.join("dirty.txt");
+        let mostly_clean = test_dir.join("mostly_clean.txt");
+        fs::write(&dirty, "a  \nb\t\nc \nd  \n").unwrap();
+        fs::write(&mostly_clean, "a  \nb\nc\nd\n").unwrap();
+
+        let config = Config {
+            max_changed_fraction: Some(0.25),
+            porcelain: true,
+            ..Config::default()
+        };
+        let (reporter, out, err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        run_file(&dirty, &config, &session).unwrap();
+        run_file(&mostly_clean, &config, &session).unwrap();
+        session.finish();
+
+        assert_eq!(fs::read_to_string(&dirty).unwrap(), "a  \nb\t\nc \nd  \n");
+        assert_eq!(fs::read_to_string(&mostly_clean).unwrap(), "a\nb\nc\nd\n");
+        assert_eq!(
+            out.contents(),
+            format!("Sf\t{}\nM\t{}\n", dirty.display(), mostly_clean.display())
+        );
+        assert!(err.contents().contains("would change 4 of 4 lines"));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_max_changed_fraction() {
+        let parse = |value: &str| {
+            parse_config(&[
+                "rtrim".to_string(),
+                "--max-changed-fraction".to_string(),
+                value.to_string(),
+                "--folder".to_string(),
+                ".".to_string(),
+            ])
+        };
+        assert_eq!(parse("0.5").unwrap().max_changed_fraction, Some(0.5));
+        assert!(parse("1.5").is_err());
+        assert!(parse("half").is_err());
+    }
+
+    // ==================== Skip Outliers Tests ====================
+
+    #[test]
+    fn test_percentile_size() {
+        let sizes: Vec<u64> = (1..=10).collect();
+        assert_eq!(percentile_size(sizes.clone(), 90.0), Some(9));
+        assert_eq!(percentile_size(sizes.clone(), 100.0), Some(10));
+        assert_eq!(percentile_size(sizes.clone(), 0.5), Some(1));
+        assert_eq!(percentile_size(vec![], 99.0), None);
+    }
+
+    #[test]
+    fn test_skip_outliers_skips_oversized_file() {
+        let test_dir = create_test_dir();
+        for i in 0..100 {
+            fs::write(test_dir.join(format!("small{}.txt", i)), "line  \n").unwrap();
+        }
+        let big = test_dir.join("generated.txt");
+        let big_content = "generated  \n".repeat(1000);
+        fs::write(&big, &big_content).unwrap();
+
+        let config = Config {
+            skip_outliers: true,
+            ..Config::default()
+        };
+        let (reporter, _out, err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        process_folder(&test_dir, &config, &session).unwrap();
+        session.finish();
+
+        assert_eq!(fs::read_to_string(&big).unwrap(), big_content);
+        assert_eq!(
+            fs::read_to_string(test_dir.join("small7.txt")).unwrap(),
+            "line\n"
+        );
+        assert!(err.contents().contains("--skip-outliers"));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_outlier_percentile_is_configurable() {
+        let test_dir = create_test_dir();
+        for (name, lines) in [("a.txt", 1), ("b.txt", 2), ("c.txt", 3), ("d.txt", 4)] {
+            fs::write(test_dir.join(name), "x  \n".repeat(lines)).unwrap();
+        }
+
+        let config = Config {
+            skip_outliers: true,
+            outlier_percentile: Some(50.0),
+            threads: Some(Threads::Count(2)),
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &Session::default()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(test_dir.join("b.txt")).unwrap(),
+            "x\n".repeat(2)
+        );
+        assert_eq!(
+            fs::read_to_string(test_dir.join("c.txt")).unwrap(),
+            "x  \n".repeat(3)
+        );
+        assert_eq!(
+            fs::read_to_string(test_dir.join("d.txt")).unwrap(),
+            "x  \n".repeat(4)
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Keep Inode Tests ====================
+
+    #[test]
+    fn test_keep_inode_preserves_inode_and_hard_links() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("linked.txt");
+        let hard_link = test_dir.join("link.txt");
+        fs::write(&test_file, "a   \nlonger line\t\n").unwrap();
+        fs::hard_link(&test_file, &hard_link).unwrap();
+        let inode = fs::metadata(&test_file).unwrap().ino();
+
+        let config = Config {
+            keep_inode: true,
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(fs::metadata(&test_file).unwrap().ino(), inode);
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "a\nlonger line\n");
+        assert_eq!(fs::read_to_string(&hard_link).unwrap(), "a\nlonger line\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_default_rewrite_replaces_inode() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("replaced.txt");
+        fs::write(&test_file, "a   \n").unwrap();
+        let inode = fs::metadata(&test_file).unwrap().ino();
+
+        process_file(&test_file, &Config::default(), &Session::default()).unwrap();
+
+        assert_ne!(fs::metadata(&test_file).unwrap().ino(), inode);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_atomic_off_rewrites_in_place_without_temp_file() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("fast.txt");
+        fs::write(&test_file, "a   \nlonger line\t\n\n\n").unwrap();
+        let inode = fs::metadata(&test_file).unwrap().ino();
+
+        let config = Config {
+            non_atomic: true,
+            ..Config::default()
+        };
+        assert_eq!(config.write_path(), WritePath::InPlace { sync: false });
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "a\nlonger line\n\n\n"
+        );
+        assert_eq!(fs::metadata(&test_file).unwrap().ino(), inode);
+        let entries: Vec<_> = fs::read_dir(&test_dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![OsStr::new("fast.txt")]);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_atomic_on_writes_same_content() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("safe.txt");
+        fs::write(&test_file, "a   \nlonger line\t\n").unwrap();
+
+        let args: Vec<String> = ["rtrim", "--atomic=on", "--file", "safe.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = parse_config(&args).unwrap();
+        assert_eq!(config.write_path(), WritePath::Atomic);
+        process_file(&test_file, &config, &Session::default()).unwrap();
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "a\nlonger line\n");
+
+        let mut off = args.clone();
+        off[1] = "--atomic=off".to_string();
+        assert!(parse_config(&off).unwrap().non_atomic);
+        off[1] = "--atomic=maybe".to_string();
+        assert!(parse_config(&off).is_err());
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Author Lines Tests ====================
+
+    #[test]
+    fn test_restrict_to_lines_reverts_other_authors() {
+        let input = "mine  \ntheirs  \nmine\t\ntheirs\n";
+        let blamed = [true, false, true, false];
+        let result = restrict_to_lines(input, trim_trailing_whitespace(input), &blamed);
+        assert_eq!(result.content, "mine\ntheirs  \nmine\ntheirs\n");
+        assert!(result.modified);
+        assert_eq!(result.removed.lines, 2);
+        assert_eq!(result.removed.spaces, 2);
+        assert_eq!(result.removed.tabs, 1);
+    }
+
+    #[test]
+    fn test_restrict_to_lines_without_authored_lines_is_unchanged() {
+        let input = "theirs  \nno newline ";
+        let result = restrict_to_lines(input, trim_trailing_whitespace(input), &[]);
+        assert_eq!(result.content, input);
+        assert!(!result.modified);
+        assert_eq!(result.removed, WhitespaceBreakdown::default());
+    }
+
+    #[test]
+    fn test_author_lines_skips_files_git_cannot_blame() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("untracked.txt");
+        fs::write(&test_file, "a  \n").unwrap();
+
+        let config = Config {
+            author_lines: Some("Ada Lovelace".into()),
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::NoBlame));
+        assert_eq!(fs::read_to_string(&test_file).unwrap(), "a  \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Min Dirty Lines Tests ====================
+
+    #[test]
+    fn test_min_dirty_lines_threshold() {
+        let test_dir = create_test_dir();
+        let below = test_dir.join("below.txt");
+        let at = test_dir.join("at.txt");
+        let above = test_dir.join("above.txt");
+        fs::write(&below, "a  \nb  \nc\n").unwrap();
+        fs::write(&at, "a  \nb  \nc  \n").unwrap();
+        fs::write(&above, "a  \nb  \nc  \nd  \n").unwrap();
+
+        let config = Config {
+            min_dirty_lines: Some(3),
+            ..Config::default()
+        };
+        let session = Session::default();
+
+        assert_eq!(
+            process_file(&below, &config, &session).unwrap(),
+            Outcome::Skipped(SkipReason::BelowDirtyThreshold)
+        );
+        assert_eq!(
+            process_file(&at, &config, &session).unwrap(),
+            Outcome::Modified
+        );
+        assert_eq!(
+            process_file(&above, &config, &session).unwrap(),
+            Outcome::Modified
+        );
+
+        assert_eq!(fs::read_to_string(&below).unwrap(), "a  \nb  \nc\n");
+        assert_eq!(fs::read_to_string(&at).unwrap(), "a\nb\nc\n");
+        assert_eq!(fs::read_to_string(&above).unwrap(), "a\nb\nc\nd\n");
+        assert_eq!(session.below_threshold.load(Ordering::Relaxed), 1);
+        assert_eq!(session.removed.lock().unwrap().lines, 7);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Normalize Indent Tests ====================
+
+    #[test]
+    fn test_parse_config_indent_options() {
+        let args: Vec<String> = [
+            "rtrim",
+            "--normalize-indent",
+            "--indent-style",
+            "tab",
+            "--indent-size",
+            "2",
+            "--folder",
+            ".",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let config = parse_config(&args).unwrap();
+        assert!(config.normalize_indent);
+        assert_eq!(config.indent_style, Some(IndentStyle::Tab));
+        assert_eq!(config.indent_size, Some(2));
+
+        let mut bad = args.clone();
+        bad[5] = "0".to_string();
+        assert!(parse_config(&bad).is_err());
+    }
+
+    #[test]
+    fn test_normalize_indent_spaces_to_tabs() {
+        let test_dir = create_test_dir();
+        let test_file = test_dir.join("spaces.c");
+        fs::write(&test_file, "int f() {\n    return 1;   \n}\n").unwrap();
+
+        let config = Config {
+            normalize_indent: true,
+            indent_style: Some(IndentStyle::Tab),
+            ..Config::default()
+        };
+        let outcome = process_file(&test_file, &config, &Session::default()).unwrap();
+
+        assert_eq!(outcome, Outcome::Modified);
+        assert_eq!(
+            fs::read_to_string(&test_file).unwrap(),
+            "int f() {\n\treturn 1;\n}\n"
+        );
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_normalize_indent_tabs_to_spaces() {
+        let config = Config {
+            normalize_indent: true,
+            indent_style: Some(IndentStyle::Space),
+            indent_size: Some(2),
+            ..Config::default()
+        };
+        let result = trim_for_config(Path::new("a.py"), "if x:\n\ty = 1\n", &config).unwrap();
+        assert!(result.modified);
+        assert_eq!(result.content, "if x:\n  y = 1\n");
+    }
+
+    #[test]
+    fn test_normalize_indent_detects_majority_style() {
+        let config = Config {
+            normalize_indent: true,
+            ..Config::default()
+        };
+        let input = "a\n\tb\n\tc\n    d\n";
+        let result = trim_for_config(Path::new("mixed.txt"), input, &config).unwrap();
+        assert_eq!(result.content, "a\n\tb\n\tc\n\td\n");
+
+        let clean = trim_for_config(Path::new("clean.txt"), "a\n\tb\n", &config).unwrap();
+        assert!(!clean.modified);
+    }
+
+    #[test]
+    fn test_normalize_tabs_in_indent_only_keeps_alignment() {
+        let args: Vec<String> = ["rtrim", "--normalize-tabs-in-indent-only", "--file", "a.c"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config {
+            indent_style: Some(IndentStyle::Space),
+            ..parse_config(&args).unwrap()
+        };
+        assert!(config.normalize_indent && config.keep_alignment);
+
+        let input = "\tfoo(a,   \n\t        b);\n";
+        let result = trim_for_config(Path::new("a.c"), input, &config).unwrap();
+        assert_eq!(result.content, "    foo(a,\n            b);\n");
+    }
+
+    // ==================== Strip Both Tests ====================
+
+    #[test]
+    fn test_strip_both_removes_indentation() {
+        let config = Config {
+            strip_both: true,
+            ..Config::default()
+        };
+        let input = "  apple  \n\tbanana\ncherry \n   \n \u{a0}date";
+        let result = trim_for_config(Path::new("words.txt"), input, &config).unwrap();
+        assert!(result.modified);
+        assert_eq!(result.content, "apple\nbanana\ncherry\n\ndate");
+        assert_eq!(result.removed.lines, 5);
+        assert_eq!(result.removed.spaces, 9);
+        assert_eq!(result.removed.tabs, 1);
+        assert_eq!(result.removed.other, 1);
+    }
+
+    #[test]
+    fn test_indentation_kept_without_strip_both() {
+        let input = "  apple  \n\tbanana\n";
+        let result = trim_for_config(Path::new("words.txt"), input, &Config::default()).unwrap();
+        assert_eq!(result.content, "  apple\n\tbanana\n");
+    }
+
+    #[test]
+    fn test_strip_both_respects_protected_lines() {
+        let config = Config {
+            strip_both: true,
+            yaml_aware: true,
+            ..Config::default()
+        };
+        let input = "  key: |\n    kept  \n";
+        let result = trim_for_config(Path::new("a.yml"), input, &config).unwrap();
+        assert_eq!(result.content, "key: |\n    kept  \n");
+    }
+
+    // ==================== Comment Spacing Tests ====================
+
+    #[test]
+    fn test_normalize_comment_spacing_rust() {
+        let config = Config {
+            normalize_comment_spacing: true,
+            ..Config::default()
+        };
+        let input = "let a = 1;     // note   \nlet s = \"a    // b\";\n";
+        let result = trim_for_config(Path::new("lib.rs"), input, &config).unwrap();
+        assert!(result.modified);
+        assert_eq!(
+            result.content,
+            "let a = 1; // note\nlet s = \"a    // b\";\n"
+        );
+    }
+
+    #[test]
+    fn test_normalize_comment_spacing_python() {
+        let config = Config {
+            normalize_comment_spacing: true,
+            comment_spaces: Some(2),
+            ..Config::default()
+        };
+        let input = "x = 1 # a\ny = \"#  \"\t\t# b\n";
+        let result = trim_for_config(Path::new("app.py"), input, &config).unwrap();
+        assert_eq!(result.content, "x = 1  # a\ny = \"#  \"  # b\n");
+
+        let other = trim_for_config(Path::new("notes.txt"), input, &config).unwrap();
+        assert!(!other.modified);
+    }
+
+    // ==================== Output Patch Tests ====================
+
+    #[test]
+    fn test_patch_labels() {
+        assert_eq!(
+            patch_labels(Path::new("./src/main.rs")),
+            ("a/src/main.rs".to_string(), "b/src/main.rs".to_string())
+        );
+        assert_eq!(
+            patch_labels(Path::new("/tmp/x.txt")),
+            ("a/tmp/x.txt".to_string(), "b/tmp/x.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn test_output_patch_applies_and_matches_trim() {
+        let test_dir = create_test_dir();
+        let tree = test_dir.join("tree");
+        let expected = test_dir.join("expected");
+        fs::create_dir_all(tree.join("sub")).unwrap();
+        let files = [
+            ("one.txt", "a  \nb\n"),
+            ("sub/two.txt", "x\t\ny\nz   "),
+            ("clean.txt", "clean\n"),
+        ];
+        for (name, content) in files {
+            fs::write(tree.join(name), content).unwrap();
+        }
+
+        // What rtrim itself would produce, on a copy of the tree.
+        fs::create_dir_all(expected.join("sub")).unwrap();
+        for (name, content) in files {
+            fs::write(expected.join(name), content).unwrap();
+        }
+        process_folder(&expected, &Config::default(), &Session::default()).unwrap();
+
+        let patch_file = test_dir.join("changes.patch");
+        let config = Config {
+            output_patch: Some(patch_file.clone()),
+            ..Config::default()
+        };
+        let session = Session::default();
+        process_folder(&tree, &config, &session).unwrap();
+        fs::write(&patch_file, &*session.patch.lock().unwrap()).unwrap();
+
+        // Nothing was modified, and unchanged files contribute nothing.
+        assert_eq!(
+            fs::read_to_string(tree.join("one.txt")).unwrap(),
+            "a  \nb\n"
+        );
+        let patch = fs::read_to_string(&patch_file).unwrap();
+        assert_eq!(patch.matches("+++ b/").count(), 2);
+        assert!(!patch.contains("clean.txt"));
+
+        let status = Command::new("patch")
+            .args(["-p1", "-s", "-d", "/", "-i"])
+            .arg(&patch_file)
+            .status();
+        // Only checked where the `patch` tool is installed.
+        if let Ok(status) = status {
+            assert!(status.success());
+            for (name, _) in files {
+                assert_eq!(
+                    fs::read_to_string(tree.join(name)).unwrap(),
+                    fs::read_to_string(expected.join(name)).unwrap(),
+                    "{}",
+                    name
+                );
+            }
+        }
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Threads Tests ====================
+
+    #[test]
+    fn test_parse_config_threads() {
+        let parse = |value: &str| {
+            parse_config(&[
+                "rtrim".to_string(),
+                "--threads".to_string(),
+                value.to_string(),
+                "--folder".to_string(),
+                ".".to_string(),
+            ])
+        };
+        assert_eq!(parse("auto").unwrap().threads, Some(Threads::Auto));
+        assert_eq!(parse("3").unwrap().threads, Some(Threads::Count(3)));
+        assert!(parse("0").is_err());
+        assert!(parse("many").is_err());
+    }
+
+    #[test]
+    fn test_parse_config_jobs_aliases_threads() {
+        for flag in ["--jobs", "-j"] {
+            let config = parse_config(&[
+                "rtrim".to_string(),
+                flag.to_string(),
+                "2".to_string(),
+                "--folder".to_string(),
+                ".".to_string(),
+            ])
+            .unwrap();
+            assert_eq!(config.threads, Some(Threads::Count(2)));
+        }
+        let default =
+            parse_config(&["rtrim".to_string(), "--folder".to_string(), ".".to_string()]).unwrap();
+        assert_eq!(default.threads, None);
+    }
+
+    #[test]
+    fn test_threads_auto_resolves_to_positive_count() {
+        assert!(Threads::Auto.resolve() >= 1);
+        assert_eq!(Threads::Count(4).resolve(), 4);
+    }
+
+    #[test]
+    fn test_process_folder_with_worker_threads() {
+        let test_dir = create_test_dir();
+        let files: Vec<PathBuf> = (0..40)
+            .map(|i| {
+                let dir = test_dir.join(format!("d{}", i % 4));
+                fs::create_dir_all(&dir).unwrap();
+                let file = dir.join(format!("f{}.txt", i));
+                fs::write(&file, format!("line {}   \n", i)).unwrap();
+                file
+            })
+            .collect();
+
+        let (reporter, out, _err) = capturing_reporter(None);
+        let session = Session::new(reporter);
+        let config = Config {
+            threads: Some(Threads::Count(4)),
+            ..Config::default()
+        };
+        process_folder(&test_dir, &config, &session).unwrap();
+        session.finish();
+
+        for (i, file) in files.iter().enumerate() {
+            assert_eq!(fs::read_to_string(file).unwrap(), format!("line {}\n", i));
+        }
+        assert_eq!(out.contents().matches("  Processed: ").count(), 40);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    // ==================== Daemon Tests ====================
+
+    #[test]
+    fn test_daemon_trims_file_sent_over_socket() {
+        let test_dir = create_test_dir();
+        let socket = test_dir.join("rtrim.sock");
+        let file = test_dir.join("dirty.txt");
+        fs::write(&file, "hello   \nworld\t\n").unwrap();
+
+        let listener = bind_socket(&socket).unwrap();
+        // The daemon never returns; leak its state to the detached thread.
+        let config: &'static Config = Box::leak(Box::default());
+        let session: &'static Session = Box::leak(Box::default());
+        thread::spawn(move || serve(&listener, config, session));
+
+        let reply = request_daemon(&socket, &file).unwrap();
+        assert_eq!(reply, format!("M\t{}", file.display()));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "hello\nworld\n");
+
+        let missing = test_dir.join("missing.txt");
+        let reply = request_daemon(&socket, &missing).unwrap();
+        assert!(reply.starts_with(&format!("E\t{}\t", missing.display())));
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_bind_socket_replaces_only_stale_sockets() {
+        let test_dir = create_test_dir();
+        let socket = test_dir.join("rtrim.sock");
+        // A listener dropped without cleanup leaves its socket file behind.
+        drop(bind_socket(&socket).unwrap());
+        assert!(socket.exists());
+        drop(bind_socket(&socket).unwrap());
+
+        let keep = test_dir.join("keep.txt");
+        fs::write(&keep, "user data\n").unwrap();
+        let err = bind_socket(&keep).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(fs::read_to_string(&keep).unwrap(), "user data\n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_connect_requires_file() {
+        let args: Vec<String> = ["rtrim", "--connect", "/tmp/rtrim.sock", "--folder", "."]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert!(parse_config(&args).is_err());
+        let args: Vec<String> = ["rtrim", "--connect", "/tmp/rtrim.sock", "--file", "a.txt"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        assert_eq!(
+            parse_config(&args).unwrap().connect,
+            Some(PathBuf::from("/tmp/rtrim.sock"))
+        );
+    }
+
+    // ==================== On Temp Fail Tests ====================
+
+    /// A dirty file in a non-writable directory, with a name so long that no
+    /// temp file name derived from it fits, so creating the temp file fails
+    /// even when the tests run as root.
+    fn file_without_temp_space(test_dir: &Path) -> PathBuf {
+        let dir = test_dir.join("locked");
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join(format!("{}.txt", "a".repeat(240)));
+        fs::write(&path, "locked   \n").unwrap();
+        fs::set_permissions(&dir, Permissions::from_mode(0o555)).unwrap();
+        path
+    }
+
+    fn unlock(test_dir: &Path) {
+        fs::set_permissions(test_dir.join("locked"), Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_on_temp_fail_error_fails_the_file() {
+        let test_dir = create_test_dir();
+        let path = file_without_temp_space(&test_dir);
+
+        let err = process_file(&path, &Config::default(), &Session::default()).unwrap_err();
+        assert_eq!(err.action(), TEMP_CREATE_FAILED);
+        unlock(&test_dir);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "locked   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_on_temp_fail_skip_leaves_file_untouched() {
+        let test_dir = create_test_dir();
+        let path = file_without_temp_space(&test_dir);
+
+        let config = Config {
+            on_temp_fail: TempFailPolicy::Skip,
+            ..Config::default()
+        };
+        let outcome = process_file(&path, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Skipped(SkipReason::NoTempFile));
+        unlock(&test_dir);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "locked   \n");
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_on_temp_fail_inplace_rewrites_file() {
+        let test_dir = create_test_dir();
+        let path = file_without_temp_space(&test_dir);
+
+        let config = Config {
+            on_temp_fail: TempFailPolicy::InPlace,
+            ..Config::default()
+        };
+        let outcome = process_file(&path, &config, &Session::default()).unwrap();
+        assert_eq!(outcome, Outcome::Modified);
+        unlock(&test_dir);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "locked\n");
+        assert_eq!(fs::read_dir(test_dir.join("locked")).unwrap().count(), 1);
+
+        cleanup_test_dir(&test_dir);
+    }
+
+    #[test]
+    fn test_parse_config_on_temp_fail() {
+        let parse = |flag: &str| {
+            let args: Vec<String> = ["rtrim", flag, "--folder", "."]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+            parse_config(&args).map(|config| config.on_temp_fail)
+        };
+        assert_eq!(parse("--verbose"), Ok(TempFailPolicy::Error));
+        assert_eq!(parse("--on-temp-fail=skip"), Ok(TempFailPolicy::Skip));
+        assert_eq!(parse("--on-temp-fail=inplace"), Ok(TempFailPolicy::InPlace));
+        assert!(parse("--on-temp-fail=retry").is_err());
+    }
+}
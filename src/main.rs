@@ -1,39 +1,8 @@
 use std::env;
-use std::fs::{self, File, Metadata, Permissions};
-use std::io::{self, Read, Write};
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
-use std::path::{Path, PathBuf};
+use std::io;
 use std::process;
-use std::time::SystemTime;
 
-/// Defines the operation mode and target path.
-#[derive(Debug)]
-struct Config {
-    mode: Mode,
-    path: PathBuf,
-    verbose: bool,
-}
-
-#[derive(Debug, PartialEq)]
-enum Mode {
-    File,
-    Folder,
-    Help,
-}
-
-/// Directories to be ignored during recursive traversal.
-const IGNORED_DIRS: &[&str] = &[
-    ".git",
-    ".svn",
-    ".hg",
-    "node_modules",
-    "target",
-    "__pycache__",
-    ".venv",
-    "venv",
-    ".idea",
-    ".vscode",
-];
+use rtrim::{parse_config, run, Mode};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -43,10 +12,12 @@ fn main() {
         process::exit(1);
     });
 
-    if let Err(e) = run(config) {
-        eprintln!("Execution Error: {}", e);
-        process::exit(1);
+    if config.mode == Mode::Help {
+        print_help();
+        return;
     }
+
+    process::exit(run(&config, Box::new(io::stdout()), Box::new(io::stderr())));
 }
 
 /// Displays the help message.
@@ -55,21 +26,348 @@ fn print_help() {
 
 USAGE:
     rtrim --file <path>       Process a single file
+    rtrim --file <url> --report-only
+                              Report on a remote file over plain http://
     rtrim --folder <path>     Process a folder recursively
+    rtrim <path>...           Process each file or folder in turn, detected by
+                              its type; --file and --folder also take several
+                              paths (`rtrim --file a.rs --file b.rs`)
+    rtrim --stdin-paths0      Process NUL-separated file paths read from stdin
+                              (file names, not content; e.g. `find -print0`)
+    rtrim -, rtrim --stdin    Trim the text read from stdin and write it to
+                              stdout, e.g. `rtrim - < messy.txt > clean.txt`;
+                              input that is not UTF-8 is an error (see
+                              --null-output-on-stdin)
+    rtrim --stdin-json        Like `rtrim -`, but answer with one JSON object
+                              for editor plugins: {"changed": bool, "content":
+                              "...", "lines_trimmed": [1-based line numbers]}
+    rtrim --daemon <socket>   Stay running and process the file paths sent to
+                              the Unix socket, one per line, with the options
+                              given here; each gets a porcelain status line
+    rtrim --connect <socket> --file <path>
+                              Have a running daemon process the file
+    rtrim --rollback <journal>
+                              Restore the files recorded by --journal from
+                              their .bak copies, e.g. after a crash; run it
+                              from the same directory as the journaled run
     rtrim --help              Display this help message
 
 DESCRIPTION:
     Removes trailing whitespace (spaces, tabs, etc.) from the end of each
     line in text files. Binary files are automatically detected and ignored.
+    Runs over several files end with a one-line summary of the files
+    scanned, modified, unchanged and skipped (not with --porcelain, --format
+    json or --check).
 
 SECURITY:
     - Atomic writes via write-sync-rename
-    - Preserves original file permissions
-    - Ignores symlinks to prevent attacks
+    - Preserves original file permissions (unless --preserve-permissions off)
+    - Ignores symlinks to prevent attacks (unless --follow-symlinks)
     - Uses unique temporary file names
 
 OPTIONS:
     -v, --verbose         Show detailed processing information
+    -q, --quiet           Do not print the per-file "Processed:" lines or the
+                          end-of-run summary on stdout
+    --quiet-errors        Do not print per-file errors on stderr; failed files
+                          still set the exit status
+    --verbose-errors      Show the full cause chain and error kind for failures
+                          (plus a backtrace when built with the `backtrace`
+                          feature and RUST_BACKTRACE=1 is set)
+    --max-warnings <N>    Print at most N warnings, then summarize the rest
+    --warn-eof-newline    Warn when trimming changes the end-of-file newline
+                          (always on with --verbose)
+    --final-newline       End every file with exactly one newline: add a
+                          missing one and drop trailing blank lines. Empty
+                          files stay empty
+    --squeeze-blank-eof   Collapse the blank lines at the end of a file into a
+                          single final newline; a file of nothing but blank
+                          lines becomes empty. Blank lines between text stay
+    --max-lines <N>       Skip files with more than N lines
+    --allow-conflicts     Trim files with unresolved merge conflict markers
+                          (<<<<<<< and >>>>>>> lines), which are skipped with
+                          a warning by default
+    --skip-outliers       With --folder, scan the tree first and skip files
+                          larger than the 99th percentile of file sizes
+                          (likely generated). EXPERIMENTAL
+    --outlier-percentile <P>
+                          Percentile used by --skip-outliers (0 < P <= 100)
+    --min-dirty-lines <N> Only rewrite files with at least N lines to trim;
+                          the others are skipped and counted separately
+    --max-changed-fraction <F>
+                          Skip files where trimming would change more than
+                          the fraction F (0 to 1) of all lines, a sign that
+                          the file is not ordinary text
+    --hash-cache <FILE>   Remember the content hash of every clean file in FILE
+                          and skip trimming content already known to be clean
+    --lossy               Trim files with a few invalid UTF-8 bytes instead of
+                          skipping them. LOSSY: invalid bytes are replaced by
+                          U+FFFD when the file is rewritten. Files containing
+                          NUL bytes are still treated as binary
+    --null-output-on-stdin
+                          With `rtrim -`, copy input that is binary or not
+                          UTF-8 to stdout byte for byte and report it
+                          unchanged, instead of failing, so editor buffers
+                          survive
+    --encoding <utf8|latin1>
+                          How to read files that are not valid UTF-8. With
+                          latin1 (any single-byte encoding such as Windows-1252)
+                          they lose trailing ASCII spaces and tabs byte by
+                          byte; source-aware options do not apply to them.
+                          Default utf8 skips them as binary. Files with NUL
+                          bytes or mostly control characters are always binary
+    --yaml                In .yaml/.yml files, leave the content of literal (|)
+                          and folded (>) block scalars untouched
+    --shell               In .sh/.bash scripts, leave the body of here-docs
+                          (<<EOF, <<-'EOF', ...) untouched
+    --csv, --trim-csv-aware
+                          In .csv files, leave lines that end inside a quoted
+                          (multi-line) field untouched; whitespace after the
+                          last field of a row is still trimmed
+    --html, --trim-html-aware
+                          In .html/.htm files, leave lines inside <pre> and
+                          <textarea> elements untouched
+    --ipynb               In .ipynb notebooks, trim only the source lines of
+                          code cells; outputs, metadata and JSON layout are
+                          left untouched
+    --strip-both          Remove leading as well as trailing whitespace from
+                          every line. DESTRUCTIVE: all indentation is lost;
+                          meant for flat data such as word lists
+    --normalize-indent    Also rewrite leading indentation in one style: the
+                          style most lines use, or the one set below. Only
+                          the leading whitespace of a line is changed
+    --indent-style <tab|space>
+                          Indentation style for --normalize-indent
+    --indent-size <N>     Columns per indentation level / tab width (default 4)
+    --normalize-tabs-in-indent-only
+                          Like --normalize-indent, but only the leading tabs
+                          of a line are indentation; the spaces after them
+                          align code and are left alone
+    --with-rustfmt        Run .rs files through rustfmt after trimming and
+                          write its output instead. Files rustfmt rejects are
+                          only trimmed; without rustfmt on PATH this option
+                          has no effect (a warning is printed once)
+    --normalize-comment-spacing
+                          Also collapse the whitespace between code and an
+                          inline `//` or `#` comment to the number of spaces
+                          set below. Only applies to the languages listed
+                          under --trim-doc-comments-only; strings are never
+                          touched
+    --comment-spaces <N>  Spaces before inline comments (default 1)
+    --dedupe              Canonicalize paths and process each file at most once
+    --skip-readonly       Skip read-only files instead of rewriting them
+    --atomic <on|off>     With on (default), write a synced temp file and
+                          rename it over the original. With off, rewrite
+                          files in place without syncing: fastest, but NOT
+                          ATOMIC and NOT DURABLE; a crash or full disk can
+                          leave a file truncated or lose the change
+    --keep-inode          Rewrite files in place so the inode (hard links, open
+                          handles, watchers) is kept. NOT ATOMIC: a crash or
+                          full disk mid-write can leave a file truncated
+    --group-commit <N>    Write up to N temp files before renaming them over
+                          their targets in one burst, then sync each affected
+                          directory once. Every file is still replaced
+                          atomically; a rename that fails leaves no temp
+                          file behind. --porcelain and --format json report
+                          a file when it is written, so a rename that fails
+                          later adds an error for the same path
+    --preserve-permissions <on|off>
+                          With off, rewritten files get the default mode for
+                          new files (0666 minus umask) instead of their
+                          original permissions (default on; --keep-inode
+                          always keeps the original mode)
+    --preserve-mtime      Give rewritten files back their original modification
+                          time, so mtime-based builds do not see a change; a
+                          time that cannot be set is only a warning
+    --backup              Copy each file to <name>.bak, with its permissions,
+                          before rewriting it; an existing .bak is replaced,
+                          and folder walks skip .bak files
+    --journal <FILE>      Record each rewrite in FILE before and after it
+                          happens, with the hash of the original content, so
+                          --rollback can undo the run; implies --backup
+    --on-temp-fail <error|skip|inplace>
+                          When no temp file can be created next to a file
+                          (e.g. its directory is not writable): fail the file
+                          (default), skip it with a warning, or rewrite it in
+                          place like --keep-inode (NOT ATOMIC)
+    -j, --jobs <N|auto>   Process folder files on N worker threads, or one per
+                          CPU with `auto` (default: auto; 1 keeps the walk
+                          order). --threads is an alias
+    --one-filesystem      Do not cross into other filesystems (like find -xdev)
+    --max-depth <N>       Descend at most N levels into a folder; 1 processes
+                          only the files directly inside it
+    --skip-submodules     Do not descend into Git submodules: directories with
+                          a .git file, or listed in the .gitmodules next to them
+    --respect-gitignore   Also skip folder entries that the .gitignore files
+                          in the walked tree (and above it, up to the
+                          repository root) ignore
+    --respect-gitattributes-eol
+                          Write the line endings that the eol attribute in
+                          .gitattributes declares (eol=lf or eol=crlf) for a
+                          file in a git repository, even if it has nothing
+                          to trim; other files end their lines with LF
+    --editorconfig        Only trim files whose .editorconfig section sets
+                          trim_trailing_whitespace = true, and end them with a
+                          newline when it sets insert_final_newline = true.
+                          .editorconfig files are read from the file's
+                          directory up to one with root = true
+    --follow-symlinks     Process the targets of symlinks instead of skipping
+                          them; the link itself is left in place. Targets
+                          must be inside the folder being walked (for a file
+                          argument, the link's directory), and each directory
+                          is walked once, so link loops end
+    --allow-symlinks-outside-root
+                          With --follow-symlinks, also follow links that lead
+                          outside the folder being walked
+    --include <EXT>       Only process files with this extension in folders
+                          (repeatable: --include rs --include toml); files
+                          without an extension are then left out
+    --exclude <EXT>       Leave out files with this extension in folders
+                          (repeatable); applied after --include
+    --enforce-ignores-on-file
+                          Refuse --file (and --stdin-paths0) paths that go
+                          through an ignored directory, such as .git/config,
+                          as folder processing would
+    --progress-bar        With --folder, count the files first, then show a
+                          progress bar on stderr while processing them. Only
+                          drawn when stderr is a terminal (requires the
+                          `progress` feature)
+    --porcelain           Print one `STATUS<TAB>PATH` line per file, a format
+                          that is stable across versions (see PORCELAIN STATUS)
+    --format <text|json>  With json, print one JSON object per line instead of
+                          human output: {"type": "file", "path", "status"}
+                          per file, with "lines" trimmed for modified and
+                          unchanged files, "reason" for skipped ones and
+                          "error" for failures; an {"type": "error"} object if
+                          the run fails; and a final {"type": "summary"}
+                          object with the counts. Not with --preview or the
+                          text reports (--report-age, --report-bom, ...)
+    --list-ignored        Print every path the run leaves out, one
+                          `REASON<TAB>PATH` line each (ignored dir, symlink,
+                          binary, ...), without modifying any file
+    --output-patch <FILE> Write every change as one unified diff to FILE instead
+                          of modifying files; apply it with `patch -p1` (or
+                          `git apply`) from the current directory, or with
+                          `patch -p1 -d /` when absolute paths were given
+    --preview <N>         Show diffs for the first N files that would change,
+                          then stop scanning (nothing is modified)
+    --content-match <REGEX>
+                          Only trim files containing a line that matches REGEX
+    --protect-regex <REGEX>
+                          Never trim a line that matches REGEX. Protection is
+                          per line: the pattern is tested against each line
+                          (trailing whitespace included) on its own
+    --preserve-continuations
+                          Never trim a line whose last non-blank character is
+                          a backslash: `foo \   ` keeps its spaces, since
+                          trimming them would turn an escaped space into a
+                          line continuation (Makefiles, C macros, shell)
+    --on-modify-exec <CMD>
+                          Run CMD after each file is modified. The path replaces
+                          a `{}` argument, or is appended when there is none.
+                          CMD is split on whitespace, not run through a shell
+    --modified-exit-code <N>
+                          Exit with status N (0-125) when any file was modified,
+                          or would be modified in report-only modes
+    --exit-code <CELL=N>  Exit with status N (0-125) for one row of the EXIT
+                          STATUS table below; may be repeated
+    --check, --dry-run    List the files that would be modified on stdout and
+                          exit with status 1 if there are any, without
+                          modifying any file (sets the would-modify row of
+                          EXIT STATUS unless --exit-code or
+                          --modified-exit-code sets it explicitly)
+    --dry-run-write-report <FILE>
+                          Like --check, and also write the files that would be
+                          modified to FILE when the run completes: as JSON with
+                          per-file whitespace counts if FILE ends in .json,
+                          otherwise one path per line (with --trim-report
+                          counts when given)
+    --dry-run-report-format <text|json>
+                          Format of the --dry-run-write-report file, whatever
+                          its extension
+    --report-trailing-types, --report-only
+                          Report how much trailing whitespace is spaces, tabs
+                          or other characters, without modifying any file
+    --trim-report=<lines|chars|both>
+                          Count affected lines, removed characters, or both
+                          (default) in reports, and add the counts to each
+                          modified file's line
+    --dedupe-trailing-newlines-across-files
+                          List the files that end with a newline and those that
+                          don't, with counts, without modifying any file
+    --report-bom          List the files that start with a UTF-8, UTF-16 LE or
+                          UTF-16 BE byte order mark, with counts, without
+                          modifying any file
+    --report-longest-lines <N>
+                          List the N files with the longest lines, with the
+                          length in characters and the line number, without
+                          modifying any file
+    --report-age          Group files by last modification (< 1d, < 1w, < 1mo,
+                          older) and print a table of files, files with
+                          trailing whitespace and lines to trim per group,
+                          without modifying any file
+    --trim-doc-comments-only
+                          Only trim lines ending inside a comment; code and
+                          string lines are left alone. Files in unsupported
+                          languages are skipped. Supported: Rust (.rs),
+                          C/C++ (.c, .h, .cc, .cpp, .hpp), Python (.py, .pyi)
+    --preserve-trailing-whitespace-in-strings
+                          Keep the trailing whitespace of lines that end
+                          inside a string literal spanning lines, e.g. Rust
+                          raw strings or Python triple-quoted strings; same
+                          languages as --trim-doc-comments-only, other files
+                          are trimmed as usual
+    --author-lines <AUTHOR>
+                          Only trim lines last changed by AUTHOR (name or
+                          email) according to `git blame`; other lines keep
+                          their trailing whitespace. Files git cannot blame
+                          (untracked, outside a repository) are skipped
+    --since-commit <REV>  Only process files that `git diff --name-only REV`
+                          lists, i.e. changed between REV and the working
+                          tree. Untracked files are not included; the target
+                          must be inside a git repository
+    --staged              Only process files staged in git's index (`git diff
+                          --cached --name-only`), as a pre-commit hook would.
+                          Files that also have unstaged changes are skipped,
+                          so --on-modify-exec 'git add' re-stages only the
+                          trimming; the target must be inside a git
+                          repository
+
+PORCELAIN STATUS:
+    M     modified (or would be, in report-only modes)
+    U     unchanged
+    Bb    skipped: binary (not valid UTF-8)
+    Sl    skipped: symlink
+    Sn    skipped: not a regular file
+    Sx    skipped: exceeds --max-lines
+    Su    skipped: unsupported language
+    Sd    skipped: duplicate path (--dedupe)
+    Sr    skipped: read-only (--skip-readonly)
+    Sc    skipped: no line matches --content-match
+    Sj    skipped: notebook is not valid JSON (--ipynb)
+    St    skipped: fewer dirty lines than --min-dirty-lines
+    Si    skipped: inside an ignored directory (--enforce-ignores-on-file)
+    So    skipped: size above the --skip-outliers percentile
+    Sf    skipped: more lines would change than --max-changed-fraction
+    Sg    skipped: git blame failed (--author-lines)
+    Sv    skipped: unchanged since --since-commit
+    Sa    skipped: not staged in git's index (--staged)
+    Sp    skipped: has unstaged changes besides staged ones (--staged)
+    Sk    skipped: .editorconfig does not ask for trimming (--editorconfig)
+    Sw    skipped: no temp file could be created (--on-temp-fail skip)
+    Sm    skipped: unresolved merge conflict markers (see --allow-conflicts)
+    Se    skipped: extension not selected by --include/--exclude
+    Sh    skipped: ignored by .gitignore (--respect-gitignore)
+    E     error (details on stderr)
+
+EXIT STATUS:
+    The first row that applies to the run decides its status (default shown).
+    error         1   the run, or any single file, failed
+    modified      0   files were modified
+    would-modify  0   files would be modified (report-only modes)
+    binary-only   0   every file found was binary
+    clean         0   anything else
+    Invalid options always exit with status 1.
 
 IGNORED DIRECTORIES:
     .git, .svn, .hg, node_modules, target, __pycache__,
@@ -79,862 +377,8 @@ EXAMPLES:
     rtrim --file src/main.rs
     rtrim --folder ./src
     rtrim --folder ./src --verbose
+    rtrim src/main.rs src/lib.rs docs/notes.md
+    find . -name '*.rs' -print0 | rtrim --stdin-paths0
 "#;
     println!("{}", help);
 }
-
-/// Performs manual CLI argument parsing.
-fn parse_config(args: &[String]) -> Result<Config, &'static str> {
-    if args.len() < 2 {
-        return Err("Usage: rtrim --file <path> | rtrim --folder <path> | rtrim --help");
-    }
-
-    // Find the optional "--" end-of-flags marker
-    let dbl = args.iter().position(|a| a == "--");
-
-    // Check for verbose flag only before "--" (or all args if no "--")
-    let args_for_flags = if let Some(pos) = dbl {
-        &args[..pos]
-    } else {
-        args
-    };
-    let verbose = args_for_flags
-        .iter()
-        .any(|a| *a == "--verbose" || *a == "-v");
-
-    // Filter out verbose flags for mode parsing
-    let filtered_args: Vec<&String> = if let Some(pos) = dbl {
-        // Filter flags only before "--", then append everything after "--" unchanged
-        args[..pos]
-            .iter()
-            .filter(|a| *a != "--verbose" && *a != "-v")
-            .chain(args[pos + 1..].iter())
-            .collect()
-    } else {
-        // No "--" found, filter across all args
-        args.iter()
-            .filter(|a| *a != "--verbose" && *a != "-v")
-            .collect()
-    };
-
-    if filtered_args.len() < 2 {
-        return Err("Usage: rtrim --file <path> | rtrim --folder <path> | rtrim --help");
-    }
-
-    match filtered_args[1].as_str() {
-        "--help" | "-h" => Ok(Config {
-            mode: Mode::Help,
-            path: PathBuf::new(),
-            verbose,
-        }),
-        "--file" => {
-            if filtered_args.len() < 3 {
-                return Err("Usage: rtrim --file <path>");
-            }
-            Ok(Config {
-                mode: Mode::File,
-                path: PathBuf::from(filtered_args[2]),
-                verbose,
-            })
-        }
-        "--folder" => {
-            if filtered_args.len() < 3 {
-                return Err("Usage: rtrim --folder <path>");
-            }
-            Ok(Config {
-                mode: Mode::Folder,
-                path: PathBuf::from(filtered_args[2]),
-                verbose,
-            })
-        }
-        _ => Err("Invalid flag. Use --file, --folder, or --help."),
-    }
-}
-
-fn run(config: Config) -> io::Result<()> {
-    match config.mode {
-        Mode::Help => {
-            print_help();
-            Ok(())
-        }
-        Mode::File => {
-            // Check if it's a symlink before processing
-            let metadata = fs::symlink_metadata(&config.path)?;
-            if metadata.file_type().is_symlink() {
-                if config.verbose {
-                    println!("  Skipped (symlink): {:?}", config.path);
-                } else {
-                    eprintln!("Warning: Ignoring symlink {:?}", config.path);
-                }
-                return Ok(());
-            }
-            process_file(&config.path, config.verbose)
-        }
-        Mode::Folder => process_folder(&config.path, config.verbose),
-    }
-}
-
-/// Checks if a directory should be ignored.
-fn should_ignore_dir(path: &Path) -> bool {
-    path.file_name()
-        .and_then(|name| name.to_str())
-        .map(|name| IGNORED_DIRS.contains(&name) || name.starts_with('.'))
-        .unwrap_or(false)
-}
-
-/// Recursive filesystem traversal (without following symlinks).
-fn process_folder(dir: &Path, verbose: bool) -> io::Result<()> {
-    // Use symlink_metadata to avoid following symlinks
-    let metadata = fs::symlink_metadata(dir)?;
-
-    // Ignore symlinks
-    if metadata.file_type().is_symlink() {
-        if verbose {
-            println!("  Skipped (symlink): {:?}", dir);
-        }
-        return Ok(());
-    }
-
-    if !metadata.is_dir() {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidInput,
-            "Path is not a directory",
-        ));
-    }
-
-    if verbose {
-        println!("Scanning: {:?}", dir);
-    }
-
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-
-        // Use symlink_metadata to detect symlinks without following them
-        let entry_metadata = match fs::symlink_metadata(&path) {
-            Ok(m) => m,
-            Err(e) => {
-                eprintln!("Warning: Could not read metadata for {:?}: {}", path, e);
-                continue;
-            }
-        };
-
-        // Ignore symlinks completely
-        if entry_metadata.file_type().is_symlink() {
-            if verbose {
-                println!("  Skipped (symlink): {:?}", path);
-            }
-            continue;
-        }
-
-        if entry_metadata.is_dir() {
-            // Ignore special directories
-            if should_ignore_dir(&path) {
-                if verbose {
-                    println!("  Skipped (ignored dir): {:?}", path);
-                }
-                continue;
-            }
-            process_folder(&path, verbose)?;
-        } else if entry_metadata.is_file() {
-            if let Err(e) = process_file(&path, verbose) {
-                eprintln!("Warning: Error processing {:?}: {}", path, e);
-            }
-        }
-    }
-
-    Ok(())
-}
-
-/// Generates a unique temporary file name in the same directory.
-fn generate_temp_path(original: &Path) -> PathBuf {
-    let parent = original.parent().unwrap_or(Path::new("."));
-    let timestamp = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .map(|d| d.as_nanos())
-        .unwrap_or(0);
-    let pid = process::id();
-
-    let original_name = original
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("file");
-
-    let temp_name = format!(".{}.{}.{}.tmp", original_name, pid, timestamp);
-    parent.join(temp_name)
-}
-
-/// Applies the original file permissions to the new file.
-fn preserve_permissions(temp_path: &Path, original_metadata: &Metadata) -> io::Result<()> {
-    let permissions = Permissions::from_mode(original_metadata.mode());
-    fs::set_permissions(temp_path, permissions)?;
-    Ok(())
-}
-
-/// Result of trimming operation.
-#[derive(Debug, PartialEq)]
-struct TrimResult {
-    content: String,
-    modified: bool,
-}
-
-/// Removes trailing whitespace from each line of the input.
-/// Returns the trimmed content and whether any modifications were made.
-fn trim_trailing_whitespace(content: &str) -> TrimResult {
-    let mut output = String::with_capacity(content.len());
-    let mut modified = false;
-
-    for line in content.lines() {
-        let trimmed = line.trim_end();
-        if trimmed.len() != line.len() {
-            modified = true;
-        }
-        output.push_str(trimmed);
-        output.push('\n');
-    }
-
-    // Preserve original behavior: if file didn't end with newline, remove the added one
-    if !content.ends_with('\n') && !output.is_empty() {
-        output.pop();
-    }
-
-    TrimResult {
-        content: output,
-        modified,
-    }
-}
-
-/// Individual file processing with security validation and atomicity.
-fn process_file(path: &Path, verbose: bool) -> io::Result<()> {
-    // Double-check it's not a symlink (defense in depth)
-    let original_metadata = fs::symlink_metadata(path)?;
-    if original_metadata.file_type().is_symlink() {
-        if verbose {
-            println!("  Skipped (symlink): {:?}", path);
-        }
-        return Ok(());
-    }
-
-    if !original_metadata.is_file() {
-        return Ok(());
-    }
-
-    if verbose {
-        println!("  Checking: {:?}", path);
-    }
-
-    let mut buffer = Vec::new();
-    {
-        let mut file = File::open(path)?;
-        file.read_to_end(&mut buffer)?;
-    }
-
-    // Binary file protection via UTF-8 validation.
-    let content = match std::str::from_utf8(&buffer) {
-        Ok(s) => s,
-        Err(_) => {
-            if verbose {
-                println!("  Skipped (binary): {:?}", path);
-            }
-            return Ok(());
-        }
-    };
-
-    let result = trim_trailing_whitespace(content);
-
-    if result.modified {
-        // Generate unique temp name (prevents collisions and symlink attacks)
-        let temp_path = generate_temp_path(path);
-
-        // Check if temp file already exists (shouldn't, but for safety)
-        if temp_path.exists() {
-            return Err(io::Error::new(
-                io::ErrorKind::AlreadyExists,
-                format!("Temporary file already exists: {:?}", temp_path),
-            ));
-        }
-
-        {
-            let mut temp_file = File::create(&temp_path)?;
-            temp_file.write_all(result.content.as_bytes())?;
-            temp_file.sync_all()?;
-        }
-
-        // Preserve original file permissions
-        if let Err(e) = preserve_permissions(&temp_path, &original_metadata) {
-            // If permission preservation fails, remove temp file and propagate error
-            let _ = fs::remove_file(&temp_path);
-            return Err(e);
-        }
-
-        // Atomic rename
-        if let Err(e) = fs::rename(&temp_path, path) {
-            // If rename fails, remove temp file
-            let _ = fs::remove_file(&temp_path);
-            return Err(e);
-        }
-
-        println!("  Processed: {:?}", path);
-    } else if verbose {
-        println!("  Unchanged: {:?}", path);
-    }
-
-    Ok(())
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::os::unix::fs::PermissionsExt;
-    use std::sync::atomic::{AtomicU64, Ordering};
-
-    static TEST_COUNTER: AtomicU64 = AtomicU64::new(0);
-
-    fn create_test_dir() -> PathBuf {
-        let counter = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
-        let dir = std::env::temp_dir().join(format!(
-            "rtrim_test_{}_{}_{}",
-            process::id(),
-            counter,
-            SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_nanos()
-        ));
-        fs::create_dir_all(&dir).unwrap();
-        dir
-    }
-
-    fn cleanup_test_dir(dir: &Path) {
-        let _ = fs::remove_dir_all(dir);
-    }
-
-    // ==================== Argument Parsing Tests ====================
-
-    #[test]
-    fn test_parse_config_help_long() {
-        let args = vec!["rtrim".to_string(), "--help".to_string()];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::Help);
-    }
-
-    #[test]
-    fn test_parse_config_help_short() {
-        let args = vec!["rtrim".to_string(), "-h".to_string()];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::Help);
-    }
-
-    #[test]
-    fn test_parse_config_file_mode() {
-        let args = vec![
-            "rtrim".to_string(),
-            "--file".to_string(),
-            "test.txt".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::File);
-        assert_eq!(config.path, PathBuf::from("test.txt"));
-    }
-
-    #[test]
-    fn test_parse_config_folder_mode() {
-        let args = vec![
-            "rtrim".to_string(),
-            "--folder".to_string(),
-            "./src".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::Folder);
-        assert_eq!(config.path, PathBuf::from("./src"));
-    }
-
-    #[test]
-    fn test_parse_config_no_args() {
-        let args = vec!["rtrim".to_string()];
-        let result = parse_config(&args);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_parse_config_invalid_flag() {
-        let args = vec!["rtrim".to_string(), "--invalid".to_string()];
-        let result = parse_config(&args);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Invalid flag"));
-    }
-
-    #[test]
-    fn test_parse_config_file_missing_path() {
-        let args = vec!["rtrim".to_string(), "--file".to_string()];
-        let result = parse_config(&args);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_parse_config_folder_missing_path() {
-        let args = vec!["rtrim".to_string(), "--folder".to_string()];
-        let result = parse_config(&args);
-        assert!(result.is_err());
-    }
-
-    // ==================== Trim Logic Tests ====================
-
-    #[test]
-    fn test_trim_trailing_spaces() {
-        let input = "hello world   \n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "hello world\n");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_trailing_tabs() {
-        let input = "hello world\t\t\n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "hello world\n");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_mixed_whitespace() {
-        let input = "hello world \t \t\n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "hello world\n");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_multiple_lines() {
-        let input = "line1   \nline2\t\nline3 \t \n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "line1\nline2\nline3\n");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_no_changes_needed() {
-        let input = "hello world\nno trailing\n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "hello world\nno trailing\n");
-        assert!(!result.modified);
-    }
-
-    #[test]
-    fn test_trim_preserves_no_final_newline() {
-        let input = "no newline at end   ";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "no newline at end");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_preserves_final_newline() {
-        let input = "has newline   \n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "has newline\n");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_empty_file() {
-        let input = "";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "");
-        assert!(!result.modified);
-    }
-
-    #[test]
-    fn test_trim_only_newline() {
-        let input = "\n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "\n");
-        assert!(!result.modified);
-    }
-
-    #[test]
-    fn test_trim_only_whitespace_line() {
-        let input = "   \n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "\n");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_multiple_empty_lines() {
-        let input = "text\n\n\n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "text\n\n\n");
-        assert!(!result.modified);
-    }
-
-    #[test]
-    fn test_trim_preserves_leading_whitespace() {
-        let input = "    indented line   \n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "    indented line\n");
-        assert!(result.modified);
-    }
-
-    #[test]
-    fn test_trim_carriage_return() {
-        // Note: Rust's lines() treats \r\n as a line separator,
-        // so \r is not part of the line content to be trimmed.
-        // This test verifies that CRLF files are handled correctly.
-        let input = "windows line\r\n";
-        let result = trim_trailing_whitespace(input);
-        // lines() already strips \r from line endings
-        assert_eq!(result.content, "windows line\n");
-        // No trailing whitespace in the line itself, so not modified
-        assert!(!result.modified);
-    }
-
-    #[test]
-    fn test_trim_crlf_with_trailing_spaces() {
-        // CRLF with actual trailing spaces before \r\n
-        let input = "windows line   \r\n";
-        let result = trim_trailing_whitespace(input);
-        assert_eq!(result.content, "windows line\n");
-        assert!(result.modified);
-    }
-
-    // ==================== Directory Ignore Tests ====================
-
-    #[test]
-    fn test_should_ignore_git() {
-        assert!(should_ignore_dir(Path::new("/project/.git")));
-    }
-
-    #[test]
-    fn test_should_ignore_node_modules() {
-        assert!(should_ignore_dir(Path::new("/project/node_modules")));
-    }
-
-    #[test]
-    fn test_should_ignore_target() {
-        assert!(should_ignore_dir(Path::new("/project/target")));
-    }
-
-    #[test]
-    fn test_should_ignore_idea() {
-        assert!(should_ignore_dir(Path::new("/project/.idea")));
-    }
-
-    #[test]
-    fn test_should_ignore_vscode() {
-        assert!(should_ignore_dir(Path::new("/project/.vscode")));
-    }
-
-    #[test]
-    fn test_should_ignore_hidden_dirs() {
-        assert!(should_ignore_dir(Path::new("/project/.hidden")));
-        assert!(should_ignore_dir(Path::new("/project/.config")));
-    }
-
-    #[test]
-    fn test_should_not_ignore_src() {
-        assert!(!should_ignore_dir(Path::new("/project/src")));
-    }
-
-    #[test]
-    fn test_should_not_ignore_regular_dir() {
-        assert!(!should_ignore_dir(Path::new("/project/lib")));
-    }
-
-    // ==================== Temp Path Generation Tests ====================
-
-    #[test]
-    fn test_generate_temp_path_format() {
-        let original = Path::new("/tmp/test.txt");
-        let temp = generate_temp_path(original);
-
-        let temp_name = temp.file_name().unwrap().to_str().unwrap();
-        assert!(temp_name.starts_with(".test.txt."));
-        assert!(temp_name.ends_with(".tmp"));
-        assert_eq!(temp.parent().unwrap(), Path::new("/tmp"));
-    }
-
-    #[test]
-    fn test_generate_temp_path_uniqueness() {
-        let original = Path::new("/tmp/test.txt");
-        let temp1 = generate_temp_path(original);
-        std::thread::sleep(std::time::Duration::from_nanos(1));
-        let temp2 = generate_temp_path(original);
-
-        // Should generate different names due to timestamp
-        assert_ne!(temp1, temp2);
-    }
-
-    #[test]
-    fn test_generate_temp_path_hidden() {
-        let original = Path::new("/tmp/test.txt");
-        let temp = generate_temp_path(original);
-
-        let temp_name = temp.file_name().unwrap().to_str().unwrap();
-        assert!(temp_name.starts_with('.'), "Temp file should be hidden");
-    }
-
-    // ==================== Integration Tests ====================
-
-    #[test]
-    fn test_process_file_removes_trailing_spaces() {
-        let test_dir = create_test_dir();
-        let test_file = test_dir.join("test.txt");
-
-        fs::write(&test_file, "hello   \nworld\t\n").unwrap();
-        process_file(&test_file, false).unwrap();
-
-        let content = fs::read_to_string(&test_file).unwrap();
-        assert_eq!(content, "hello\nworld\n");
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_process_file_preserves_permissions() {
-        let test_dir = create_test_dir();
-        let test_file = test_dir.join("test.txt");
-
-        fs::write(&test_file, "hello   \n").unwrap();
-        fs::set_permissions(&test_file, Permissions::from_mode(0o754)).unwrap();
-
-        process_file(&test_file, false).unwrap();
-
-        let metadata = fs::metadata(&test_file).unwrap();
-        assert_eq!(metadata.permissions().mode() & 0o777, 0o754);
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_process_file_ignores_binary() {
-        let test_dir = create_test_dir();
-        let test_file = test_dir.join("binary.bin");
-
-        // Write invalid UTF-8
-        fs::write(&test_file, &[0xFF, 0xFE, 0x00, 0x01]).unwrap();
-        let original_content = fs::read(&test_file).unwrap();
-
-        process_file(&test_file, false).unwrap();
-
-        let new_content = fs::read(&test_file).unwrap();
-        assert_eq!(original_content, new_content);
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_process_file_no_changes_when_clean() {
-        let test_dir = create_test_dir();
-        let test_file = test_dir.join("clean.txt");
-
-        let original = "hello\nworld\n";
-        fs::write(&test_file, original).unwrap();
-        let mtime_before = fs::metadata(&test_file).unwrap().modified().unwrap();
-
-        // Small delay to ensure mtime would change if file is rewritten
-        std::thread::sleep(std::time::Duration::from_millis(10));
-
-        process_file(&test_file, false).unwrap();
-
-        let mtime_after = fs::metadata(&test_file).unwrap().modified().unwrap();
-        // File should not have been modified
-        assert_eq!(mtime_before, mtime_after);
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_process_file_ignores_symlink() {
-        let test_dir = create_test_dir();
-        let original_file = test_dir.join("original.txt");
-        let symlink_file = test_dir.join("symlink.txt");
-
-        fs::write(&original_file, "hello   \n").unwrap();
-        std::os::unix::fs::symlink(&original_file, &symlink_file).unwrap();
-
-        process_file(&symlink_file, false).unwrap();
-
-        // Original file should not have been modified
-        let content = fs::read_to_string(&original_file).unwrap();
-        assert_eq!(content, "hello   \n");
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_process_folder_recursive() {
-        let test_dir = create_test_dir();
-        let sub_dir = test_dir.join("subdir");
-        fs::create_dir(&sub_dir).unwrap();
-
-        let file1 = test_dir.join("file1.txt");
-        let file2 = sub_dir.join("file2.txt");
-
-        fs::write(&file1, "line1   \n").unwrap();
-        fs::write(&file2, "line2\t\n").unwrap();
-
-        process_folder(&test_dir, false).unwrap();
-
-        assert_eq!(fs::read_to_string(&file1).unwrap(), "line1\n");
-        assert_eq!(fs::read_to_string(&file2).unwrap(), "line2\n");
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_process_folder_ignores_git() {
-        let test_dir = create_test_dir();
-        let git_dir = test_dir.join(".git");
-        fs::create_dir(&git_dir).unwrap();
-
-        let git_file = git_dir.join("config");
-        fs::write(&git_file, "content   \n").unwrap();
-
-        process_folder(&test_dir, false).unwrap();
-
-        // File inside .git should not have been modified
-        let content = fs::read_to_string(&git_file).unwrap();
-        assert_eq!(content, "content   \n");
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_no_temp_file_left_on_success() {
-        let test_dir = create_test_dir();
-        let test_file = test_dir.join("test.txt");
-
-        fs::write(&test_file, "hello   \n").unwrap();
-        process_file(&test_file, false).unwrap();
-
-        // Check no .tmp files are left
-        let entries: Vec<_> = fs::read_dir(&test_dir)
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .filter(|e| {
-                e.path()
-                    .extension()
-                    .map(|ext| ext == "tmp")
-                    .unwrap_or(false)
-            })
-            .collect();
-
-        assert!(entries.is_empty(), "No temp files should remain");
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    // ==================== Verbose Flag Tests ====================
-
-    #[test]
-    fn test_parse_config_verbose_long() {
-        let args = vec![
-            "rtrim".to_string(),
-            "--file".to_string(),
-            "test.txt".to_string(),
-            "--verbose".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::File);
-        assert!(config.verbose);
-    }
-
-    #[test]
-    fn test_parse_config_verbose_short() {
-        let args = vec![
-            "rtrim".to_string(),
-            "--folder".to_string(),
-            "./src".to_string(),
-            "-v".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::Folder);
-        assert!(config.verbose);
-    }
-
-    #[test]
-    fn test_parse_config_verbose_before_mode() {
-        let args = vec![
-            "rtrim".to_string(),
-            "--verbose".to_string(),
-            "--file".to_string(),
-            "test.txt".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::File);
-        assert!(config.verbose);
-    }
-
-    #[test]
-    fn test_parse_config_no_verbose() {
-        let args = vec![
-            "rtrim".to_string(),
-            "--file".to_string(),
-            "test.txt".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert!(!config.verbose);
-    }
-
-    #[test]
-    fn test_parse_config_double_dash_path() {
-        // Path "-v" after "--" should be treated as literal path, not as verbose flag
-        let args = vec![
-            "rtrim".to_string(),
-            "--file".to_string(),
-            "--".to_string(),
-            "-v".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::File);
-        assert_eq!(config.path, PathBuf::from("-v"));
-        assert!(!config.verbose);
-    }
-
-    #[test]
-    fn test_parse_config_verbose_before_double_dash() {
-        // -v before "--" is recognized as verbose, "--verbose" after "--" is the path
-        let args = vec![
-            "rtrim".to_string(),
-            "-v".to_string(),
-            "--file".to_string(),
-            "--".to_string(),
-            "--verbose".to_string(),
-        ];
-        let config = parse_config(&args).unwrap();
-        assert_eq!(config.mode, Mode::File);
-        assert_eq!(config.path, PathBuf::from("--verbose"));
-        assert!(config.verbose);
-    }
-
-    #[test]
-    fn test_process_file_verbose_mode() {
-        let test_dir = create_test_dir();
-        let test_file = test_dir.join("test.txt");
-
-        fs::write(&test_file, "hello   \n").unwrap();
-        // Should not panic in verbose mode
-        process_file(&test_file, true).unwrap();
-
-        let content = fs::read_to_string(&test_file).unwrap();
-        assert_eq!(content, "hello\n");
-
-        cleanup_test_dir(&test_dir);
-    }
-
-    #[test]
-    fn test_process_folder_verbose_mode() {
-        let test_dir = create_test_dir();
-        let test_file = test_dir.join("test.txt");
-
-        fs::write(&test_file, "hello   \n").unwrap();
-        // Should not panic in verbose mode
-        process_folder(&test_dir, true).unwrap();
-
-        let content = fs::read_to_string(&test_file).unwrap();
-        assert_eq!(content, "hello\n");
-
-        cleanup_test_dir(&test_dir);
-    }
-}
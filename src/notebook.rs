@@ -0,0 +1,295 @@
+//! Locating the source strings of Jupyter notebook code cells.
+//!
+//! A notebook is JSON, but rewriting it through a serializer would reformat the
+//! whole file. Instead this module parses just enough JSON to find the byte span
+//! of every source string in a code cell, so the caller can splice trimmed
+//! strings back into the original text and leave everything else untouched.
+
+use std::ops::Range;
+
+/// A JSON string literal: its span in the document (quotes included) and its
+/// decoded text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonString {
+    pub span: Range<usize>,
+    pub text: String,
+}
+
+/// The document is not JSON, or not shaped like a notebook.
+#[derive(Debug, PartialEq)]
+pub struct MalformedNotebook;
+
+#[derive(Debug)]
+enum Value {
+    Object(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Str(JsonString),
+    Scalar,
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the source strings of every code cell, in document order. Markdown
+/// and raw cells, outputs and metadata are not included.
+pub fn code_cell_sources(content: &str) -> Result<Vec<JsonString>, MalformedNotebook> {
+    let mut parser = Parser {
+        bytes: content.as_bytes(),
+        pos: 0,
+    };
+    let root = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos != content.len() {
+        return Err(MalformedNotebook);
+    }
+
+    let Some(Value::Array(cells)) = root.get("cells") else {
+        return Err(MalformedNotebook);
+    };
+    let mut sources = Vec::new();
+    for cell in cells {
+        let is_code = matches!(cell.get("cell_type"), Some(Value::Str(s)) if s.text == "code");
+        if !is_code {
+            continue;
+        }
+        match cell.get("source") {
+            Some(Value::Str(line)) => sources.push(line.clone()),
+            Some(Value::Array(lines)) => {
+                for line in lines {
+                    match line {
+                        Value::Str(line) => sources.push(line.clone()),
+                        _ => return Err(MalformedNotebook),
+                    }
+                }
+            }
+            _ => return Err(MalformedNotebook),
+        }
+    }
+    Ok(sources)
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while matches!(self.bytes.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), MalformedNotebook> {
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(MalformedNotebook)
+        }
+    }
+
+    /// Consumes `byte` if it is the next token.
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.bytes.get(self.pos) == Some(&byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn value(&mut self) -> Result<Value, MalformedNotebook> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => self.string().map(Value::Str),
+            Some(b'-' | b'0'..=b'9' | b't' | b'f' | b'n') => self.scalar(),
+            _ => Err(MalformedNotebook),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, MalformedNotebook> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        if self.eat(b'}') {
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?.text;
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            if self.eat(b'}') {
+                return Ok(Value::Object(members));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, MalformedNotebook> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.eat(b']') {
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.eat(b']') {
+                return Ok(Value::Array(items));
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    /// Numbers, `true`, `false` and `null`; their values are never needed.
+    fn scalar(&mut self) -> Result<Value, MalformedNotebook> {
+        let start = self.pos;
+        while matches!(
+            self.bytes.get(self.pos),
+            Some(b'-' | b'+' | b'.' | b'0'..=b'9' | b'a'..=b'z' | b'E')
+        ) {
+            self.pos += 1;
+        }
+        let token = &self.bytes[start..self.pos];
+        let is_number = token[0] == b'-' || token[0].is_ascii_digit();
+        if is_number || matches!(token, b"true" | b"false" | b"null") {
+            Ok(Value::Scalar)
+        } else {
+            Err(MalformedNotebook)
+        }
+    }
+
+    fn string(&mut self) -> Result<JsonString, MalformedNotebook> {
+        let start = self.pos;
+        if self.bytes.get(self.pos) != Some(&b'"') {
+            return Err(MalformedNotebook);
+        }
+        self.pos += 1;
+
+        let mut text = Vec::new();
+        loop {
+            let byte = *self.bytes.get(self.pos).ok_or(MalformedNotebook)?;
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self.bytes.get(self.pos).ok_or(MalformedNotebook)?;
+                    self.pos += 1;
+                    let decoded = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(MalformedNotebook),
+                    };
+                    let mut buf = [0; 4];
+                    text.extend_from_slice(decoded.encode_utf8(&mut buf).as_bytes());
+                }
+                byte => text.push(byte),
+            }
+        }
+
+        Ok(JsonString {
+            span: start..self.pos,
+            text: String::from_utf8(text).map_err(|_| MalformedNotebook)?,
+        })
+    }
+
+    /// Decodes the digits of a `\u` escape, combining surrogate pairs.
+    fn unicode_escape(&mut self) -> Result<char, MalformedNotebook> {
+        let high = self.hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or(MalformedNotebook);
+        }
+        if self.bytes.get(self.pos..self.pos + 2) != Some(b"\\u") {
+            return Err(MalformedNotebook);
+        }
+        self.pos += 2;
+        let low = self.hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(MalformedNotebook);
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)).ok_or(MalformedNotebook)
+    }
+
+    fn hex4(&mut self) -> Result<u32, MalformedNotebook> {
+        let digits = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .and_then(|d| std::str::from_utf8(d).ok())
+            .ok_or(MalformedNotebook)?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| MalformedNotebook)?;
+        self.pos += 4;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::encode_string;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "metadata": {},
+   "source": ["# Title  \n"]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {},
+   "outputs": [{"name": "stdout", "text": ["out  \n"]}],
+   "source": ["x = 1  \n", "print(\"é\")\t"]
+  }
+ ],
+ "nbformat": 4
+}
+"##;
+
+    #[test]
+    fn test_only_code_cell_sources_are_returned() {
+        let sources = code_cell_sources(NOTEBOOK).unwrap();
+        let texts: Vec<&str> = sources.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["x = 1  \n", "print(\"é\")\t"]);
+        assert_eq!(&NOTEBOOK[sources[0].span.clone()], r#""x = 1  \n""#);
+    }
+
+    #[test]
+    fn test_string_source_and_malformed_input() {
+        let doc = r#"{"cells": [{"cell_type": "code", "source": "a  \nb"}]}"#;
+        let sources = code_cell_sources(doc).unwrap();
+        assert_eq!(sources[0].text, "a  \nb");
+
+        assert_eq!(code_cell_sources("{\"cells\": [}"), Err(MalformedNotebook));
+        assert_eq!(code_cell_sources("{}"), Err(MalformedNotebook));
+        assert_eq!(code_cell_sources("[] x"), Err(MalformedNotebook));
+    }
+
+    #[test]
+    fn test_encode_string_round_trips() {
+        let text = "say \"hi\"\\\n\té\u{1}";
+        let encoded = encode_string(text);
+        assert_eq!(encoded, "\"say \\\"hi\\\"\\\\\\n\\té\\u0001\"");
+        let doc = format!(
+            "{{\"cells\": [{{\"cell_type\": \"code\", \"source\": [{}]}}]}}",
+            encoded
+        );
+        assert_eq!(code_cell_sources(&doc).unwrap()[0].text, text);
+    }
+}
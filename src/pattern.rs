@@ -0,0 +1,642 @@
+//! A small regular expression engine.
+//!
+//! rtrim has no external dependencies, so this implements the commonly used
+//! subset of regex syntax needed by the pattern-based filters:
+//!
+//! - literals and escaped metacharacters (`\.`, `\*`, ...)
+//! - `.` (any character), `^` and `$` anchors
+//! - character classes `[abc]`, `[a-z]`, `[^...]` and `\d \w \s \D \W \S`
+//! - groups `( ... )` and alternation `|`
+//! - quantifiers `*`, `+`, `?`, `{n}`, `{n,}`, `{n,m}`, with a lazy `?` suffix
+//!
+//! Patterns are matched against a single line of text at a time. They are
+//! compiled to a program run as a Pike VM, which follows every way of
+//! matching in lockstep: a match takes time linear in the length of the line,
+//! and neither long lines nor nested quantifiers can blow up the stack.
+
+use std::fmt;
+
+/// A compiled regular expression.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    program: Vec<Inst>,
+}
+
+/// Most instructions a pattern may compile to. Counted repetitions copy
+/// their operand, so `(a{100}){100}` would otherwise grow without bound.
+const MAX_PROGRAM: usize = 10_000;
+
+/// Error returned when a pattern cannot be compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    pub message: &'static str,
+    pub position: usize,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+type Alternation = Vec<Sequence>;
+type Sequence = Vec<Piece>;
+
+#[derive(Debug, Clone)]
+struct Piece {
+    node: Node,
+    min: usize,
+    max: Option<usize>,
+    greedy: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Group(Alternation),
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            ClassItem::Range(lo, hi) => lo <= c && c <= hi,
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => (c.is_alphanumeric() || c == '_') != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        self.items.iter().any(|item| item.matches(c)) != self.negated
+    }
+}
+
+impl Pattern {
+    /// Compiles `source` into a pattern.
+    pub fn new(source: &str) -> Result<Pattern, PatternError> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut parser = Parser { chars, pos: 0 };
+        let root = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unmatched ')'"));
+        }
+        let mut compiler = Compiler {
+            program: Vec::new(),
+        };
+        compiler.alternation(&root)?;
+        compiler.program.push(Inst::Match);
+        Ok(Pattern {
+            source: source.to_string(),
+            program: compiler.program,
+        })
+    }
+
+    /// The pattern as written by the user.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns true if the pattern matches anywhere in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    /// Returns the char-index span of the leftmost match in `text`. Among
+    /// matches starting there, the one a backtracking engine would find wins:
+    /// threads are kept in priority order, and a match drops every thread
+    /// ranked below it.
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut found = None;
+        for pos in 0..=chars.len() {
+            if found.is_none() {
+                // A match starting here ranks below any that started earlier.
+                current.add(&self.program, 0, pos, pos, chars.len());
+            } else if current.list.is_empty() {
+                break;
+            }
+            next.clear();
+            for &(pc, start) in &current.list {
+                let matched = match &self.program[pc] {
+                    Inst::Match => {
+                        found = Some((start, pos));
+                        break;
+                    }
+                    Inst::Char(c) => chars.get(pos) == Some(c),
+                    Inst::Any => pos < chars.len(),
+                    Inst::Class(class) => chars.get(pos).is_some_and(|&c| class.matches(c)),
+                    _ => false,
+                };
+                if matched {
+                    next.add(&self.program, pc + 1, start, pos + 1, chars.len());
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+        found
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &'static str) -> PatternError {
+        PatternError {
+            message,
+            position: self.pos,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn alternation(&mut self) -> Result<Alternation, PatternError> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Sequence, PatternError> {
+        let mut pieces = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let node = self.atom()?;
+            pieces.push(self.quantifier(node)?);
+        }
+        Ok(pieces)
+    }
+
+    fn atom(&mut self) -> Result<Node, PatternError> {
+        let c = self.peek().ok_or_else(|| self.error("unexpected end"))?;
+        self.pos += 1;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                let group = self.alternation()?;
+                if self.peek() != Some(')') {
+                    return Err(self.error("missing ')'"));
+                }
+                self.pos += 1;
+                Node::Group(group)
+            }
+            '[' => Node::Class(self.class()?),
+            '\\' => match self.escape()? {
+                Escaped::Char(c) => Node::Char(c),
+                Escaped::Class(item) => Node::Class(Class {
+                    negated: false,
+                    items: vec![item],
+                }),
+            },
+            '*' | '+' | '?' | '{' => return Err(self.error("quantifier without a target")),
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Escaped, PatternError> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("trailing backslash"))?;
+        self.pos += 1;
+        Ok(match c {
+            'd' => Escaped::Class(ClassItem::Digit(false)),
+            'D' => Escaped::Class(ClassItem::Digit(true)),
+            'w' => Escaped::Class(ClassItem::Word(false)),
+            'W' => Escaped::Class(ClassItem::Word(true)),
+            's' => Escaped::Class(ClassItem::Space(false)),
+            'S' => Escaped::Class(ClassItem::Space(true)),
+            't' => Escaped::Char('\t'),
+            'n' => Escaped::Char('\n'),
+            'r' => Escaped::Char('\r'),
+            c => Escaped::Char(c),
+        })
+    }
+
+    fn class(&mut self) -> Result<Class, PatternError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("missing ']'"))?;
+            self.pos += 1;
+            // A ']' right after the opening bracket is a literal.
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = if c == '\\' {
+                match self.escape()? {
+                    Escaped::Char(c) => c,
+                    Escaped::Class(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                }
+            } else {
+                c
+            };
+
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']') {
+                self.pos += 1;
+                let hi = self.peek().ok_or_else(|| self.error("missing ']'"))?;
+                self.pos += 1;
+                let hi = if hi == '\\' {
+                    match self.escape()? {
+                        Escaped::Char(c) => c,
+                        Escaped::Class(_) => return Err(self.error("invalid class range")),
+                    }
+                } else {
+                    hi
+                };
+                if hi < lo {
+                    return Err(self.error("invalid class range"));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+            }
+        }
+        Ok(Class { negated, items })
+    }
+
+    fn quantifier(&mut self, node: Node) -> Result<Piece, PatternError> {
+        let (min, max) = match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                (0, None)
+            }
+            Some('+') => {
+                self.pos += 1;
+                (1, None)
+            }
+            Some('?') => {
+                self.pos += 1;
+                (0, Some(1))
+            }
+            Some('{') => {
+                let start = self.pos;
+                match self.braces() {
+                    Some(bounds) => bounds,
+                    None => {
+                        self.pos = start;
+                        return Err(self.error("invalid repetition"));
+                    }
+                }
+            }
+            _ => {
+                return Ok(Piece {
+                    node,
+                    min: 1,
+                    max: Some(1),
+                    greedy: true,
+                })
+            }
+        };
+        let greedy = if self.peek() == Some('?') {
+            self.pos += 1;
+            false
+        } else {
+            true
+        };
+        if matches!(node, Node::Start | Node::End) {
+            return Err(self.error("quantifier on an anchor"));
+        }
+        Ok(Piece {
+            node,
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}`, leaving `pos` after the closing brace.
+    fn braces(&mut self) -> Option<(usize, Option<usize>)> {
+        self.pos += 1;
+        let min = self.number()?;
+        let max = if self.peek() == Some(',') {
+            self.pos += 1;
+            if self.peek() == Some('}') {
+                None
+            } else {
+                Some(self.number()?)
+            }
+        } else {
+            Some(min)
+        };
+        if self.peek() != Some('}') || max.is_some_and(|max| max < min) {
+            return None;
+        }
+        self.pos += 1;
+        Some((min, max))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .parse()
+            .ok()
+    }
+}
+
+enum Escaped {
+    Char(char),
+    Class(ClassItem),
+}
+
+/// One step of a compiled pattern.
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    /// Continues at both targets, preferring the first.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, PatternError> {
+        if self.program.len() >= MAX_PROGRAM {
+            return Err(PatternError {
+                message: "pattern too large",
+                position: 0,
+            });
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn alternation(&mut self, alt: &Alternation) -> Result<(), PatternError> {
+        let mut jumps = Vec::new();
+        for (index, seq) in alt.iter().enumerate() {
+            if index + 1 == alt.len() {
+                self.sequence(seq)?;
+                break;
+            }
+            let split = self.push(Inst::Split(0, 0))?;
+            self.sequence(seq)?;
+            jumps.push(self.push(Inst::Jump(0))?);
+            self.program[split] = Inst::Split(split + 1, self.program.len());
+        }
+        let end = self.program.len();
+        for jump in jumps {
+            self.program[jump] = Inst::Jump(end);
+        }
+        Ok(())
+    }
+
+    fn sequence(&mut self, seq: &Sequence) -> Result<(), PatternError> {
+        seq.iter().try_for_each(|piece| self.piece(piece))
+    }
+
+    /// Emits the required copies of the node, then either a loop or the
+    /// optional copies up to the maximum.
+    fn piece(&mut self, piece: &Piece) -> Result<(), PatternError> {
+        for _ in 0..piece.min {
+            self.node(&piece.node)?;
+        }
+        let Some(max) = piece.max else {
+            let split = self.push(Inst::Split(0, 0))?;
+            self.node(&piece.node)?;
+            self.push(Inst::Jump(split))?;
+            self.program[split] = self.choice(split + 1, self.program.len(), piece.greedy);
+            return Ok(());
+        };
+        let mut splits = Vec::new();
+        for _ in piece.min..max {
+            splits.push(self.push(Inst::Split(0, 0))?);
+            self.node(&piece.node)?;
+        }
+        let end = self.program.len();
+        for split in splits {
+            self.program[split] = self.choice(split + 1, end, piece.greedy);
+        }
+        Ok(())
+    }
+
+    /// A split between taking one more repetition and moving on.
+    fn choice(&self, repeat: usize, skip: usize, greedy: bool) -> Inst {
+        if greedy {
+            Inst::Split(repeat, skip)
+        } else {
+            Inst::Split(skip, repeat)
+        }
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), PatternError> {
+        let inst = match node {
+            Node::Char(c) => Inst::Char(*c),
+            Node::Any => Inst::Any,
+            Node::Class(class) => Inst::Class(class.clone()),
+            Node::Start => Inst::Start,
+            Node::End => Inst::End,
+            Node::Group(alt) => return self.alternation(alt),
+        };
+        self.push(inst).map(drop)
+    }
+}
+
+/// The threads of the VM at one position of the text, in priority order.
+/// Each is a program counter and the position where its match started.
+struct Threads {
+    list: Vec<(usize, usize)>,
+    /// Generation in which each instruction was last reached, so that a
+    /// thread is only added once per position and empty loops end.
+    seen: Vec<usize>,
+    generation: usize,
+    stack: Vec<usize>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            list: Vec::new(),
+            seen: vec![0; len],
+            generation: 1,
+            stack: Vec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.generation += 1;
+    }
+
+    /// Adds the thread at `pc`, following jumps, splits and anchors up to the
+    /// instructions that consume a character or match.
+    fn add(&mut self, program: &[Inst], pc: usize, start: usize, pos: usize, len: usize) {
+        self.stack.push(pc);
+        while let Some(pc) = self.stack.pop() {
+            if self.seen[pc] == self.generation {
+                continue;
+            }
+            self.seen[pc] = self.generation;
+            match program[pc] {
+                Inst::Jump(to) => self.stack.push(to),
+                Inst::Split(first, second) => {
+                    // Popped first, so explored first.
+                    self.stack.push(second);
+                    self.stack.push(first);
+                }
+                Inst::Start if pos == 0 => self.stack.push(pc + 1),
+                Inst::End if pos == len => self.stack.push(pc + 1),
+                Inst::Start | Inst::End => {}
+                _ => self.list.push((pc, start)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Pattern::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn test_literals_and_any() {
+        assert!(is_match("license", "MIT license header"));
+        assert!(!is_match("license", "nothing here"));
+        assert!(is_match("a.c", "xabcx"));
+        assert!(is_match(r"a\.c", "a.c"));
+        assert!(!is_match(r"a\.c", "abc"));
+    }
+
+    #[test]
+    fn test_anchors() {
+        assert!(is_match("^// SPDX", "// SPDX-License-Identifier: MIT"));
+        assert!(!is_match("^// SPDX", "x // SPDX"));
+        assert!(is_match("end$", "the end"));
+        assert!(!is_match("end$", "end of story"));
+        assert!(is_match("^$", ""));
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        assert!(is_match("^ab*c$", "ac"));
+        assert!(is_match("^ab*c$", "abbbc"));
+        assert!(!is_match("^ab+c$", "ac"));
+        assert!(is_match("^colou?r$", "color"));
+        assert!(is_match("^a{2,3}$", "aaa"));
+        assert!(!is_match("^a{2,3}$", "aaaa"));
+        assert!(is_match("^a{2,}$", "aaaaa"));
+        assert!(is_match("^a{2}$", "aa"));
+    }
+
+    #[test]
+    fn test_lazy_quantifier_span() {
+        let pattern = Pattern::new("<.+?>").unwrap();
+        assert_eq!(pattern.find("<a><b>"), Some((0, 3)));
+        let greedy = Pattern::new("<.+>").unwrap();
+        assert_eq!(greedy.find("<a><b>"), Some((0, 6)));
+    }
+
+    #[test]
+    fn test_classes() {
+        assert!(is_match("^[a-c]+$", "abcabc"));
+        assert!(!is_match("^[a-c]+$", "abd"));
+        assert!(is_match("^[^0-9]+$", "abc"));
+        assert!(is_match(r"^\d{4}-\d{2}$", "2024-01"));
+        assert!(is_match(r"\s$", "trailing "));
+        assert!(is_match(r"^\w+$", "snake_case1"));
+        assert!(is_match("[]x]", "]"));
+        assert!(is_match("[a-]", "-"));
+        assert!(is_match(r"^\}+$", "}}"));
+    }
+
+    #[test]
+    fn test_groups_and_alternation() {
+        assert!(is_match("^(foo|bar)baz$", "barbaz"));
+        assert!(!is_match("^(foo|bar)baz$", "quxbaz"));
+        assert!(is_match("^(ab)+$", "ababab"));
+        assert!(is_match("cat|dog", "hotdog"));
+    }
+
+    #[test]
+    fn test_empty_loops_terminate() {
+        assert!(is_match("^(a*)*$", "aaa"));
+        assert!(is_match("^(a?)+b$", "b"));
+    }
+
+    #[test]
+    fn test_long_line() {
+        let line = "a".repeat(200_000);
+        assert!(!is_match("a.*z", &line));
+        let pattern = Pattern::new("a.*z").unwrap();
+        assert_eq!(pattern.find(&format!("{}z", line)), Some((0, 200_001)));
+    }
+
+    #[test]
+    fn test_nested_quantifiers() {
+        let line = "a".repeat(10_000);
+        assert!(!is_match("(a*)*b", &line));
+        assert!(!is_match("^(a|aa)+$", &format!("{}b", line)));
+        assert!(is_match("(a*)*b", &format!("{}b", line)));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        assert!(Pattern::new("(a{100}){200}").is_err());
+        assert!(Pattern::new("(abc").is_err());
+        assert!(Pattern::new("abc)").is_err());
+        assert!(Pattern::new("[abc").is_err());
+        assert!(Pattern::new("*a").is_err());
+        assert!(Pattern::new("a{3,1}").is_err());
+        assert!(Pattern::new("[z-a]").is_err());
+        assert!(Pattern::new("\\").is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! Terminal progress bar for `--progress-bar` (`progress` feature).
+//!
+//! The bar is only rendered here; the reporter's printer thread draws it on
+//! stderr and keeps it below any other output.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Minimum time between two redraws, so fast runs don't flood the terminal.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(80);
+
+const BAR_WIDTH: usize = 30;
+
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Counts finished files against the total found by the counting pass.
+#[derive(Debug)]
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    last_draw: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    pub fn new(total: usize) -> Self {
+        Progress {
+            total,
+            done: AtomicUsize::new(0),
+            last_draw: Mutex::new(None),
+        }
+    }
+
+    /// Records one finished file. Returns the bar to draw, unless the last
+    /// redraw was too recent; the final file is always drawn.
+    pub fn tick(&self) -> Option<String> {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let now = Instant::now();
+        let mut last_draw = self.last_draw.lock().unwrap();
+        let due = last_draw.is_none_or(|last| now.duration_since(last) >= REDRAW_INTERVAL);
+        if !due && done < self.total {
+            return None;
+        }
+        *last_draw = Some(now);
+        Some(render(done, self.total))
+    }
+}
+
+/// Renders `[#####-----] done/total files` with a spinner in front.
+fn render(done: usize, total: usize) -> String {
+    let done = done.min(total);
+    let filled = (done * BAR_WIDTH).checked_div(total).unwrap_or(BAR_WIDTH);
+    let spinner = if done == total {
+        ' '
+    } else {
+        SPINNER[done % SPINNER.len()]
+    };
+    format!(
+        "{} [{}{}] {}/{} files",
+        spinner,
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        done,
+        total
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(0, 4),
+            format!("| [{}] 0/4 files", "-".repeat(BAR_WIDTH))
+        );
+        assert_eq!(
+            render(2, 4),
+            format!("- [{}{}] 2/4 files", "#".repeat(15), "-".repeat(15))
+        );
+        assert_eq!(
+            render(4, 4),
+            format!("  [{}] 4/4 files", "#".repeat(BAR_WIDTH))
+        );
+    }
+
+    #[test]
+    fn test_tick_throttles_but_draws_last_file() {
+        let progress = Progress::new(3);
+        assert!(progress.tick().is_some());
+        assert!(progress.tick().is_none());
+        assert_eq!(
+            progress.tick(),
+            Some(format!("  [{}] 3/3 files", "#".repeat(BAR_WIDTH)))
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Detection of lines whose trailing whitespace is significant in structured
+//! text formats, so the format-aware modes can leave them untouched.
+//!
+//! Every function returns one flag per `str::lines` item; `true` marks a line
+//! that must be preserved verbatim.
+
+use std::collections::VecDeque;
+
+/// Marks the content lines of YAML literal (`|`) and folded (`>`) block scalars.
+///
+/// A block scalar starts on a line whose value is a block indicator (optionally
+/// with chomping/indentation modifiers and a trailing comment) and continues over
+/// every following line that is blank or indented deeper than that line.
+pub fn yaml_block_scalars(content: &str) -> Vec<bool> {
+    let mut protected = Vec::new();
+    let mut block_parent: Option<usize> = None;
+
+    for line in content.lines() {
+        let indent = indentation(line);
+        let blank = line.trim().is_empty();
+
+        if let Some(parent) = block_parent {
+            if blank || indent > parent {
+                protected.push(true);
+                continue;
+            }
+            block_parent = None;
+        }
+
+        protected.push(false);
+        if is_block_scalar_header(line) {
+            block_parent = Some(indent);
+        }
+    }
+    protected
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Recognizes `key: |`, `- >-`, `--- |2` and similar block scalar headers.
+fn is_block_scalar_header(line: &str) -> bool {
+    let without_comment = match line.find(" #") {
+        Some(pos) => &line[..pos],
+        None => line,
+    };
+    let mut tokens = without_comment.split_whitespace().rev();
+    let Some(indicator) = tokens.next() else {
+        return false;
+    };
+
+    let mut chars = indicator.chars();
+    if !matches!(chars.next(), Some('|' | '>')) {
+        return false;
+    }
+    let modifiers: Vec<char> = chars.collect();
+    let valid_modifiers = modifiers.len() <= 2
+        && modifiers
+            .iter()
+            .filter(|c| **c == '-' || **c == '+')
+            .count()
+            <= 1
+        && modifiers.iter().filter(|c| c.is_ascii_digit()).count() <= 1
+        && modifiers
+            .iter()
+            .all(|c| *c == '-' || *c == '+' || ('1'..='9').contains(c));
+    if !valid_modifiers {
+        return false;
+    }
+
+    match tokens.next() {
+        None => true,
+        Some(previous) => previous.ends_with(':') || previous == "-" || previous == "---",
+    }
+}
+
+/// Marks the body lines of shell here-documents (`<<EOF`, `<<-'EOF'`, ...).
+///
+/// Trailing whitespace in a here-doc body is data whether the delimiter is
+/// quoted or not; quoting only changes how the delimiter word is spelled. The
+/// delimiter lines themselves are not protected. Several here-docs opened on
+/// one line are read back to back, as the shell does.
+pub fn shell_heredocs(content: &str) -> Vec<bool> {
+    let mut protected = Vec::new();
+    let mut pending: VecDeque<Heredoc> = VecDeque::new();
+
+    for line in content.lines() {
+        if let Some(current) = pending.front() {
+            let candidate = if current.strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                line
+            };
+            if candidate == current.delimiter {
+                pending.pop_front();
+                protected.push(false);
+            } else {
+                protected.push(true);
+            }
+            continue;
+        }
+        protected.push(false);
+        pending.extend(heredoc_redirections(line));
+    }
+    protected
+}
+
+/// A here-doc opened by a `<<` redirection, waiting for its delimiter line.
+#[derive(Debug, PartialEq, Eq)]
+struct Heredoc {
+    delimiter: String,
+    /// `<<-` form: leading tabs are ignored on the delimiter line.
+    strip_tabs: bool,
+}
+
+/// Finds the here-doc redirections on a command line, skipping quoted text,
+/// comments and here-strings (`<<<`).
+fn heredoc_redirections(line: &str) -> Vec<Heredoc> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut heredocs = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '\'' => i = skip_past(&chars, i + 1, '\''),
+            '"' => i = skip_past(&chars, i + 1, '"'),
+            '#' if i == 0 || chars[i - 1].is_whitespace() => break,
+            '<' if chars.get(i + 1) == Some(&'<') => {
+                if chars.get(i + 2) == Some(&'<') {
+                    i += 3;
+                    continue;
+                }
+                i += 2;
+                let strip_tabs = chars.get(i) == Some(&'-');
+                if strip_tabs {
+                    i += 1;
+                }
+                while matches!(chars.get(i), Some(' ' | '\t')) {
+                    i += 1;
+                }
+                let (delimiter, end) = delimiter_word(&chars, i);
+                i = end;
+                if !delimiter.is_empty() {
+                    heredocs.push(Heredoc {
+                        delimiter,
+                        strip_tabs,
+                    });
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    heredocs
+}
+
+/// Index just past the closing `quote` (or the end of the line if unclosed).
+fn skip_past(chars: &[char], start: usize, quote: char) -> usize {
+    let mut i = start;
+    while i < chars.len() {
+        if chars[i] == quote {
+            return i + 1;
+        }
+        if quote == '"' && chars[i] == '\\' {
+            i += 1;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// Reads a here-doc delimiter word starting at `start`, removing any quoting,
+/// and returns it with the index just past the word.
+fn delimiter_word(chars: &[char], start: usize) -> (String, usize) {
+    let mut word = String::new();
+    let mut i = start;
+    while let Some(&c) = chars.get(i) {
+        match c {
+            '\'' | '"' => {
+                let end = skip_past(chars, i + 1, c);
+                let closed = end > i + 1 && chars[end - 1] == c;
+                word.extend(&chars[i + 1..if closed { end - 1 } else { end }]);
+                i = end;
+            }
+            '\\' => {
+                if let Some(&escaped) = chars.get(i + 1) {
+                    word.push(escaped);
+                }
+                i += 2;
+            }
+            c if c.is_whitespace() || matches!(c, ';' | '&' | '|' | '<' | '>' | '(' | ')') => break,
+            c => {
+                word.push(c);
+                i += 1;
+            }
+        }
+    }
+    (word, i)
+}
+
+/// Marks the lines of a CSV file that end inside a quoted field.
+///
+/// A quoted field may span lines, and whitespace before such a line break is
+/// part of the field's value. A doubled quote (`""`) inside a quoted field is
+/// an escaped quote and leaves the field open, which toggling on every quote
+/// handles by itself.
+pub fn csv_quoted_line_ends(content: &str) -> Vec<bool> {
+    let mut in_quotes = false;
+    content
+        .lines()
+        .map(|line| {
+            in_quotes ^= line.matches('"').count() % 2 == 1;
+            in_quotes
+        })
+        .collect()
+}
+
+/// Elements whose text content keeps its whitespace when rendered.
+const PREFORMATTED_TAGS: [&str; 2] = ["pre", "textarea"];
+
+/// Marks the lines of an HTML document that end inside a `<pre>` or
+/// `<textarea>` element.
+///
+/// Tags are matched case-insensitively. Nested elements are counted, so a
+/// region ends with the closing tag of the outermost one; a stray closing tag
+/// outside any region is ignored.
+pub fn html_preformatted(content: &str) -> Vec<bool> {
+    let mut depth = 0usize;
+    content
+        .lines()
+        .map(|line| {
+            let lower = line.to_ascii_lowercase();
+            let mut rest = lower.as_str();
+            while let Some(pos) = rest.find('<') {
+                rest = &rest[pos + 1..];
+                let (closing, name) = match rest.strip_prefix('/') {
+                    Some(name) => (true, name),
+                    None => (false, rest),
+                };
+                if PREFORMATTED_TAGS
+                    .iter()
+                    .any(|tag| starts_with_tag(name, tag))
+                {
+                    depth = if closing {
+                        depth.saturating_sub(1)
+                    } else {
+                        depth + 1
+                    };
+                }
+            }
+            depth > 0
+        })
+        .collect()
+}
+
+/// Whether `text` starts with the tag name `tag`, and not a longer name
+/// such as `prefix` for `pre`.
+fn starts_with_tag(text: &str, tag: &str) -> bool {
+    text.strip_prefix(tag).is_some_and(|rest| {
+        rest.chars()
+            .next()
+            .is_none_or(|c| c == '>' || c == '/' || c.is_ascii_whitespace())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_literal_block_is_protected() {
+        let yaml = "key: value  \nscript: |\n  echo hi  \n\n  done  \nnext: 1  \n";
+        assert_eq!(
+            yaml_block_scalars(yaml),
+            vec![false, false, true, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_yaml_folded_block_in_list_with_modifiers() {
+        let yaml = "items:\n  - >-\n    folded  \n    text  \n  - plain  \n";
+        assert_eq!(
+            yaml_block_scalars(yaml),
+            vec![false, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_yaml_block_header_detection() {
+        assert!(is_block_scalar_header("key: |"));
+        assert!(is_block_scalar_header("key: |+ # keep"));
+        assert!(is_block_scalar_header("  - >2-"));
+        assert!(is_block_scalar_header("--- |"));
+        assert!(!is_block_scalar_header("key: a | b"));
+        assert!(!is_block_scalar_header("key: '|'"));
+        assert!(!is_block_scalar_header("key: value"));
+        assert!(!is_block_scalar_header("key: |++"));
+    }
+
+    #[test]
+    fn test_shell_quoted_and_unquoted_heredocs_are_protected() {
+        let script = "cat <<'EOF'  \n  data  \nEOF\necho ok  \ncat <<END\n$HOME  \nEND\n";
+        assert_eq!(
+            shell_heredocs(script),
+            vec![false, true, false, false, false, true, false]
+        );
+    }
+
+    #[test]
+    fn test_shell_heredoc_tab_stripping_and_stacked_heredocs() {
+        let script = "if x; then\n\tcat <<-\"A\" <<B\n\tone  \n\tA\ntwo  \nB\nfi  \n";
+        assert_eq!(
+            shell_heredocs(script),
+            vec![false, false, true, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn test_shell_heredoc_redirection_detection() {
+        let delimiters = |line| -> Vec<String> {
+            heredoc_redirections(line)
+                .into_iter()
+                .map(|heredoc| heredoc.delimiter)
+                .collect()
+        };
+        assert_eq!(delimiters("cat << 'E O F' > out"), vec!["E O F"]);
+        assert_eq!(delimiters("cat <<\\EOF;"), vec!["EOF"]);
+        assert_eq!(delimiters("cat <<<\"$x\""), Vec::<String>::new());
+        assert_eq!(delimiters("echo '<<EOF' \"<<X\""), Vec::<String>::new());
+        assert_eq!(delimiters("echo hi # cat <<EOF"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_csv_multiline_quoted_fields() {
+        let csv = "id,note\n1,\"ends in spaces   \n  and \"\"quotes\"\" \"  \n2,plain  \n";
+        assert_eq!(csv_quoted_line_ends(csv), vec![false, true, false, false]);
+        assert_eq!(csv_quoted_line_ends("a,\"b  \",c  \n"), vec![false]);
+    }
+
+    #[test]
+    fn test_html_preformatted_regions() {
+        let html = "<p>intro</p>  \n<PRE class=\"x\">code  \nmore  \n</Pre>  \n<textarea>\ntyped  \n</textarea>\n";
+        assert_eq!(
+            html_preformatted(html),
+            vec![false, true, true, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_html_preformatted_nesting_and_lookalikes() {
+        let html = "<pre>\n<pre>inner  \n</pre>  \nstill  \n</pre>\n</pre>\n<prefix>  \n";
+        assert_eq!(
+            html_preformatted(html),
+            vec![true, true, true, true, false, false, false]
+        );
+    }
+}